@@ -4,6 +4,11 @@ extern crate tonic_prost_build;
 #[allow(deprecated)]
 fn main() {
     println!("cargo:rerun-if-changed=../../../proto");
+    // Builds without a system protoc use the vendored one
+    if std::env::var_os("PROTOC").is_none() {
+        let protoc = protoc_bin_vendored::protoc_bin_path().unwrap();
+        std::env::set_var("PROTOC", protoc);
+    }
     let config = tonic_prost_build::configure();
     config
         .protoc_arg("--experimental_allow_proto3_optional")
@@ -1,6 +1,5 @@
 use anyhow::Result;
 use spn_coordinator::server::HttpServer;
-use logger;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -1,9 +1,25 @@
 use anyhow::Result;
-use spn_coordinator::client::run_client;
-use spn_coordinator::server::run_server;
+use clap::Parser;
+use spn_coordinator::client::{run_client, DemoArgs};
+use spn_coordinator::server::{run_server, ConfigArgs, CoordinatorConfig};
 use tokio::sync::mpsc;
 use tokio::signal;
-use logger;
+
+/// Self-hosted coordinator of the SP1 prover network
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    config: ConfigArgs,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum Command {
+    /// Upload an ELF to a running coordinator and register it as a program
+    Demo(DemoArgs),
+}
 
 // Initialize rustls crypto provider
 fn init_crypto_provider() {
@@ -42,52 +58,34 @@ async fn shutdown_signal() {
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let cli = Cli::parse();
+
     // Initialize logger
     logger::init();
 
     // Initialize crypto provider before any TLS operations
     init_crypto_provider();
 
-    tracing::info!("ProverNetwork gRPC - Server/Client Architecture");
+    if let Some(Command::Demo(args)) = cli.command {
+        // Talks to an already running coordinator, nothing is started here
+        return run_client(&args).await;
+    }
+
+    tracing::info!("ProverNetwork gRPC - Server");
     tracing::info!("===================================================");
-    
+
+    let config = CoordinatorConfig::load(&cli.config)?;
+
     // Create shutdown channel
     let (shutdown_tx, shutdown_rx) = mpsc::channel::<()>(1);
-    
-    // Spawn server task that runs in background
-    let server_handle = tokio::spawn(async move {
-        if let Err(e) = run_server(shutdown_rx).await {
-            tracing::error!("Server error: {}", e);
-        }
-    });
-    
-    // Spawn client task
-    let client_handle = tokio::spawn(async move {
-        if let Err(e) = run_client().await {
-            tracing::error!("Client error: {}", e);
-        }
-    });
-    
+
     // Spawn signal handler task
-    let signal_handle = tokio::spawn(async move {
+    tokio::spawn(async move {
         shutdown_signal().await;
         tracing::debug!("Sending shutdown signal to server...");
         let _ = shutdown_tx.send(()).await;
     });
-    
-    // Wait for client to finish
-    let _ = client_handle.await;
-    tracing::info!("Client completed. Server continues running in background...");
-    tracing::info!("Press Ctrl+C to gracefully shutdown the server");
-    
-    // Wait for shutdown signal
-    let _ = signal_handle.await;
-    
-    // Give the server a moment to process the shutdown signal
-    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-    
-    // Wait for server to finish gracefully
-    let _ = server_handle.await;
-    
-    Ok(())
+
+    // Run the server until the shutdown signal
+    run_server(config, shutdown_rx).await
 }
@@ -4,18 +4,29 @@ use tonic::{Request, Response, Status, transport::{Channel, Endpoint, ClientTlsC
 use prost::Message;
 use std::time::Duration;
 use ethers::{utils::keccak256};
-use ethers::signers::{LocalWallet};
+use ethers::signers::{LocalWallet, Signer};
+use std::path::PathBuf;
 use std::str::FromStr;
 
-/// The zkvm ELF binaries.
-pub const AGGREGATION_ELF: &[u8] = include_bytes!("./elf/aggregation-elf");
-pub const RANGE_ELF_BUMP: &[u8] = include_bytes!("./elf/range-elf-bump");
-pub const RANGE_ELF_EMBEDDED: &[u8] = include_bytes!("./elf/range-elf-embedded");
-pub const CELESTIA_RANGE_ELF_EMBEDDED: &[u8] =
-    include_bytes!("./elf/celestia-range-elf-embedded");
-// TODO: Update to EigenDA Range ELF Embedded
-pub const EIGENDA_RANGE_ELF_EMBEDDED: &[u8] = include_bytes!("./elf/range-elf-embedded");
-
+/// Arguments of the end-to-end demo: upload an ELF and register it as a program
+#[derive(Debug, Clone, clap::Args)]
+pub struct DemoArgs {
+    /// gRPC endpoint of the coordinator
+    #[arg(long, default_value = "http://127.0.0.1:50051")]
+    pub endpoint: String,
+    /// Hex private key signing the demo requests
+    #[arg(long, env = "SPN_DEMO_PRIVATE_KEY")]
+    pub private_key: String,
+    /// ELF of the program to upload, e.g. src/client/elf/aggregation-elf
+    #[arg(long)]
+    pub elf: PathBuf,
+    /// Hex verification key hash of the ELF, `vk.bytes32()` of the SP1 SDK
+    #[arg(long)]
+    pub vk_hash: String,
+    /// File holding the bincode serialized verification key of the ELF
+    #[arg(long)]
+    pub vk: PathBuf,
+}
 
 /// Real gRPC client that makes actual gRPC calls
 pub struct ProverNetworkClient {
@@ -129,6 +140,13 @@ impl ProverNetworkClient {
     ) -> Result<Response<CreateProgramResponse>, Status> {
         self.client.create_program(Request::new(request)).await
     }
+
+    pub async fn get_nonce(
+        &mut self,
+        request: GetNonceRequest,
+    ) -> Result<Response<GetNonceResponse>, Status> {
+        self.client.get_nonce(Request::new(request)).await
+    }
 }
 
 async fn sign_body(wallet: &LocalWallet, encoded_message: Vec<u8>) -> anyhow::Result<Vec<u8>> {
@@ -147,57 +165,64 @@ async fn sign_body(wallet: &LocalWallet, encoded_message: Vec<u8>) -> anyhow::Re
     Ok(sig.to_vec())
 }
 
-pub async fn create_program_request(program_uri: String) -> anyhow::Result<CreateProgramRequest> {
+pub async fn create_program_request(wallet: &LocalWallet, vk_hash: Vec<u8>, vk: Vec<u8>, program_uri: String, nonce: u64) -> anyhow::Result<CreateProgramRequest> {
     let program = rpc_types::CreateProgramRequestBody {
-        vk_hash: hex::decode("005d763c1b4e00563d156f9ba8cc60561014267a5d3f5f16e2b8a47fa9dfe173").unwrap_or_default(),
-        vk: hex::decode("18c19a61c29c213edfea9e0e5f7b35610f968f43282c5002be4fd123980b3a4644a92d00fecded6ac7efd272fca32d3f487d864ef12bf638be069326153b79650edd32370c739032ac70962f7b08ef1376627c701343d63742584c2c0200000000000000070000000000000050726f6772616d1400000000000000010000000e0000000000000000001000000000000400000000000000427974651000000000000000010000000b0000000000000000000100000000000200000000000000070000000000000050726f6772616d00000000000000000400000000000000427974650100000000000000").unwrap_or_default(),
-        program_uri: program_uri,
-        nonce: 0,
+        vk_hash,
+        vk,
+        program_uri,
+        nonce,
     };
 
     let mut buf = Vec::new();
-    let wallet = LocalWallet::from_str("0xe5d76acbffb5be6d87002e2cd5622b6dfe715f73ac60c613f14ba2d3f735c20b")?;
     program.encode(&mut buf).expect("prost encode failed");
-    let signature = sign_body(&wallet, buf).await?;
+    let signature = sign_body(wallet, buf).await?;
     let request = rpc_types::CreateProgramRequest {
         format: MessageFormat::Json as i32,
-        signature: signature,
+        signature,
         body: Some(program),
     };
     
-    return Ok(request);
+    Ok(request)
 }
-pub async fn create_artifact_request(artifact_type: ArtifactType) -> anyhow::Result<CreateArtifactRequest> {
+pub async fn create_artifact_request(wallet: &LocalWallet, artifact_type: ArtifactType) -> anyhow::Result<CreateArtifactRequest> {
+    // The server expects a signature of the pre-defined "create_artifact" message
+    let signature = sign_body(wallet, b"create_artifact".to_vec()).await?;
     let request = CreateArtifactRequest {
+        signature,
         artifact_type: artifact_type as i32,
-        ..Default::default()
     };
     
     Ok(request)
 }
 
-/// Client function that connects to the server
-pub async fn run_client() -> Result<()> {
+/// Upload an ELF to a running coordinator and register it as a program
+pub async fn run_client(args: &DemoArgs) -> Result<()> {
     tracing::info!("=== Starting Client ===");
-    
-    // Wait a bit for server to start
-    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-    
-    let mut prover_network_client = ProverNetworkClient::new("http://127.0.0.1:50051".to_string()).await
+
+    let wallet = LocalWallet::from_str(&args.private_key)?;
+    let artifact_bytes = std::fs::read(&args.elf)
+        .map_err(|e| anyhow::anyhow!("Failed to read ELF {}: {}", args.elf.display(), e))?;
+    // The program is registered under the verification key of the ELF, which the SP1 SDK computes
+    let vk_hash = hex::decode(args.vk_hash.trim().trim_start_matches("0x"))
+        .map_err(|e| anyhow::anyhow!("Invalid verification key hash {}: {}", args.vk_hash, e))?;
+    let vk = std::fs::read(&args.vk)
+        .map_err(|e| anyhow::anyhow!("Failed to read verification key {}: {}", args.vk.display(), e))?;
+
+    let mut prover_network_client = ProverNetworkClient::new(args.endpoint.clone()).await
         .map_err(|e| {
             tracing::error!("Detailed prover_network_client creation error: {:?}", e);
             anyhow::anyhow!("Failed to create prover_network_client: {}", e)
         })?;
     
     // Create artifact service client
-    let mut artifact_client = ArtifactServiceClient::new("http://127.0.0.1:50051".to_string()).await
+    let mut artifact_client = ArtifactServiceClient::new(args.endpoint.clone()).await
         .map_err(|e| {
             tracing::error!("Detailed artifact_client creation error: {:?}", e);
             anyhow::anyhow!("Failed to create artifact_client: {}", e)
         })?;
     
     let artifact_type = ArtifactType::Program;
-    let artifact_request = create_artifact_request(artifact_type).await?;
+    let artifact_request = create_artifact_request(&wallet, artifact_type).await?;
     
     let response_inner = match artifact_client.create_artifact(artifact_request).await {
         Ok(response) => {
@@ -214,15 +239,13 @@ pub async fn run_client() -> Result<()> {
     };
     
     // Upload the artifact using the presigned URL
-    let artifact_bytes = AGGREGATION_ELF;
     tracing::info!("Uploading artifact ({} bytes) to presigned URL...", artifact_bytes.len());
 
-    let put_url = response_inner.artifact_presigned_url.clone().replace("spn-coordinator-001", "localhost");
     let client = reqwest::Client::new();
     let upload_response = client
-        .put(put_url.clone())
+        .put(response_inner.artifact_presigned_url.clone())
         .header("Content-Type", "application/binary")
-        .body(bincode::serialize(artifact_bytes)?)
+        .body(bincode::serialize(&artifact_bytes)?)
         .send()
         .await
         .map_err(|e| anyhow::anyhow!("Failed to upload artifact: {}", e))?;
@@ -234,8 +257,10 @@ pub async fn run_client() -> Result<()> {
         tracing::error!("Response: {:?}", upload_response.text().await);
     }
 
-    // Create a request
-    let request = create_program_request(response_inner.artifact_presigned_url.clone()).await?;
+    // Create a request, signed with the next nonce of the demo account
+    let address = wallet.address().as_bytes().to_vec();
+    let nonce = prover_network_client.get_nonce(GetNonceRequest { address }).await?.into_inner().nonce;
+    let request = create_program_request(&wallet, vk_hash, vk, response_inner.artifact_presigned_url.clone(), nonce).await?;
     
     tracing::info!("Client sending proof request ");
     // let response = client.request_proof(request).await?;
@@ -243,9 +268,6 @@ pub async fn run_client() -> Result<()> {
     let response_inner = response.into_inner();
     
     tracing::info!("Client create program response: TX Hash = {}", hex::encode(&response_inner.tx_hash));
-    
-    // Wait between requests
-    tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-    
+
     Ok(())
 }
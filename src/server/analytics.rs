@@ -0,0 +1,290 @@
+use chrono::{DateTime, SecondsFormat};
+use rpc_types::*;
+
+use crate::server::auction::parse_amount;
+use crate::server::ledger::request_cost;
+use crate::storage::{Storage, StorageError};
+
+/// Graphs cover this many days when the request sets no range
+pub const DEFAULT_RANGE_DAYS: u64 = 30;
+/// Longest range a graph can cover
+const MAX_RANGE_DAYS: u64 = 366;
+/// Ranges up to this many days are graphed per hour, longer ones per day
+const HOURLY_RANGE_DAYS: u64 = 2;
+
+/// Storage flag set once the counters were rebuilt from the stored requests and programs
+const BACKFILL_FLAG: &[u8] = b"analytics_backfill";
+
+const HOUR: u64 = 3600;
+const DAY: u64 = 86400;
+
+/// Buckets the counters are aggregated in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Granularity {
+    Total,
+    Day,
+    Hour,
+}
+
+impl Granularity {
+    fn tag(self) -> u8 {
+        match self {
+            Self::Total => b't',
+            Self::Day => b'd',
+            Self::Hour => b'h',
+        }
+    }
+
+    /// Start of the bucket `at` falls in
+    fn bucket(self, at: u64) -> u64 {
+        match self {
+            Self::Total => 0,
+            Self::Day => at - at % DAY,
+            Self::Hour => at - at % HOUR,
+        }
+    }
+}
+
+/// Counters of one bucket
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counters {
+    /// Fulfilled proof requests
+    pub proofs: u128,
+    /// Created programs
+    pub programs: u128,
+    /// Prover gas units used by the fulfilled requests
+    pub gas: u128,
+    pub cycles: u128,
+    /// Credits paid to the provers of the fulfilled requests
+    pub rewards: u128,
+    /// Auctions that found a prover
+    pub auctions_won: u128,
+    /// Auctions that expired without a prover
+    pub auctions_lost: u128,
+}
+
+impl Counters {
+    fn to_vec(self) -> Vec<u128> {
+        vec![self.proofs, self.programs, self.gas, self.cycles, self.rewards, self.auctions_won, self.auctions_lost]
+    }
+
+    fn from_slice(counters: &[u128]) -> Self {
+        let at = |i: usize| counters.get(i).copied().unwrap_or(0);
+        Self {
+            proofs: at(0),
+            programs: at(1),
+            gas: at(2),
+            cycles: at(3),
+            rewards: at(4),
+            auctions_won: at(5),
+            auctions_lost: at(6),
+        }
+    }
+}
+
+/// Proof request metrics, aggregated as they happen.
+///
+/// Every event adds to the total, daily and hourly buckets of the network and
+/// of each address involved, so metrics and graphs are read from a handful of
+/// buckets instead of scanning the stored requests.
+#[derive(Debug, Clone, Default)]
+pub struct Analytics {
+    storage: Storage,
+}
+
+impl Analytics {
+    pub fn new(storage: Storage) -> Self {
+        Self { storage }
+    }
+
+    /// Count a fulfilled request for its requester and fulfiller
+    pub fn record_proof(&self, request: &ProofRequest, now: u64) -> Result<(), StorageError> {
+        let escrowed = request.deduction_amount.as_deref().and_then(parse_amount).unwrap_or(0);
+        let counters = Counters {
+            proofs: 1,
+            gas: request.gas_used.unwrap_or(0) as u128,
+            cycles: request.cycles.unwrap_or(0) as u128,
+            rewards: request_cost(request, escrowed).1,
+            ..Default::default()
+        };
+        self.record(&[&request.requester, request.fulfiller.as_deref().unwrap_or_default()], counters, now)
+    }
+
+    /// Count a new program for its owner
+    pub fn record_program(&self, program: &Program, now: u64) -> Result<(), StorageError> {
+        self.record(&[&program.owner], Counters { programs: 1, ..Default::default() }, now)
+    }
+
+    /// Count an auction that found a prover, or that expired without one
+    pub fn record_auction(&self, request: &ProofRequest, won: bool, now: u64) -> Result<(), StorageError> {
+        let counters = if won { Counters { auctions_won: 1, ..Default::default() } } else { Counters { auctions_lost: 1, ..Default::default() } };
+        self.record(&[&request.requester, request.fulfiller.as_deref().unwrap_or_default()], counters, now)
+    }
+
+    /// Rebuild the counters from the stored requests and programs, once per database.
+    /// Returns false if they were rebuilt already.
+    pub fn backfill(&self) -> Result<bool, StorageError> {
+        if self.storage.has_meta_flag(BACKFILL_FLAG)? {
+            return Ok(false);
+        }
+        // Counters recorded before the backfill cover stored events too, so they are started over
+        self.storage.clear_counters()?;
+        for program in self.storage.programs()? {
+            self.record_program(&program, program.created_at)?;
+        }
+        for (request, _) in self.storage.proof_requests()? {
+            if request.fulfillment_status == FulfillmentStatus::Fulfilled as i32 {
+                self.record_proof(&request, request.updated_at)?;
+            }
+            if request.strategy != FulfillmentStrategy::Auction as i32 {
+                continue;
+            }
+            match self.storage.get_assigned_at(&request.request_id)? {
+                Some(assigned_at) => self.record_auction(&request, true, assigned_at)?,
+                None if request.fulfillment_status == FulfillmentStatus::Unfulfillable as i32 => {
+                    self.record_auction(&request, false, request.updated_at)?
+                }
+                None => {}
+            }
+        }
+        self.storage.set_meta_flag(BACKFILL_FLAG)?;
+        Ok(true)
+    }
+
+    fn record(&self, addresses: &[&[u8]], counters: Counters, now: u64) -> Result<(), StorageError> {
+        let mut scopes: Vec<&[u8]> = vec![&[]];
+        for address in addresses {
+            if !address.is_empty() && !scopes.contains(address) {
+                scopes.push(address);
+            }
+        }
+        let deltas = counters.to_vec();
+        for scope in scopes {
+            for granularity in [Granularity::Total, Granularity::Day, Granularity::Hour] {
+                self.storage.add_counters(&bucket_key(granularity, scope, granularity.bucket(now)), &deltas)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// All time counters of the network, or of an address
+    pub fn totals(&self, address: &[u8]) -> Result<Counters, StorageError> {
+        Ok(Counters::from_slice(&self.storage.get_counters(&bucket_key(Granularity::Total, address, 0))?))
+    }
+
+    /// Counters of the last `range_days` up to `now`, oldest first. Buckets are hours for short ranges and days otherwise.
+    pub fn series(&self, address: &[u8], range_days: Option<u64>, now: u64) -> Result<Vec<(u64, Counters)>, StorageError> {
+        let range_days = range_days.filter(|days| *days > 0).unwrap_or(DEFAULT_RANGE_DAYS).min(MAX_RANGE_DAYS);
+        let (granularity, size) = if range_days <= HOURLY_RANGE_DAYS { (Granularity::Hour, HOUR) } else { (Granularity::Day, DAY) };
+        let count = range_days * DAY / size;
+        let first = granularity.bucket(now).saturating_sub((count - 1) * size);
+
+        let prefix = bucket_prefix(granularity, address);
+        let stored = self.storage.counters_range(&prefix, &bucket_key(granularity, address, first), count as usize)?;
+        let mut stored = stored.into_iter().peekable();
+        let mut series = Vec::with_capacity(count as usize);
+        for i in 0..count {
+            let start = first + i * size;
+            let key = bucket_key(granularity, address, start);
+            let counters = match stored.next_if(|(stored_key, _)| *stored_key == key) {
+                Some((_, counters)) => Counters::from_slice(&counters),
+                None => Counters::default(),
+            };
+            series.push((start, counters));
+        }
+        Ok(series)
+    }
+}
+
+/// Graph points of a series, `value` picks what is graphed from each bucket
+pub fn graph(series: &[(u64, Counters)], value: impl Fn(&Counters) -> u128) -> Vec<GraphData> {
+    series
+        .iter()
+        .map(|(start, counters)| GraphData {
+            timestamp: DateTime::from_timestamp(*start as i64, 0)
+                .map(|at| at.to_rfc3339_opts(SecondsFormat::Secs, true))
+                .unwrap_or_default(),
+            value: value(counters).min(u64::MAX as u128) as u64,
+        })
+        .collect()
+}
+
+/// Buckets of one granularity and scope: the granularity tag, the address length and the address
+fn bucket_prefix(granularity: Granularity, address: &[u8]) -> Vec<u8> {
+    [&[granularity.tag(), address.len() as u8], address].concat()
+}
+
+/// Bucket prefix followed by the bucket start, so the buckets of a scope are ordered by time
+fn bucket_key(granularity: Granularity, address: &[u8], start: u64) -> Vec<u8> {
+    [bucket_prefix(granularity, address).as_slice(), &start.to_be_bytes()].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buckets_and_series() {
+        let analytics = Analytics::new(Storage::default());
+        let (requester, prover) = (vec![1; 20], vec![2; 20]);
+        let request = ProofRequest {
+            requester: requester.clone(),
+            fulfiller: Some(prover.clone()),
+            gas_used: Some(1000),
+            cycles: Some(5000),
+            ..Default::default()
+        };
+        let now = 10 * DAY + 5 * HOUR;
+        analytics.record_proof(&request, now - DAY).unwrap();
+        analytics.record_proof(&request, now).unwrap();
+        analytics.record_program(&Program { owner: requester.clone(), ..Default::default() }, now).unwrap();
+
+        let totals = analytics.totals(&[]).unwrap();
+        assert_eq!((totals.proofs, totals.programs, totals.gas, totals.cycles), (2, 1, 2000, 10000));
+        assert_eq!(analytics.totals(&prover).unwrap().proofs, 2);
+        assert_eq!(analytics.totals(&[3; 20]).unwrap(), Counters::default());
+
+        // A week of days, the last two have a proof each
+        let series = analytics.series(&prover, Some(7), now).unwrap();
+        let proofs: Vec<u128> = series.iter().map(|(_, counters)| counters.proofs).collect();
+        assert_eq!(proofs, vec![0, 0, 0, 0, 0, 1, 1]);
+        assert_eq!(graph(&series, |c| c.proofs)[6].timestamp, "1970-01-11T00:00:00Z");
+
+        // Short ranges are graphed per hour
+        assert_eq!(analytics.series(&[], Some(1), now).unwrap().len(), 24);
+    }
+
+    #[test]
+    fn test_backfill_once() {
+        let storage = Storage::default();
+        let analytics = Analytics::new(storage.clone());
+        let status = GetProofRequestStatusResponse::default();
+        let fulfilled = ProofRequest {
+            request_id: vec![1; 32],
+            requester: vec![1; 20],
+            fulfiller: Some(vec![2; 20]),
+            fulfillment_status: FulfillmentStatus::Fulfilled as i32,
+            strategy: FulfillmentStrategy::Auction as i32,
+            updated_at: DAY,
+            ..Default::default()
+        };
+        storage.put_proof_request(&fulfilled, &status).unwrap();
+        storage.put_assigned_at(&fulfilled.request_id, DAY - HOUR).unwrap();
+        let expired = ProofRequest {
+            request_id: vec![2; 32],
+            fulfillment_status: FulfillmentStatus::Unfulfillable as i32,
+            strategy: FulfillmentStrategy::Auction as i32,
+            ..Default::default()
+        };
+        storage.put_proof_request(&expired, &status).unwrap();
+        storage.put_program(&Program { owner: vec![1; 20], ..Default::default() }).unwrap();
+        // Counted before the backfill, which covers it again
+        analytics.record_program(&Program::default(), DAY).unwrap();
+
+        assert!(analytics.backfill().unwrap());
+        assert!(!analytics.backfill().unwrap());
+        let totals = analytics.totals(&[]).unwrap();
+        assert_eq!((totals.proofs, totals.programs, totals.auctions_won, totals.auctions_lost), (1, 1, 1, 1));
+        assert_eq!(analytics.totals(&[2; 20]).unwrap().proofs, 1);
+    }
+}
@@ -5,11 +5,47 @@ use tokio::sync::Mutex;
 use tonic::{Request, Response, Status};
 use rand::random;
 
+use crate::server::auth::authenticate_artifact_request;
+use crate::server::config::DEFAULT_PUBLIC_URL;
+use crate::storage::S3Presigner;
+
 /// Real gRPC service implementation for ArtifactStore
 #[derive(Debug, Default)]
 pub struct ArtifactStoreServiceImpl {
     /// TODO Store artifacts in memory (in real implementation this would be a database or S3)  
     artifacts: Mutex<HashMap<String, (ArtifactType, String)>>, // artifact_uri -> (type, presigned_url)
+    /// When set, artifacts are uploaded straight to the object store instead of the HTTP server
+    s3: Option<S3Presigner>,
+    /// Accounts allowed to create artifacts
+    allowlist: Vec<Vec<u8>>,
+    /// Let any authenticated account create artifacts, whatever the allowlist
+    allow_all: bool,
+    /// Base URL of our HTTP server as seen by clients
+    public_url: String,
+}
+
+impl ArtifactStoreServiceImpl {
+    pub fn new(s3: Option<S3Presigner>, allowlist: Vec<Vec<u8>>) -> Self {
+        Self {
+            artifacts: Mutex::new(HashMap::new()),
+            s3,
+            allowlist,
+            allow_all: false,
+            public_url: DEFAULT_PUBLIC_URL.to_string(),
+        }
+    }
+
+    /// Accept `create_artifact` from any authenticated account
+    pub fn with_allow_all(mut self, allow_all: bool) -> Self {
+        self.allow_all = allow_all;
+        self
+    }
+
+    /// Base URL of the HTTP server that artifacts are uploaded to when S3 is not used
+    pub fn with_public_url(mut self, public_url: impl Into<String>) -> Self {
+        self.public_url = public_url.into();
+        self
+    }
 }
 
 #[tonic::async_trait]
@@ -26,14 +62,25 @@ impl artifact_store_server::ArtifactStore for ArtifactStoreServiceImpl {
         let artifact_type = ArtifactType::try_from(req.artifact_type)
             .map_err(|_| Status::invalid_argument("Invalid artifact type"))?;
         
-        // TODO: Verify signature for authentication
-        // For now, we'll skip signature verification as it would require the signed message format
-        
+        // The signature covers the pre-defined "create_artifact" message
+        let signer = authenticate_artifact_request(&req.signature)?;
+        if !self.allow_all && !self.allowlist.contains(&signer) {
+            tracing::warn!("ARTIFACT: Rejected create_artifact from {}", hex::encode(&signer));
+            return Err(Status::unauthenticated("Account is not allowed to create artifacts"));
+        }
+
         // Generate unique artifact URI and presigned URL
         let artifact_id = generate_artifact_id();
-        // let artifact_uri = generate_artifact_uri(&artifact_type, &artifact_id);
-        let presigned_url = generate_presigned_url(&artifact_type, &artifact_id);
-        let artifact_uri = presigned_url.clone();
+        let (artifact_uri, presigned_url) = match &self.s3 {
+            Some(s3) => (
+                s3.artifact_uri(&artifact_type, &artifact_id),
+                s3.presign_put(&S3Presigner::artifact_key(&artifact_type, &artifact_id)),
+            ),
+            None => {
+                let presigned_url = generate_presigned_url(&self.public_url, &artifact_type, &artifact_id);
+                (presigned_url.clone(), presigned_url)
+            }
+        };
 
         tracing::info!("ARTIFACT: Generated presigned URL: {}", presigned_url);
         
@@ -54,19 +101,14 @@ impl artifact_store_server::ArtifactStore for ArtifactStoreServiceImpl {
 }
 
 /// Generate a unique artifact identifier
-fn generate_artifact_id() -> String {
+pub(crate) fn generate_artifact_id() -> String {
     let id_bytes = random::<[u8; 16]>();
     hex::encode(id_bytes)
 }
 
-/// Generate an artifact URI based on the ID
-// fn generate_artifact_uri(artifact_type: &ArtifactType, artifact_id: &str) -> String {    
-//     format!("s3://spn-artifacts/{:?}/{}", artifact_type, artifact_id)
-// }
-
 /// Generate a presigned URL for artifact upload
-fn generate_presigned_url(artifact_type: &ArtifactType, artifact_id: &str) -> String {
+pub(crate) fn generate_presigned_url(public_url: &str, artifact_type: &ArtifactType, artifact_id: &str) -> String {
     // Generate a URL pointing to our HTTP server
     // The client will use this URL to PUT the artifact data
-    format!("http://spn-coordinator-001:8082/artifacts/{:?}/{}", artifact_type, artifact_id)
+    format!("{}/artifacts/{:?}/{}", public_url.trim_end_matches('/'), artifact_type, artifact_id)
 }
@@ -0,0 +1,238 @@
+use rpc_types::*;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tonic::Status;
+
+use crate::server::provers::ProverRegistry;
+use crate::server::staking::Staking;
+use crate::storage::{ProofRequestIndex, Storage};
+
+/// Picks the fulfiller of new proof requests according to their strategy.
+///
+/// `Reserved` requests go to the fulfiller reserved for their requester and
+/// `Auction` requests wait for bids (see [`crate::server::auction::Auctioneer`]).
+/// `Hosted` requests go to the least loaded live prover of the hosted pool, or
+/// of every staked prover when there is no pool. They stay `Requested` until a
+/// prover is live, provers waiting for hosted requests keep themselves live
+/// with signed `set_gpu_variant` requests.
+#[derive(Debug, Clone)]
+pub struct Assigner {
+    storage: Storage,
+    registry: ProverRegistry,
+    /// Provers below the minimum stake are not assigned requests
+    staking: Staking,
+    hosted_provers: Vec<Vec<u8>>,
+    /// Rotates the provers tied for the lowest load
+    next_hosted: Arc<AtomicUsize>,
+}
+
+impl Assigner {
+    pub fn new(storage: Storage, registry: ProverRegistry, staking: Staking) -> Self {
+        Self { storage, registry, staking, hosted_provers: Vec::new(), next_hosted: Arc::default() }
+    }
+
+    /// Provers fulfilling `Hosted` requests
+    pub fn with_hosted_provers(mut self, provers: Vec<Vec<u8>>) -> Self {
+        self.hosted_provers = provers;
+        self
+    }
+
+    pub fn with_registry(mut self, registry: ProverRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    pub fn with_staking(mut self, staking: Staking) -> Self {
+        self.staking = staking;
+        self
+    }
+
+    /// Fulfiller of a new request, `None` when it waits for bids or for a live prover
+    pub fn assign(&self, strategy: i32, requester: &[u8], now: u64) -> Result<Option<Vec<u8>>, Status> {
+        match FulfillmentStrategy::try_from(strategy) {
+            Ok(FulfillmentStrategy::Auction) => Ok(None),
+            Ok(FulfillmentStrategy::Reserved) => {
+                let reservation = self
+                    .storage
+                    .get_reservation(requester)?
+                    .ok_or_else(|| Status::failed_precondition("Requester has no reservation"))?;
+                self.staking.require_min_stake(&reservation.fulfiller)?;
+                Ok(Some(reservation.fulfiller))
+            }
+            // Requests without a strategy have always been hosted
+            Ok(FulfillmentStrategy::Hosted | FulfillmentStrategy::UnspecifiedFulfillmentStrategy) => {
+                let candidates = self.hosted_candidates(now)?;
+                Ok(self.pick(&candidates, &self.loads(&candidates)?))
+            }
+            Err(_) => Err(Status::invalid_argument(format!("Invalid fulfillment strategy: {}", strategy))),
+        }
+    }
+
+    /// Assign the hosted requests still waiting for a live prover, returns how many were assigned
+    pub fn assign_pending(&self, now: u64) -> Result<usize, Status> {
+        let candidates = self.hosted_candidates(now)?;
+        if candidates.is_empty() {
+            return Ok(0);
+        }
+        let pending: Vec<ProofRequest> = self
+            .storage
+            .proof_requests_with_status(FulfillmentStatus::Requested)?
+            .into_iter()
+            .map(|(request, _)| request)
+            .filter(is_pending_hosted)
+            .collect();
+        if pending.is_empty() {
+            return Ok(0);
+        }
+        let mut loads = self.loads(&candidates)?;
+        let mut assigned = 0;
+        for request in pending {
+            let Some(prover) = self.pick(&candidates, &loads) else {
+                break;
+            };
+            let updated = self.storage.update_proof_request(&request.request_id, |request, status| {
+                if !is_pending_hosted(request) {
+                    return Ok::<_, Status>(false);
+                }
+                request.fulfiller = Some(prover.clone());
+                request.fulfillment_status = FulfillmentStatus::Assigned as i32;
+                request.updated_at = now;
+                status.fulfillment_status = request.fulfillment_status;
+                Ok(true)
+            })?;
+            if updated {
+                tracing::info!("ASSIGNMENT: Request {} assigned to {}", hex::encode(&request.request_id), hex::encode(&prover));
+                *loads.entry(prover).or_default() += 1;
+                assigned += 1;
+            }
+        }
+        Ok(assigned)
+    }
+
+    /// Live provers of the pool, or live known provers without a pool.
+    /// Only provers holding the minimum stake are candidates. Without a pool,
+    /// any signer can be known, so they also need some stake.
+    fn hosted_candidates(&self, now: u64) -> Result<Vec<Vec<u8>>, Status> {
+        let live = self.registry.live_provers(now)?;
+        let pooled = !self.hosted_provers.is_empty();
+        let candidates = if pooled {
+            self.hosted_provers.iter().filter(|p| live.contains(p)).cloned().collect()
+        } else {
+            live
+        };
+        let mut staked = Vec::with_capacity(candidates.len());
+        for prover in candidates {
+            if self.staking.has_min_stake(&prover)? && (pooled || self.staking.stake_of(&prover)? > 0) {
+                staked.push(prover);
+            }
+        }
+        Ok(staked)
+    }
+
+    /// Number of requests each candidate has been assigned and not fulfilled yet
+    fn loads(&self, candidates: &[Vec<u8>]) -> Result<HashMap<Vec<u8>, usize>, Status> {
+        let mut loads = HashMap::new();
+        for prover in candidates {
+            loads.insert(prover.clone(), self.storage.count_proof_requests(&ProofRequestIndex::AssignedTo(prover.clone()))?);
+        }
+        Ok(loads)
+    }
+
+    /// Least loaded candidate, ties are taken in turn
+    fn pick(&self, candidates: &[Vec<u8>], loads: &HashMap<Vec<u8>, usize>) -> Option<Vec<u8>> {
+        if candidates.is_empty() {
+            return None;
+        }
+        let start = self.next_hosted.fetch_add(1, Ordering::Relaxed);
+        (0..candidates.len())
+            .map(|i| &candidates[(start + i) % candidates.len()])
+            .min_by_key(|prover| loads.get(*prover).copied().unwrap_or(0))
+            .cloned()
+    }
+
+    /// Periodically assign the hosted requests waiting for a live prover
+    pub fn spawn(self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let now = chrono::Utc::now().timestamp() as u64;
+                if let Err(e) = self.assign_pending(now) {
+                    tracing::error!("ASSIGNMENT: Failed to assign pending requests: {}", e.message());
+                }
+            }
+        })
+    }
+}
+
+fn is_pending_hosted(request: &ProofRequest) -> bool {
+    let hosted = request.strategy == FulfillmentStrategy::Hosted as i32
+        || request.strategy == FulfillmentStrategy::UnspecifiedFulfillmentStrategy as i32;
+    hosted && request.fulfillment_status == FulfillmentStatus::Requested as i32 && request.fulfiller.is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::ledger::Ledger;
+
+    #[test]
+    fn test_assign_by_strategy() {
+        let storage = Storage::default();
+        let registry = ProverRegistry::new(storage.clone());
+        let staking = Staking::new(storage.clone(), Ledger::new(storage.clone()));
+        let requester = vec![1; 20];
+        let assigner = Assigner::new(storage.clone(), registry.clone(), staking);
+
+        // Nobody to host the request yet
+        assert_eq!(assigner.assign(FulfillmentStrategy::Auction as i32, &requester, 100).unwrap(), None);
+        assert_eq!(assigner.assign(FulfillmentStrategy::Hosted as i32, &requester, 100).unwrap(), None);
+
+        let reserved = assigner.assign(FulfillmentStrategy::Reserved as i32, &requester, 100);
+        assert_eq!(reserved.unwrap_err().code(), tonic::Code::FailedPrecondition);
+        storage
+            .put_reservation(&Reservation { requester: requester.clone(), fulfiller: vec![4; 20], ..Default::default() })
+            .unwrap();
+        assert_eq!(assigner.assign(FulfillmentStrategy::Reserved as i32, &requester, 100).unwrap(), Some(vec![4; 20]));
+
+        // Pool provers are only used while they are live, other live provers never
+        let pooled = assigner.clone().with_hosted_provers(vec![vec![2; 20]]);
+        registry.record_activity(&[3; 20], 100, |_| {}).unwrap();
+        assert_eq!(pooled.assign(FulfillmentStrategy::Hosted as i32, &requester, 100).unwrap(), None);
+        registry.record_activity(&[2; 20], 100, |_| {}).unwrap();
+        assert_eq!(pooled.assign(FulfillmentStrategy::Hosted as i32, &requester, 100).unwrap(), Some(vec![2; 20]));
+        assert_eq!(pooled.assign(FulfillmentStrategy::Hosted as i32, &requester, 1000).unwrap(), None);
+    }
+
+    #[test]
+    fn test_pending_requests_go_to_least_loaded_live_prover() {
+        let storage = Storage::default();
+        let registry = ProverRegistry::new(storage.clone());
+        let staking = Staking::new(storage.clone(), Ledger::new(storage.clone()));
+        let assigner = Assigner::new(storage.clone(), registry.clone(), staking.clone());
+        for id in 1..=3 {
+            let request = ProofRequest {
+                request_id: vec![id; 32],
+                strategy: FulfillmentStrategy::Hosted as i32,
+                fulfillment_status: FulfillmentStatus::Requested as i32,
+                ..Default::default()
+            };
+            storage.put_proof_request(&request, &GetProofRequestStatusResponse::default()).unwrap();
+        }
+        assert_eq!(assigner.assign_pending(100).unwrap(), 0);
+
+        registry.record_activity(&[7; 20], 100, |_| {}).unwrap();
+        registry.record_activity(&[8; 20], 100, |_| {}).unwrap();
+        // Live provers outside a pool need some stake
+        assert_eq!(assigner.assign_pending(100).unwrap(), 0);
+        staking.stake(&[7; 20], 1, &[7; 32], 100).unwrap();
+        staking.stake(&[8; 20], 1, &[8; 32], 100).unwrap();
+        assert_eq!(assigner.assign_pending(100).unwrap(), 3);
+
+        let loads = assigner.loads(&[vec![7; 20], vec![8; 20]]).unwrap();
+        assert_eq!(loads.values().sum::<usize>(), 3);
+        assert!(loads.values().all(|load| *load >= 1));
+    }
+}
@@ -0,0 +1,271 @@
+use rpc_types::*;
+use std::time::Duration;
+use tonic::Status;
+
+use crate::server::analytics::Analytics;
+use crate::server::deadline::is_past_deadline;
+use crate::storage::Storage;
+
+/// Runs the proof contest of requests using the `Auction` fulfillment strategy.
+///
+/// Requests stay `Requested` while provers bid on them. Once `min_auction_period`
+/// has elapsed the lowest bid wins, its prover becomes the `fulfiller` and the
+/// request moves to `Assigned`.
+#[derive(Debug, Clone, Default)]
+pub struct Auctioneer {
+    storage: Storage,
+    analytics: Analytics,
+}
+
+impl Auctioneer {
+    pub fn new(storage: Storage) -> Self {
+        Self { analytics: Analytics::new(storage.clone()), storage }
+    }
+
+    /// Record the bid of `prover` on a request, replacing any previous bid of the same prover.
+    /// Returns whether it is the first bid of the prover on the request.
+    pub fn place_bid(&self, request_id: &[u8], prover: &[u8], amount: &str, now: u64) -> Result<bool, Status> {
+        let bid_amount = parse_amount(amount).ok_or_else(|| Status::invalid_argument(format!("Invalid bid amount: {}", amount)))?;
+        // The winning bid becomes the gas price of the request
+        if u64::try_from(bid_amount).is_err() {
+            return Err(Status::invalid_argument(format!("Bid amount {} is too large", bid_amount)));
+        }
+        self.storage.update_proof_request_with_writes(request_id, |request, _, writes| {
+            if request.strategy != FulfillmentStrategy::Auction as i32 {
+                return Err(Status::failed_precondition("Request does not use the auction strategy"));
+            }
+            if request.fulfillment_status != FulfillmentStatus::Requested as i32 {
+                return Err(Status::failed_precondition("Request is not open for bids"));
+            }
+            if request.deadline != 0 && now >= request.deadline {
+                return Err(Status::failed_precondition("Request is past its deadline"));
+            }
+            if !request.whitelist.is_empty() && !request.whitelist.iter().any(|p| p.as_slice() == prover) {
+                return Err(Status::permission_denied("Prover is not in the request whitelist"));
+            }
+            if let Some(max_price) = request.max_price_per_pgu.as_deref().and_then(parse_amount) {
+                if bid_amount > max_price {
+                    return Err(Status::invalid_argument(format!("Bid {} is above the max price per PGU {}", bid_amount, max_price)));
+                }
+            }
+
+            let first_bid = !self.storage.bids(request_id)?.iter().any(|bid| bid.bidder == prover);
+            writes.put_bid(request_id, &BidHistory {
+                bidder: prover.to_vec(),
+                amount: bid_amount.to_string(),
+                created_at: now,
+                bidder_name: None,
+            });
+            Ok(first_bid)
+        })
+    }
+
+    /// Close the auction of a request and assign it to the best bidder.
+    /// If `winner` is not empty it must match the best bid.
+    pub fn settle(&self, request_id: &[u8], winner: &[u8], now: u64) -> Result<Vec<u8>, Status> {
+        let (request, first) = self.storage.update_proof_request_with_writes(request_id, |request, status, writes| {
+            if request.strategy != FulfillmentStrategy::Auction as i32 {
+                return Err(Status::failed_precondition("Request does not use the auction strategy"));
+            }
+            if request.fulfillment_status != FulfillmentStatus::Requested as i32 {
+                return Err(Status::failed_precondition("Request auction is already settled"));
+            }
+            if now < auction_end(request) {
+                return Err(Status::failed_precondition("Minimum auction period has not elapsed"));
+            }
+            // Expired requests are left to the deadline reaper
+            if is_past_deadline(request, now) {
+                return Err(Status::failed_precondition("Request is past its deadline"));
+            }
+            let bids = self.storage.bids(request_id)?;
+            let best = best_bid(&bids).ok_or_else(|| Status::failed_precondition("Request has no bids"))?;
+            if !winner.is_empty() && winner != best.bidder.as_slice() {
+                return Err(Status::invalid_argument("Winner is not the best bidder"));
+            }
+
+            let gas_price = best.amount.parse().map_err(|_| Status::internal(format!("Best bid {} is not a gas price", best.amount)))?;
+            request.fulfiller = Some(best.bidder.clone());
+            request.gas_price = Some(gas_price);
+            request.fulfillment_status = FulfillmentStatus::Assigned as i32;
+            request.settlement_status = SettlementStatus::Settled as i32;
+            request.updated_at = now;
+            status.fulfillment_status = request.fulfillment_status;
+            // Auctions re-opened after their prover stalled were counted when first settled
+            let first = self.storage.get_assigned_at(request_id)?.is_none();
+            writes.put_assigned_at(request_id, now);
+            Ok((request.clone(), first))
+        })?;
+        if first {
+            self.analytics.record_auction(&request, true, now)?;
+        }
+        Ok(request.fulfiller.unwrap_or_default())
+    }
+
+    /// Settle every auction whose minimum period has elapsed, whose deadline has not passed and that
+    /// received at least one bid
+    pub fn settle_expired(&self, now: u64) -> Result<usize, Status> {
+        let mut settled = 0;
+        for (request, _) in self.storage.proof_requests_with_status(FulfillmentStatus::Requested)? {
+            if request.strategy != FulfillmentStrategy::Auction as i32
+                || now < auction_end(&request)
+                || is_past_deadline(&request, now)
+                || self.storage.bids(&request.request_id)?.is_empty()
+            {
+                continue;
+            }
+            match self.settle(&request.request_id, &[], now) {
+                Ok(winner) => {
+                    tracing::info!("AUCTION: Request {} assigned to {}", hex::encode(&request.request_id), hex::encode(&winner));
+                    settled += 1;
+                }
+                Err(e) => tracing::warn!("AUCTION: Failed to settle request {}: {}", hex::encode(&request.request_id), e.message()),
+            }
+        }
+        Ok(settled)
+    }
+
+    /// Bids of a request, best bid first. With `winner_first` the assigned fulfiller goes first.
+    pub fn bid_history(&self, request_id: &[u8], winner_first: bool) -> Result<Vec<BidHistory>, Status> {
+        let mut bids = self.storage.bids(request_id)?;
+        bids.sort_by_key(|bid| (parse_amount(&bid.amount).unwrap_or(u128::MAX), bid.created_at));
+        if winner_first {
+            if let Some((request, _)) = self.storage.get_proof_request(request_id)? {
+                if let Some(fulfiller) = request.fulfiller {
+                    bids.sort_by_key(|bid| bid.bidder != fulfiller);
+                }
+            }
+        }
+        Ok(bids)
+    }
+
+    /// Periodically settle the auctions that are ready
+    pub fn spawn(self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let now = chrono::Utc::now().timestamp() as u64;
+                if let Err(e) = self.settle_expired(now) {
+                    tracing::error!("AUCTION: Failed to settle auctions: {}", e.message());
+                }
+            }
+        })
+    }
+}
+
+/// Lowest bid wins, ties go to the earliest bid
+fn best_bid(bids: &[BidHistory]) -> Option<&BidHistory> {
+    bids.iter()
+        .filter_map(|bid| parse_amount(&bid.amount).map(|amount| (amount, bid)))
+        .min_by_key(|(amount, bid)| (*amount, bid.created_at))
+        .map(|(_, bid)| bid)
+}
+
+/// Time the minimum auction period of a request ends, saturating for periods too long to end
+fn auction_end(request: &ProofRequest) -> u64 {
+    request.created_at.saturating_add(request.min_auction_period)
+}
+
+/// Amounts are decimal strings of the smallest unit
+pub(crate) fn parse_amount(amount: &str) -> Option<u128> {
+    amount.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::StorageError;
+
+    fn auction_request(storage: &Storage, whitelist: Vec<Vec<u8>>) -> Vec<u8> {
+        let request = ProofRequest {
+            request_id: vec![1; 32],
+            strategy: FulfillmentStrategy::Auction as i32,
+            fulfillment_status: FulfillmentStatus::Requested as i32,
+            created_at: 100,
+            deadline: 1000,
+            min_auction_period: 10,
+            whitelist,
+            max_price_per_pgu: Some("50".to_string()),
+            ..Default::default()
+        };
+        storage.put_proof_request(&request, &GetProofRequestStatusResponse::default()).unwrap();
+        request.request_id
+    }
+
+    #[test]
+    fn test_auction_lowest_bid_wins() {
+        let storage = Storage::default();
+        let auctioneer = Auctioneer::new(storage.clone());
+        let request_id = auction_request(&storage, vec![]);
+
+        auctioneer.place_bid(&request_id, &[0xa; 20], "30", 101).unwrap();
+        auctioneer.place_bid(&request_id, &[0xb; 20], "20", 102).unwrap();
+        assert_eq!(auctioneer.place_bid(&request_id, &[0xc; 20], "51", 103).unwrap_err().code(), tonic::Code::InvalidArgument);
+
+        // Too early to settle
+        assert_eq!(auctioneer.settle_expired(105).unwrap(), 0);
+        assert_eq!(auctioneer.settle_expired(110).unwrap(), 1);
+
+        let (request, status) = storage.get_proof_request(&request_id).unwrap().unwrap();
+        assert_eq!(request.fulfiller, Some(vec![0xb; 20]));
+        assert_eq!(request.gas_price, Some(20));
+        assert_eq!(status.fulfillment_status, FulfillmentStatus::Assigned as i32);
+
+        let history = auctioneer.bid_history(&request_id, true).unwrap();
+        assert_eq!(history[0].bidder, vec![0xb; 20]);
+        assert_eq!(history.len(), 2);
+
+        // Settling the auction again once re-opened doesn't count it twice
+        storage
+            .update_proof_request(&request_id, |request, status| {
+                request.fulfillment_status = FulfillmentStatus::Requested as i32;
+                status.fulfillment_status = request.fulfillment_status;
+                Ok::<_, StorageError>(())
+            })
+            .unwrap();
+        assert_eq!(auctioneer.settle_expired(120).unwrap(), 1);
+        assert_eq!(Analytics::new(storage.clone()).totals(&[]).unwrap().auctions_won, 1);
+    }
+
+    #[test]
+    fn test_whitelist_is_enforced() {
+        let storage = Storage::default();
+        let auctioneer = Auctioneer::new(storage.clone());
+        let request_id = auction_request(&storage, vec![vec![0xa; 20]]);
+
+        assert!(auctioneer.place_bid(&request_id, &[0xa; 20], "1", 101).is_ok());
+        assert_eq!(auctioneer.place_bid(&request_id, &[0xb; 20], "1", 101).unwrap_err().code(), tonic::Code::PermissionDenied);
+        assert_eq!(auctioneer.settle(&request_id, &[0xb; 20], 120).unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
+
+    #[test]
+    fn test_expired_auctions_are_not_settled() {
+        let storage = Storage::default();
+        let auctioneer = Auctioneer::new(storage.clone());
+        let request_id = auction_request(&storage, vec![]);
+        auctioneer.place_bid(&request_id, &[0xa; 20], "30", 101).unwrap();
+
+        assert_eq!(auctioneer.settle_expired(1000).unwrap(), 0);
+        assert_eq!(auctioneer.settle(&request_id, &[], 1000).unwrap_err().code(), tonic::Code::FailedPrecondition);
+        let (request, _) = storage.get_proof_request(&request_id).unwrap().unwrap();
+        assert_eq!(request.fulfillment_status, FulfillmentStatus::Requested as i32);
+        assert_eq!(request.fulfiller, None);
+    }
+
+    #[test]
+    fn test_bids_above_a_gas_price_are_refused() {
+        let storage = Storage::default();
+        let auctioneer = Auctioneer::new(storage.clone());
+        let request = ProofRequest {
+            request_id: vec![2; 32],
+            strategy: FulfillmentStrategy::Auction as i32,
+            fulfillment_status: FulfillmentStatus::Requested as i32,
+            ..Default::default()
+        };
+        storage.put_proof_request(&request, &GetProofRequestStatusResponse::default()).unwrap();
+
+        let too_large = (u64::MAX as u128 + 1).to_string();
+        assert_eq!(auctioneer.place_bid(&request.request_id, &[0xa; 20], &too_large, 1).unwrap_err().code(), tonic::Code::InvalidArgument);
+        assert!(storage.bids(&request.request_id).unwrap().is_empty());
+    }
+}
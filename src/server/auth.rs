@@ -0,0 +1,255 @@
+use ethers_core::types::Signature;
+use ethers_core::utils::{hash_message, keccak256}; // hash_message adds the EIP-191 prefix
+use prost::Message;
+use rpc_types::*;
+use tonic::Status;
+
+/// Pre-defined message signed by clients calling `create_artifact`
+pub const CREATE_ARTIFACT_MESSAGE: &[u8] = b"create_artifact";
+
+/// A request made of a body signed by the sender
+pub trait SignedRequest {
+    type Body: Message;
+
+    /// Split the request into (format, signature, body)
+    fn into_parts(self) -> (i32, Vec<u8>, Option<Self::Body>);
+
+    /// Build a request from (format, signature, body), as [`Self::into_parts`] splits it
+    fn from_parts(format: i32, signature: Vec<u8>, body: Option<Self::Body>) -> Self;
+
+    /// Account nonce of the sender
+    fn nonce(body: &Self::Body) -> u64;
+
+    /// Domain separator and transaction variant of the body, with the variant
+    /// expected by the RPC. `None` for bodies that don't carry them.
+    fn domain_and_variant(body: &Self::Body) -> Option<(&[u8], i32, TransactionVariant)>;
+}
+
+macro_rules! impl_signed_request {
+    ($($request:ty => $body:ty $(, $variant:ident)?);* $(;)?) => {
+        $(
+            impl SignedRequest for $request {
+                type Body = $body;
+
+                fn into_parts(self) -> (i32, Vec<u8>, Option<Self::Body>) {
+                    (self.format, self.signature, self.body)
+                }
+
+                fn from_parts(format: i32, signature: Vec<u8>, body: Option<Self::Body>) -> Self {
+                    Self { format, signature, body }
+                }
+
+                fn nonce(body: &Self::Body) -> u64 {
+                    body.nonce
+                }
+
+                #[allow(unused_variables)]
+                fn domain_and_variant(body: &Self::Body) -> Option<(&[u8], i32, TransactionVariant)> {
+                    impl_signed_request!(@domain body $(, $variant)?)
+                }
+            }
+        )*
+    };
+    (@domain $body:ident) => { None };
+    (@domain $body:ident, $variant:ident) => {
+        Some(($body.domain.as_slice(), $body.variant, TransactionVariant::$variant))
+    };
+}
+
+impl_signed_request! {
+    RequestProofRequest => RequestProofRequestBody, RequestVariant;
+    FulfillProofRequest => FulfillProofRequestBody, FulfillVariant;
+    ExecuteProofRequest => ExecuteProofRequestBody, ExecuteVariant;
+    FailFulfillmentRequest => FailFulfillmentRequestBody;
+    CreateProgramRequest => CreateProgramRequestBody;
+    SetProgramNameRequest => SetProgramNameRequestBody;
+    SetAccountNameRequest => SetAccountNameRequestBody;
+    AddCreditRequest => AddCreditRequestBody;
+    TransferRequest => TransferRequestBody, TransferVariant;
+    WithdrawRequest => WithdrawRequestBody, WithdrawVariant;
+    AddReservationRequest => AddReservationRequestBody;
+    RemoveReservationRequest => RemoveReservationRequestBody;
+    BidRequest => BidRequestBody, BidVariant;
+    SettleRequest => SettleRequestBody, SettleVariant;
+    SetDelegationRequest => SetDelegationRequestBody, DelegateVariant;
+    SetGpuVariantRequest => SetGpuVariantRequestBody;
+}
+
+/// Domain separator used when none is configured
+pub fn default_domain() -> Vec<u8> {
+    keccak256(b"spn_coordinator").to_vec()
+}
+
+/// Check the signature of a request, returning its body and the recovered signer address
+pub fn authenticate<R: SignedRequest>(request: R) -> Result<(R::Body, Vec<u8>), Status> {
+    let (format, signature, body) = request.into_parts();
+    let body = body.ok_or_else(|| Status::invalid_argument("Request body is required"))?;
+    let msg_bytes: Vec<u8> = encode_body_for_signing(format, &body)
+        .map_err(|e| Status::internal(format!("Failed to encode body for signing: {}", e)))?;
+    let signer = recover_signer_addr(msg_bytes, &signature)
+        .map_err(|e| Status::unauthenticated(format!("Failed to recover signer address: {}", e)))?;
+    Ok((body, signer))
+}
+
+/// Reject bodies signed for another network (`domain`) or for another RPC (`variant`)
+pub fn verify_domain<R: SignedRequest>(body: &R::Body, domain: &[u8]) -> Result<(), Status> {
+    let Some((body_domain, variant, expected_variant)) = R::domain_and_variant(body) else {
+        return Ok(());
+    };
+    if body_domain != domain {
+        return Err(Status::invalid_argument(format!(
+            "Invalid domain {}, expected {}",
+            hex::encode(body_domain),
+            hex::encode(domain)
+        )));
+    }
+    if variant != expected_variant as i32 {
+        let variant = TransactionVariant::try_from(variant).map(|v| v.as_str_name()).unwrap_or("UNKNOWN");
+        return Err(Status::invalid_argument(format!(
+            "Invalid transaction variant {}, expected {}",
+            variant,
+            expected_variant.as_str_name()
+        )));
+    }
+    Ok(())
+}
+
+/// Check the signature sent with `create_artifact`, returning the signer address
+pub fn authenticate_artifact_request(signature: &[u8]) -> Result<Vec<u8>, Status> {
+    recover_signer_addr(CREATE_ARTIFACT_MESSAGE.to_vec(), signature)
+        .map_err(|e| Status::unauthenticated(format!("Failed to recover signer address: {}", e)))
+}
+
+/// Reject the request unless it was signed by `expected`
+pub fn require_signer(signer: &[u8], expected: &[u8], role: &str) -> Result<(), Status> {
+    if signer != expected {
+        return Err(Status::unauthenticated(format!(
+            "Signer {} is not the {} {}",
+            hex::encode(signer),
+            role,
+            hex::encode(expected)
+        )));
+    }
+    Ok(())
+}
+
+pub(crate) fn encode_body_for_signing<T: Message>(format: i32, body: &T) -> eyre::Result<Vec<u8>> {
+    let fmt = MessageFormat::try_from(format).unwrap_or(MessageFormat::Binary);
+    match fmt {
+        MessageFormat::Binary => {
+            // Protobuf canonical binary
+            let mut buf = Vec::new();
+            body.encode(&mut buf)?;
+            Ok(buf)
+        }
+        // MessageFormat::Json => {
+        //     // Only use if your client truly signed JSON and both sides enforce a canonical form.
+        //     // If you control both ends, prefer Binary to avoid JSON canonicalization traps.
+        //     #[derive(Serialize)]
+        //     struct Canon<'a> {
+        //         nonce: u64,
+        //         vk_hash: &'a [u8],
+        //         version: &'a str,
+        //         mode: i32,
+        //         strategy: i32,
+        //         stdin_uri: &'a str,
+        //         deadline: u64,
+        //         cycle_limit: u64,
+        //         gas_limit: u64,
+        //     }
+        //     // Map your fields EXACTLY as the client did:
+        //     let c = Canon {
+        //         nonce: body.nonce,
+        //         vk_hash: &body.vk_hash,
+        //         version: &body.version,
+        //         mode: body.mode,
+        //         strategy: body.strategy,
+        //         stdin_uri: &body.stdin_uri,
+        //         deadline: body.deadline,
+        //         cycle_limit: body.cycle_limit,
+        //         gas_limit: body.gas_limit,
+        //     };
+        //     Ok(serde_json::to_vec(&c)?)
+        // }
+        // Fallbacks if your enum has others:
+        _ => {
+            // Default to protobuf binary unless you KNOW another format was used.
+            let mut buf = Vec::new();
+            body.encode(&mut buf)?;
+            Ok(buf)
+        }
+    }
+}
+
+pub fn recover_signer_addr(msg_bytes: Vec<u8>, sig_bytes: &[u8]) -> eyre::Result<Vec<u8>> {
+    // Apply EIP-191 prefix (Ethereum personal message format)
+    let msg_hash = hash_message(&msg_bytes); // This applies EIP-191 prefix and hashes
+
+    // Parse the signature and recover the address
+    let sig = Signature::try_from(sig_bytes)?;
+    let address = sig.recover(msg_hash)?;
+    let address_bytes = address.as_bytes().to_vec();
+    Ok(address_bytes)
+}
+
+// pub fn recover_address_from_personal_sign(message: impl AsRef<[u8]>, sig_hex: &str) -> Result<Address> {
+//     // Parse 0x… signature; v can be 27/28 or 0/1 — ethers handles both.
+//     let sig = Signature::from_str(sig_hex)?;
+//     // Keccak256("\x19Ethereum Signed Message:\n{len(m)}" || m)
+//     let digest = hash_message(message);
+//     // Recover the address that signed the digest
+//     let addr = sig.recover(digest)?;
+//     Ok(addr)
+// }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::signers::{LocalWallet, Signer};
+
+    #[test]
+    fn test_authenticate_recovers_signer() {
+        let wallet = LocalWallet::new(&mut ethers::core::rand::thread_rng());
+        let body = FailFulfillmentRequestBody { nonce: 1, request_id: vec![1; 32], error: None };
+        let signature = wallet.sign_hash(hash_message(body.encode_to_vec())).unwrap();
+        let request = FailFulfillmentRequest {
+            format: MessageFormat::Binary as i32,
+            signature: signature.to_vec(),
+            body: Some(body.clone()),
+        };
+
+        let (decoded, signer) = authenticate(request).unwrap();
+        assert_eq!(decoded, body);
+        assert_eq!(signer, wallet.address().as_bytes().to_vec());
+
+        let tampered = FailFulfillmentRequest {
+            format: MessageFormat::Binary as i32,
+            signature: vec![0; 65],
+            body: Some(body),
+        };
+        assert_eq!(authenticate(tampered).unwrap_err().code(), tonic::Code::Unauthenticated);
+    }
+
+    #[test]
+    fn test_verify_domain_and_variant() {
+        let domain = default_domain();
+        let mut body = FulfillProofRequestBody {
+            domain: domain.clone(),
+            variant: TransactionVariant::FulfillVariant as i32,
+            ..Default::default()
+        };
+        assert!(verify_domain::<FulfillProofRequest>(&body, &domain).is_ok());
+
+        // A bid signature can't be used to fulfill
+        body.variant = TransactionVariant::BidVariant as i32;
+        assert_eq!(verify_domain::<FulfillProofRequest>(&body, &domain).unwrap_err().code(), tonic::Code::InvalidArgument);
+
+        // Nor a signature made for another network
+        body.variant = TransactionVariant::FulfillVariant as i32;
+        body.domain = vec![1; 32];
+        assert_eq!(verify_domain::<FulfillProofRequest>(&body, &domain).unwrap_err().code(), tonic::Code::InvalidArgument);
+
+        // Bodies without a domain are not checked
+        assert!(verify_domain::<FailFulfillmentRequest>(&FailFulfillmentRequestBody::default(), &domain).is_ok());
+    }
+}
@@ -0,0 +1,392 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::server::params::NetworkParams;
+use crate::server::provers::DEFAULT_LIVENESS_WINDOW;
+use crate::server::staking::StakingParams;
+use crate::storage::S3Config;
+
+/// Base URL of the HTTP artifact server when none is configured
+pub const DEFAULT_PUBLIC_URL: &str = "http://localhost:8082";
+
+/// Configuration of the coordinator.
+///
+/// Values are layered: built-in defaults, then the TOML file, then `SPN_*`
+/// environment variables, then command line flags (see [`ConfigArgs`]).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CoordinatorConfig {
+    /// Address of the gRPC server
+    pub grpc_addr: SocketAddr,
+    /// Address of the HTTP artifact server
+    pub http_addr: SocketAddr,
+    /// Base URL the HTTP artifact server is reachable at by clients, used in upload and proof URLs
+    pub public_url: String,
+    pub tls: TlsConfig,
+    pub storage: StorageConfig,
+    pub artifacts: ArtifactsConfig,
+    /// S3-compatible object store for artifacts, artifacts go through the HTTP server when unset
+    pub s3: Option<S3Config>,
+    pub retention: RetentionConfig,
+    pub deadlines: DeadlineConfig,
+    pub hosted: HostedConfig,
+    pub provers: ProversConfig,
+    pub staking: StakingParams,
+    /// Parameters served by `get_proof_request_params` and enforced on requests
+    pub network: NetworkParams,
+    /// `verifier.Verifier` gRPC endpoint checking fulfilled proofs, the coordinator refuses to start when unset
+    pub verifier_endpoint: Option<String>,
+}
+
+/// TLS of the gRPC server, enabled when both `cert` and `key` are set
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    pub cert: Option<PathBuf>,
+    pub key: Option<PathBuf>,
+    /// When set, clients must present a certificate signed by this CA
+    pub ca: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackendKind {
+    /// Persistent sled database under `storage.path`
+    Sled,
+    /// Everything is lost on restart, for tests
+    Memory,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ArtifactBackendKind {
+    /// Files under `artifacts.path`
+    Fs,
+    /// Everything is lost on restart, for tests
+    Memory,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub backend: StorageBackendKind,
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArtifactsConfig {
+    pub backend: ArtifactBackendKind,
+    /// Directory of the uploaded artifacts when using the `fs` backend
+    pub path: PathBuf,
+    /// Accounts allowed to create artifacts, nobody when empty unless `allow_all` is set
+    pub allowlist: Vec<String>,
+    /// Let any authenticated account create artifacts
+    pub allow_all: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HostedConfig {
+    /// Provers that hosted strategy requests are assigned to, any live prover holding some stake when empty
+    pub provers: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProversConfig {
+    /// Provers not seen for this long are not live anymore
+    pub liveness_secs: u64,
+}
+
+impl Default for ProversConfig {
+    fn default() -> Self {
+        Self { liveness_secs: DEFAULT_LIVENESS_WINDOW.as_secs() }
+    }
+}
+
+impl ProversConfig {
+    pub fn liveness_window(&self) -> Duration {
+        Duration::from_secs(self.liveness_secs)
+    }
+}
+
+/// How long finished proof requests and uploaded artifacts are kept, forever when unset
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetentionConfig {
+    pub proof_requests_secs: Option<u64>,
+    pub artifacts_secs: Option<u64>,
+}
+
+/// Requests past their deadline always become unfulfillable
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeadlineConfig {
+    /// Auction requests assigned for this long without a proof are put back to auction, never when unset
+    pub reassign_after_secs: Option<u64>,
+}
+
+impl DeadlineConfig {
+    pub fn reassign_after(&self) -> Option<Duration> {
+        self.reassign_after_secs.map(Duration::from_secs)
+    }
+}
+
+impl Default for CoordinatorConfig {
+    fn default() -> Self {
+        Self {
+            grpc_addr: ([0, 0, 0, 0], 50051).into(),
+            http_addr: ([0, 0, 0, 0], 8082).into(),
+            public_url: DEFAULT_PUBLIC_URL.to_string(),
+            tls: TlsConfig::default(),
+            storage: StorageConfig::default(),
+            artifacts: ArtifactsConfig::default(),
+            s3: None,
+            retention: RetentionConfig::default(),
+            deadlines: DeadlineConfig::default(),
+            hosted: HostedConfig::default(),
+            provers: ProversConfig::default(),
+            staking: StakingParams::default(),
+            network: NetworkParams::default(),
+            verifier_endpoint: None,
+        }
+    }
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self { backend: StorageBackendKind::Sled, path: PathBuf::from("data/coordinator") }
+    }
+}
+
+impl Default for ArtifactsConfig {
+    fn default() -> Self {
+        Self { backend: ArtifactBackendKind::Fs, path: PathBuf::from("data/artifacts"), allowlist: Vec::new(), allow_all: false }
+    }
+}
+
+impl RetentionConfig {
+    pub fn proof_requests(&self) -> Option<Duration> {
+        self.proof_requests_secs.map(Duration::from_secs)
+    }
+
+    pub fn artifacts(&self) -> Option<Duration> {
+        self.artifacts_secs.map(Duration::from_secs)
+    }
+}
+
+impl CoordinatorConfig {
+    /// Read a TOML configuration file, missing keys keep their default value
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).with_context(|| format!("Failed to read config file {}", path.display()))?;
+        toml::from_str(&content).with_context(|| format!("Invalid config file {}", path.display()))
+    }
+
+    /// Load the configuration: defaults, then the file, then the environment, then the flags
+    pub fn load(args: &ConfigArgs) -> Result<Self> {
+        let mut config = match &args.config {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+        config.apply_env()?;
+        args.apply(&mut config);
+        Ok(config)
+    }
+
+    /// Override the file with the environment variables that are not command line flags
+    fn apply_env(&mut self) -> Result<()> {
+        if let Some(s3) = S3Config::from_env()? {
+            self.s3 = Some(s3);
+        }
+        if let Ok(allowlist) = std::env::var("SPN_ARTIFACT_ALLOWLIST") {
+            self.artifacts.allowlist = split_list(&allowlist);
+        }
+        if let Ok(provers) = std::env::var("SPN_HOSTED_PROVERS") {
+            self.hosted.provers = split_list(&provers);
+        }
+        if let Ok(allow_all) = std::env::var("SPN_ARTIFACT_ALLOW_ALL") {
+            self.artifacts.allow_all = allow_all.parse().context("Invalid SPN_ARTIFACT_ALLOW_ALL")?;
+        }
+        if let Ok(secs) = std::env::var("SPN_PROOF_REQUEST_RETENTION_SECS") {
+            self.retention.proof_requests_secs = Some(secs.parse().context("Invalid SPN_PROOF_REQUEST_RETENTION_SECS")?);
+        }
+        if let Ok(secs) = std::env::var("SPN_ARTIFACT_RETENTION_SECS") {
+            self.retention.artifacts_secs = Some(secs.parse().context("Invalid SPN_ARTIFACT_RETENTION_SECS")?);
+        }
+        if let Ok(secs) = std::env::var("SPN_PROVER_LIVENESS_SECS") {
+            self.provers.liveness_secs = secs.parse().context("Invalid SPN_PROVER_LIVENESS_SECS")?;
+        }
+        if let Ok(secs) = std::env::var("SPN_REASSIGN_AFTER_SECS") {
+            self.deadlines.reassign_after_secs = Some(secs.parse().context("Invalid SPN_REASSIGN_AFTER_SECS")?);
+        }
+        self.staking.apply_env()?;
+        self.network.apply_env()
+    }
+
+    /// Artifact allowlist decoded from hex addresses
+    pub fn artifact_allowlist(&self) -> Result<Vec<Vec<u8>>> {
+        decode_addresses(&self.artifacts.allowlist)
+    }
+
+    /// Verifier endpoint checking fulfilled proofs. The coordinator serves a verifier
+    /// forwarding to this endpoint, so pointing it back at the coordinator would loop.
+    pub fn verifier_endpoint(&self) -> Result<&str> {
+        let Some(endpoint) = &self.verifier_endpoint else {
+            anyhow::bail!("A verifier endpoint is required, set SPN_VERIFIER_ENDPOINT or verifier_endpoint");
+        };
+        let uri: http::Uri = endpoint.parse().with_context(|| format!("Invalid verifier endpoint {}", endpoint))?;
+        let public_url: Option<http::Uri> = self.public_url.parse().ok();
+        let is_local = |host: &str| {
+            host == "localhost"
+                || host.trim_start_matches('[').trim_end_matches(']').parse::<std::net::IpAddr>().is_ok_and(|ip| {
+                    ip.is_loopback() || ip.is_unspecified() || ip == self.grpc_addr.ip() || ip == self.http_addr.ip()
+                })
+        };
+        let port = uri.port_u16().unwrap_or(if uri.scheme_str() == Some("https") { 443 } else { 80 });
+        let own_port = port == self.grpc_addr.port() || port == self.http_addr.port();
+        let is_public_url = public_url.is_some_and(|url| url.authority() == uri.authority());
+        if (own_port && uri.host().is_some_and(is_local)) || is_public_url {
+            anyhow::bail!("The verifier endpoint {} is the coordinator itself, set it to a verifier sidecar", endpoint);
+        }
+        Ok(endpoint)
+    }
+
+    /// Hosted prover pool decoded from hex addresses
+    pub fn hosted_provers(&self) -> Result<Vec<Vec<u8>>> {
+        decode_addresses(&self.hosted.provers)
+    }
+}
+
+/// Comma separated list of an environment variable
+fn split_list(value: &str) -> Vec<String> {
+    value.split(',').map(|a| a.trim().to_string()).filter(|a| !a.is_empty()).collect()
+}
+
+fn decode_addresses(addresses: &[String]) -> Result<Vec<Vec<u8>>> {
+    addresses
+        .iter()
+        .map(|a| hex::decode(a.trim_start_matches("0x")).map_err(|e| anyhow::anyhow!("Invalid address {}: {}", a, e)))
+        .collect()
+}
+
+/// Command line flags of the coordinator, each one can also be set with its environment variable
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct ConfigArgs {
+    /// TOML configuration file
+    #[arg(long, env = "SPN_CONFIG")]
+    pub config: Option<PathBuf>,
+    /// Address of the gRPC server
+    #[arg(long, env = "SPN_GRPC_ADDR")]
+    pub grpc_addr: Option<SocketAddr>,
+    /// Address of the HTTP artifact server
+    #[arg(long, env = "SPN_HTTP_ADDR")]
+    pub http_addr: Option<SocketAddr>,
+    /// Base URL of the HTTP artifact server as seen by clients
+    #[arg(long, env = "SPN_PUBLIC_URL")]
+    pub public_url: Option<String>,
+    /// PEM certificate of the gRPC server
+    #[arg(long, env = "SPN_TLS_CERT")]
+    pub tls_cert: Option<PathBuf>,
+    /// PEM private key of the gRPC server
+    #[arg(long, env = "SPN_TLS_KEY")]
+    pub tls_key: Option<PathBuf>,
+    /// PEM CA that client certificates must be signed by
+    #[arg(long, env = "SPN_TLS_CA")]
+    pub tls_ca: Option<PathBuf>,
+    /// `verifier.Verifier` gRPC endpoint checking fulfilled proofs
+    #[arg(long, env = "SPN_VERIFIER_ENDPOINT")]
+    pub verifier_endpoint: Option<String>,
+    /// Backend of the coordinator state
+    #[arg(long, env = "SPN_STORAGE_BACKEND", value_enum)]
+    pub storage_backend: Option<StorageBackendKind>,
+    /// Directory of the coordinator state
+    #[arg(long, env = "SPN_STORAGE_PATH")]
+    pub storage_path: Option<PathBuf>,
+    /// Backend of the uploaded artifacts
+    #[arg(long, env = "SPN_ARTIFACTS_BACKEND", value_enum)]
+    pub artifacts_backend: Option<ArtifactBackendKind>,
+    /// Directory of the uploaded artifacts
+    #[arg(long, env = "SPN_ARTIFACTS_PATH")]
+    pub artifacts_path: Option<PathBuf>,
+}
+
+impl ConfigArgs {
+    fn apply(&self, config: &mut CoordinatorConfig) {
+        fn set<T: Clone>(target: &mut T, value: &Option<T>) {
+            if let Some(value) = value {
+                *target = value.clone();
+            }
+        }
+        set(&mut config.grpc_addr, &self.grpc_addr);
+        set(&mut config.http_addr, &self.http_addr);
+        set(&mut config.public_url, &self.public_url);
+        set(&mut config.storage.backend, &self.storage_backend);
+        set(&mut config.storage.path, &self.storage_path);
+        set(&mut config.artifacts.backend, &self.artifacts_backend);
+        set(&mut config.artifacts.path, &self.artifacts_path);
+        if self.verifier_endpoint.is_some() {
+            config.verifier_endpoint = self.verifier_endpoint.clone();
+        }
+        if self.tls_cert.is_some() {
+            config.tls.cert = self.tls_cert.clone();
+        }
+        if self.tls_key.is_some() {
+            config.tls.key = self.tls_key.clone();
+        }
+        if self.tls_ca.is_some() {
+            config.tls.ca = self.tls_ca.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_then_flags() {
+        let config: CoordinatorConfig = toml::from_str(
+            r#"
+            public_url = "http://spn-coordinator-001:8082"
+            [storage]
+            backend = "memory"
+            [retention]
+            proof_requests_secs = 86400
+            [network]
+            auctioneer = "0x0000000000000000000000000000000000000001"
+            base_fee = "5"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.grpc_addr, "0.0.0.0:50051".parse().unwrap());
+        assert_eq!(config.storage.backend, StorageBackendKind::Memory);
+        assert_eq!(config.storage.path, PathBuf::from("data/coordinator"));
+        assert_eq!(config.retention.proof_requests(), Some(Duration::from_secs(86400)));
+        assert_eq!(config.network.auctioneer[19], 1);
+        assert_eq!(config.network.base_fee, "5");
+
+        let mut config = config;
+        let args = ConfigArgs { public_url: Some("https://spn.example.com".to_string()), ..Default::default() };
+        args.apply(&mut config);
+        assert_eq!(config.public_url, "https://spn.example.com");
+        assert_eq!(config.storage.backend, StorageBackendKind::Memory);
+
+        assert!(toml::from_str::<CoordinatorConfig>("grpc_port = 1").is_err());
+    }
+
+    #[test]
+    fn test_verifier_endpoint_is_not_the_coordinator() {
+        let config = |endpoint: &str| CoordinatorConfig { verifier_endpoint: Some(endpoint.to_string()), ..Default::default() };
+        assert!(CoordinatorConfig::default().verifier_endpoint().is_err());
+        assert!(config("http://localhost:50051").verifier_endpoint().is_err());
+        assert!(config("http://127.0.0.1:50051").verifier_endpoint().is_err());
+        assert!(config("http://[::1]:8082").verifier_endpoint().is_err());
+        assert!(config(DEFAULT_PUBLIC_URL).verifier_endpoint().is_err());
+        assert_eq!(config("http://localhost:50052").verifier_endpoint().unwrap(), "http://localhost:50052");
+        assert!(config("http://verifier:50051").verifier_endpoint().is_ok());
+    }
+}
@@ -0,0 +1,303 @@
+use rpc_types::*;
+use std::time::Duration;
+use tonic::Status;
+
+use crate::server::analytics::Analytics;
+use crate::server::ledger::Ledger;
+use crate::server::staking::Staking;
+use crate::storage::{Storage, StorageError};
+
+/// Enforces the deadline of proof requests.
+///
+/// Requests still `Requested` or `Assigned` once their deadline has passed
+/// become `Unfulfillable`. Auction requests whose fulfiller has not delivered
+/// within `reassign_after` can also be put back to auction, as long as their
+/// deadline has not passed.
+#[derive(Debug, Clone)]
+pub struct DeadlineReaper {
+    storage: Storage,
+    /// Releases the escrow of expired requests
+    ledger: Ledger,
+    /// Slashes the fulfillers of expired requests
+    staking: Staking,
+    /// Counts the auctions that expired without a prover
+    analytics: Analytics,
+    reassign_after: Option<Duration>,
+}
+
+impl DeadlineReaper {
+    pub fn new(storage: Storage) -> Self {
+        let ledger = Ledger::new(storage.clone());
+        Self {
+            staking: Staking::new(storage.clone(), ledger.clone()),
+            analytics: Analytics::new(storage.clone()),
+            ledger,
+            storage,
+            reassign_after: None,
+        }
+    }
+
+    pub fn with_ledger(mut self, ledger: Ledger) -> Self {
+        self.ledger = ledger;
+        self
+    }
+
+    pub fn with_staking(mut self, staking: Staking) -> Self {
+        self.staking = staking;
+        self
+    }
+
+    /// Re-open the auction of requests assigned for longer than `period`
+    pub fn with_reassignment(mut self, period: Option<Duration>) -> Self {
+        self.reassign_after = period;
+        self
+    }
+
+    /// Expire the requests past their deadline and re-open the stalled auctions.
+    /// Returns the number of expired and re-opened requests.
+    pub fn reap(&self, now: u64) -> Result<(usize, usize), Status> {
+        let (mut expired, mut reopened) = (0, 0);
+        let mut requests = self.storage.proof_requests_with_status(FulfillmentStatus::Requested)?;
+        requests.extend(self.storage.proof_requests_with_status(FulfillmentStatus::Assigned)?);
+        for (request, _) in requests {
+            // One failing request doesn't hold back the others
+            match self.reap_request(&request, now) {
+                Ok(Reaped::Expired) => expired += 1,
+                Ok(Reaped::Reopened) => reopened += 1,
+                Ok(Reaped::Skipped) => {}
+                Err(e) => tracing::error!("DEADLINE: Failed to reap request {}: {}", hex::encode(&request.request_id), e.message()),
+            }
+        }
+        Ok((expired, reopened))
+    }
+
+    /// Expire or re-open a request listed as open. It may have been fulfilled or
+    /// failed since it was listed, in which case it is left as it is.
+    fn reap_request(&self, request: &ProofRequest, now: u64) -> Result<Reaped, Status> {
+        if is_past_deadline(request, now) {
+            let expired = self.storage.update_proof_request(&request.request_id, |request, status| {
+                if !is_open(request) {
+                    return Ok::<_, Status>(None);
+                }
+                // The network has no dedicated error for requests nobody proved in time
+                request.error = ProofRequestError::UnknownFailure as i32;
+                request.fulfillment_status = FulfillmentStatus::Unfulfillable as i32;
+                request.updated_at = now;
+                status.fulfillment_status = request.fulfillment_status;
+                let executed = request.execution_status == ExecutionStatus::Executed as i32;
+                Ok(Some((request.fulfiller.clone(), executed)))
+            })?;
+            let Some((fulfiller, executed)) = expired else {
+                return Ok(Reaped::Skipped);
+            };
+            tracing::info!("DEADLINE: Request {} expired", hex::encode(&request.request_id));
+            self.ledger.settle_request(&request.request_id, now)?;
+            // The prover assigned to the request missed its deadline. Proofs are only
+            // accepted once the request is executed, so a prover can't be late before.
+            match fulfiller {
+                Some(fulfiller) if executed => {
+                    self.staking.slash(&fulfiller, &request.request_id, now)?;
+                }
+                Some(_) => {}
+                // Re-opened auctions were counted as won already
+                None if request.strategy == FulfillmentStrategy::Auction as i32 && self.storage.get_assigned_at(&request.request_id)?.is_none() => {
+                    self.analytics.record_auction(request, false, now)?;
+                }
+                None => {}
+            }
+            Ok(Reaped::Expired)
+        } else if self.is_stalled(request, now)? {
+            let fulfiller = self.storage.update_proof_request_with_writes(&request.request_id, |request, status, writes| {
+                if !self.is_stalled(request, now)? {
+                    return Ok::<_, Status>(None);
+                }
+                // The stalled prover can't win the auction again with its old bid
+                let fulfiller = request.fulfiller.take();
+                if let Some(fulfiller) = &fulfiller {
+                    writes.remove_bid(&request.request_id, fulfiller);
+                }
+                request.gas_price = None;
+                request.fulfillment_status = FulfillmentStatus::Requested as i32;
+                request.settlement_status = SettlementStatus::Unsettled as i32;
+                request.updated_at = now;
+                status.fulfillment_status = request.fulfillment_status;
+                Ok(fulfiller)
+            })?;
+            let Some(fulfiller) = fulfiller else {
+                return Ok(Reaped::Skipped);
+            };
+            tracing::info!(
+                "DEADLINE: Request {} taken back from {} and put back to auction",
+                hex::encode(&request.request_id),
+                hex::encode(&fulfiller)
+            );
+            Ok(Reaped::Reopened)
+        } else {
+            Ok(Reaped::Skipped)
+        }
+    }
+
+    /// Auction requests assigned for longer than the reassignment period. Executing a
+    /// request updates it, so the period runs from the assignment instead.
+    fn is_stalled(&self, request: &ProofRequest, now: u64) -> Result<bool, StorageError> {
+        let Some(period) = self.reassign_after else {
+            return Ok(false);
+        };
+        if request.strategy != FulfillmentStrategy::Auction as i32 || request.fulfillment_status != FulfillmentStatus::Assigned as i32 {
+            return Ok(false);
+        }
+        // Requests assigned before assignments were recorded fall back to their last update
+        let assigned_at = self.storage.get_assigned_at(&request.request_id)?.unwrap_or(request.updated_at);
+        Ok(now >= assigned_at.saturating_add(period.as_secs()))
+    }
+
+    /// Periodically expire and re-open requests
+    pub fn spawn(self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let now = chrono::Utc::now().timestamp() as u64;
+                if let Err(e) = self.reap(now) {
+                    tracing::error!("DEADLINE: Failed to reap proof requests: {}", e.message());
+                }
+            }
+        })
+    }
+}
+
+/// What reaping did to a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reaped {
+    Expired,
+    Reopened,
+    /// Not due yet, or no longer open
+    Skipped,
+}
+
+/// Reject fulfillments of requests past their deadline
+pub fn require_before_deadline(request: &ProofRequest, now: u64) -> Result<(), Status> {
+    if is_past_deadline(request, now) {
+        return Err(Status::failed_precondition("Request is past its deadline"));
+    }
+    Ok(())
+}
+
+/// A deadline of zero means no deadline
+pub(crate) fn is_past_deadline(request: &ProofRequest, now: u64) -> bool {
+    request.deadline != 0 && now >= request.deadline
+}
+
+fn is_open(request: &ProofRequest) -> bool {
+    request.fulfillment_status == FulfillmentStatus::Requested as i32
+        || request.fulfillment_status == FulfillmentStatus::Assigned as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put_request(storage: &Storage, id: u8, status: FulfillmentStatus, deadline: u64) {
+        let request = ProofRequest {
+            request_id: vec![id; 32],
+            strategy: FulfillmentStrategy::Auction as i32,
+            fulfillment_status: status as i32,
+            fulfiller: (status == FulfillmentStatus::Assigned).then(|| vec![9; 20]),
+            deadline,
+            updated_at: 100,
+            ..Default::default()
+        };
+        storage.put_proof_request(&request, &GetProofRequestStatusResponse::default()).unwrap();
+    }
+
+    #[test]
+    fn test_past_deadline_requests_expire() {
+        let storage = Storage::default();
+        put_request(&storage, 1, FulfillmentStatus::Assigned, 500);
+        put_request(&storage, 2, FulfillmentStatus::Requested, 2000);
+        put_request(&storage, 3, FulfillmentStatus::Fulfilled, 500);
+
+        let mut events = storage.subscribe_proof_requests();
+        assert_eq!(DeadlineReaper::new(storage.clone()).reap(1000).unwrap(), (1, 0));
+
+        let (request, status) = storage.get_proof_request(&[1; 32]).unwrap().unwrap();
+        assert_eq!(status.fulfillment_status, FulfillmentStatus::Unfulfillable as i32);
+        assert_eq!(request.error, ProofRequestError::UnknownFailure as i32);
+        assert_eq!(events.try_recv().unwrap().request_id, vec![1; 32]);
+        assert!(require_before_deadline(&request, 1000).is_err());
+        assert_eq!(storage.get_proof_request(&[3; 32]).unwrap().unwrap().1.fulfillment_status, 0);
+    }
+
+    #[test]
+    fn test_only_executed_requests_slash_their_prover() {
+        let storage = Storage::default();
+        let ledger = Ledger::new(storage.clone()).with_treasury(vec![3; 20]);
+        let staking = Staking::new(storage.clone(), ledger.clone());
+        ledger.add_credit(&[9; 20], 200, &[0; 32], 1).unwrap();
+        staking.transfer_stake(&[9; 20], 200, (&[3; 20], 0), &[0; 32], 1).unwrap();
+        let reaper = DeadlineReaper::new(storage.clone()).with_ledger(ledger).with_staking(staking.clone());
+
+        // Never executed, the prover could not have proved it
+        put_request(&storage, 1, FulfillmentStatus::Assigned, 500);
+        assert_eq!(reaper.reap(1000).unwrap(), (1, 0));
+        assert_eq!(staking.stake_of(&[9; 20]).unwrap(), 200);
+
+        put_request(&storage, 2, FulfillmentStatus::Assigned, 500);
+        storage
+            .update_proof_request(&[2; 32], |request, _| {
+                request.execution_status = ExecutionStatus::Executed as i32;
+                Ok::<_, StorageError>(())
+            })
+            .unwrap();
+        assert_eq!(reaper.reap(1000).unwrap(), (1, 0));
+        assert!(staking.stake_of(&[9; 20]).unwrap() < 200);
+    }
+
+    #[test]
+    fn test_requests_closed_since_listed_are_skipped() {
+        let storage = Storage::default();
+        put_request(&storage, 1, FulfillmentStatus::Requested, 500);
+        let (listed, _) = storage.get_proof_request(&[1; 32]).unwrap().unwrap();
+        // Fulfilled after the reaper listed it
+        storage
+            .update_proof_request(&[1; 32], |request, status| {
+                request.fulfillment_status = FulfillmentStatus::Fulfilled as i32;
+                status.fulfillment_status = request.fulfillment_status;
+                Ok::<_, StorageError>(())
+            })
+            .unwrap();
+
+        let reaper = DeadlineReaper::new(storage.clone());
+        assert_eq!(reaper.reap_request(&listed, 1000).unwrap(), Reaped::Skipped);
+        let (request, status) = storage.get_proof_request(&[1; 32]).unwrap().unwrap();
+        assert_eq!(status.fulfillment_status, FulfillmentStatus::Fulfilled as i32);
+        assert_eq!(request.error, 0);
+        assert_eq!(reaper.reap(1000).unwrap(), (0, 0));
+    }
+
+    #[test]
+    fn test_stalled_auction_is_reopened() {
+        let storage = Storage::default();
+        put_request(&storage, 1, FulfillmentStatus::Assigned, 2000);
+        storage
+            .put_bid(&[1; 32], &BidHistory { bidder: vec![9; 20], amount: "1".to_string(), created_at: 50, bidder_name: None })
+            .unwrap();
+        // Executing the request later doesn't restart the period
+        storage.put_assigned_at(&[1; 32], 100).unwrap();
+        storage
+            .update_proof_request(&[1; 32], |request, _| {
+                request.updated_at = 350;
+                Ok::<_, StorageError>(())
+            })
+            .unwrap();
+
+        let reaper = DeadlineReaper::new(storage.clone()).with_reassignment(Some(Duration::from_secs(300)));
+        assert_eq!(reaper.reap(300).unwrap(), (0, 0));
+        assert_eq!(reaper.reap(400).unwrap(), (0, 1));
+
+        let (request, _) = storage.get_proof_request(&[1; 32]).unwrap().unwrap();
+        assert_eq!(request.fulfillment_status, FulfillmentStatus::Requested as i32);
+        assert_eq!(request.fulfiller, None);
+        assert!(storage.bids(&[1; 32]).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,124 @@
+use rpc_types::*;
+use tonic::Status;
+
+/// Record the report of the execution oracle on a proof request.
+///
+/// A successful execution is checked against the limits of the request and
+/// the public values hash the requester expects. Failed executions make the
+/// request unfulfillable, successful ones let the fulfiller submit a proof.
+pub fn apply_execution(
+    request: &mut ProofRequest,
+    status: &mut GetProofRequestStatusResponse,
+    report: &ExecuteProofRequestBody,
+    now: u64,
+) -> Result<ExecutionStatus, Status> {
+    if request.execution_status != ExecutionStatus::Unexecuted as i32 {
+        return Err(Status::failed_precondition("Proof request was already executed"));
+    }
+    if request.fulfillment_status == FulfillmentStatus::Fulfilled as i32
+        || request.fulfillment_status == FulfillmentStatus::Unfulfillable as i32
+    {
+        return Err(Status::failed_precondition("Proof request is already finished"));
+    }
+
+    let mut execution_status = match ExecutionStatus::try_from(report.execution_status) {
+        Ok(s @ (ExecutionStatus::Executed | ExecutionStatus::Unexecutable | ExecutionStatus::ValidationFailed)) => s,
+        _ => return Err(Status::invalid_argument("Execution status must be EXECUTED, UNEXECUTABLE or VALIDATION_FAILED")),
+    };
+    let mut failure_cause = report.failure_cause.unwrap_or(0);
+    let mut error = ProofRequestError::ExecutionFailure;
+
+    if execution_status == ExecutionStatus::Executed {
+        let (Some(cycles), Some(public_values_hash)) = (report.cycles, &report.public_values_hash) else {
+            return Err(Status::invalid_argument("Successful executions must report cycles and a public values hash"));
+        };
+        let pgus = report.pgus.unwrap_or(cycles);
+        let gas_limit = if request.gas_limit == 0 { request.cycle_limit } else { request.gas_limit };
+        if (request.cycle_limit != 0 && cycles > request.cycle_limit) || (gas_limit != 0 && pgus > gas_limit) {
+            execution_status = ExecutionStatus::Unexecutable;
+            failure_cause = ExecuteFailureCause::ExceededCycleLimit as i32;
+        } else if request.public_values_hash.as_ref().is_some_and(|expected| expected != public_values_hash) {
+            execution_status = ExecutionStatus::ValidationFailed;
+            error = ProofRequestError::PublicValuesMismatch;
+        }
+        request.cycles = Some(cycles);
+        request.gas_used = Some(pgus);
+        request.public_values_hash = Some(public_values_hash.clone());
+        status.public_values_hash = Some(public_values_hash.clone());
+    }
+
+    request.execution_status = execution_status as i32;
+    status.execution_status = request.execution_status;
+    if execution_status != ExecutionStatus::Executed {
+        request.execute_fail_cause = failure_cause;
+        request.error = error as i32;
+        request.fulfillment_status = FulfillmentStatus::Unfulfillable as i32;
+        status.fulfillment_status = request.fulfillment_status;
+    }
+    request.updated_at = now;
+    Ok(execution_status)
+}
+
+/// Reject fulfillments of requests the execution oracle has not validated yet
+pub fn require_executed(request: &ProofRequest) -> Result<(), Status> {
+    if request.execution_status != ExecutionStatus::Executed as i32 {
+        return Err(Status::failed_precondition("Proof request has not been successfully executed"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assigned_request() -> (ProofRequest, GetProofRequestStatusResponse) {
+        let request = ProofRequest {
+            fulfillment_status: FulfillmentStatus::Assigned as i32,
+            execution_status: ExecutionStatus::Unexecuted as i32,
+            cycle_limit: 1000,
+            public_values_hash: Some(vec![1; 32]),
+            ..Default::default()
+        };
+        (request, GetProofRequestStatusResponse::default())
+    }
+
+    fn report(cycles: u64, public_values_hash: Vec<u8>) -> ExecuteProofRequestBody {
+        ExecuteProofRequestBody {
+            execution_status: ExecutionStatus::Executed as i32,
+            cycles: Some(cycles),
+            public_values_hash: Some(public_values_hash),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_successful_execution_unblocks_fulfillment() {
+        let (mut request, mut status) = assigned_request();
+        assert!(require_executed(&request).is_err());
+
+        let result = apply_execution(&mut request, &mut status, &report(500, vec![1; 32]), 10).unwrap();
+        assert_eq!(result, ExecutionStatus::Executed);
+        assert_eq!(request.cycles, Some(500));
+        assert_eq!(request.gas_used, Some(500));
+        assert_eq!(status.public_values_hash, Some(vec![1; 32]));
+        assert!(require_executed(&request).is_ok());
+
+        // Executed only once
+        assert!(apply_execution(&mut request, &mut status, &report(500, vec![1; 32]), 11).is_err());
+    }
+
+    #[test]
+    fn test_failed_execution_makes_request_unfulfillable() {
+        let (mut request, mut status) = assigned_request();
+        let result = apply_execution(&mut request, &mut status, &report(500, vec![2; 32]), 10).unwrap();
+        assert_eq!(result, ExecutionStatus::ValidationFailed);
+        assert_eq!(request.error, ProofRequestError::PublicValuesMismatch as i32);
+        assert_eq!(status.fulfillment_status, FulfillmentStatus::Unfulfillable as i32);
+
+        let (mut request, mut status) = assigned_request();
+        let result = apply_execution(&mut request, &mut status, &report(5000, vec![1; 32]), 10).unwrap();
+        assert_eq!(result, ExecutionStatus::Unexecutable);
+        assert_eq!(request.execute_fail_cause, ExecuteFailureCause::ExceededCycleLimit as i32);
+        assert!(require_executed(&request).is_err());
+    }
+}
@@ -0,0 +1,206 @@
+use rpc_types::*;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tonic_health::server::HealthReporter;
+use tonic_health::ServingStatus;
+
+use crate::storage::{ArtifactBackend, Storage};
+
+/// Lifecycle of the gRPC server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum GrpcState {
+    Starting = 0,
+    Serving = 1,
+    Stopped = 2,
+}
+
+impl GrpcState {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Self::Starting,
+            1 => Self::Serving,
+            _ => Self::Stopped,
+        }
+    }
+}
+
+/// Outcome of the readiness checks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthReport {
+    /// State of the gRPC server, `None` when this process runs no gRPC server
+    pub grpc: Option<GrpcState>,
+    pub storage: Result<(), String>,
+    pub artifacts: Result<(), String>,
+}
+
+impl HealthReport {
+    pub fn prover_network_ready(&self) -> bool {
+        self.grpc_ready() && self.storage.is_ok()
+    }
+
+    pub fn artifact_store_ready(&self) -> bool {
+        self.grpc_ready() && self.artifacts.is_ok()
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.prover_network_ready() && self.artifact_store_ready()
+    }
+
+    fn grpc_ready(&self) -> bool {
+        self.grpc.is_none_or(|state| state == GrpcState::Serving)
+    }
+
+    /// One line per check, such as `storage: ok`
+    pub fn summary(&self) -> String {
+        let line = |name: &str, result: &Result<(), String>| match result {
+            Ok(()) => format!("{}: ok\n", name),
+            Err(e) => format!("{}: {}\n", name, e),
+        };
+        let mut summary = String::new();
+        if let Some(state) = self.grpc {
+            summary.push_str(&format!("grpc: {:?}\n", state).to_lowercase());
+        }
+        summary.push_str(&line("storage", &self.storage));
+        summary.push_str(&line("artifacts", &self.artifacts));
+        summary
+    }
+}
+
+/// Serving state of the coordinator.
+///
+/// Backs `/health/live` and `/health/ready` on the HTTP server and the
+/// statuses of the `grpc.health.v1.Health` service. The process is live until
+/// the gRPC server stops, and ready while the gRPC server serves and the
+/// storage and artifact backends pass their checks.
+#[derive(Debug, Clone, Default)]
+pub struct Health {
+    /// `None` when the process runs no gRPC server, such as the standalone HTTP server
+    grpc: Option<Arc<AtomicU8>>,
+    /// Statuses reported by the gRPC health service
+    reporter: Option<HealthReporter>,
+    storage: Option<Storage>,
+    artifacts: Option<Arc<dyn ArtifactBackend>>,
+    /// Readiness at the last refresh, to log when it changes
+    ready: Arc<AtomicBool>,
+}
+
+impl Health {
+    /// Track a gRPC server, reporting the statuses of its services to `reporter`
+    pub fn new(reporter: HealthReporter) -> Self {
+        Self {
+            grpc: Some(Arc::new(AtomicU8::new(GrpcState::Starting as u8))),
+            reporter: Some(reporter),
+            ready: Arc::new(AtomicBool::new(true)),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_storage(mut self, storage: Storage) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    pub fn with_artifacts(mut self, artifacts: Arc<dyn ArtifactBackend>) -> Self {
+        self.artifacts = Some(artifacts);
+        self
+    }
+
+    /// Whether artifacts are checked, the HTTP server checks its own backend otherwise
+    pub fn has_artifacts(&self) -> bool {
+        self.artifacts.is_some()
+    }
+
+    /// Record the gRPC server state and report it to the gRPC health service at once
+    pub async fn set_grpc_state(&self, state: GrpcState) {
+        if let Some(grpc) = &self.grpc {
+            grpc.store(state as u8, Ordering::SeqCst);
+        }
+        self.refresh().await;
+    }
+
+    pub fn is_live(&self) -> bool {
+        self.grpc_state() != Some(GrpcState::Stopped)
+    }
+
+    fn grpc_state(&self) -> Option<GrpcState> {
+        self.grpc.as_ref().map(|grpc| GrpcState::from_u8(grpc.load(Ordering::SeqCst)))
+    }
+
+    pub async fn check(&self) -> HealthReport {
+        let storage = match &self.storage {
+            Some(storage) => storage.check().map_err(|e| e.to_string()),
+            None => Ok(()),
+        };
+        let artifacts = match &self.artifacts {
+            Some(artifacts) => artifacts.check().await.map_err(|e| e.to_string()),
+            None => Ok(()),
+        };
+        HealthReport { grpc: self.grpc_state(), storage, artifacts }
+    }
+
+    /// Run the checks and report the serving status of each gRPC service
+    pub async fn refresh(&self) -> HealthReport {
+        let report = self.check().await;
+        let ready = report.is_ready();
+        if self.ready.swap(ready, Ordering::SeqCst) != ready {
+            if ready {
+                tracing::info!("HEALTH: Ready");
+            } else {
+                tracing::warn!("HEALTH: Not ready\n{}", report.summary().trim_end());
+            }
+        }
+        if let Some(reporter) = &self.reporter {
+            let status = |serving: bool| if serving { ServingStatus::Serving } else { ServingStatus::NotServing };
+            reporter.set_service_status(prover_network_server::SERVICE_NAME, status(report.prover_network_ready())).await;
+            reporter.set_service_status(artifact_store_server::SERVICE_NAME, status(report.artifact_store_ready())).await;
+            // The empty service name stands for the whole server
+            reporter.set_service_status("", status(ready)).await;
+        }
+        report
+    }
+
+    /// Refresh the gRPC service statuses every `interval`
+    pub fn spawn(self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                self.refresh().await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::FsArtifactBackend;
+
+    #[tokio::test]
+    async fn test_grpc_state_and_checks() {
+        let (reporter, _) = tonic_health::server::health_reporter();
+        let root = std::env::temp_dir().join(format!("spn_coordinator_health_{}", hex::encode(rand::random::<[u8; 8]>())));
+        let health = Health::new(reporter)
+            .with_storage(Storage::default())
+            .with_artifacts(Arc::new(FsArtifactBackend::new(&root)));
+
+        // Live but not ready until the gRPC server listens
+        assert!(health.is_live());
+        assert!(!health.check().await.is_ready());
+        health.set_grpc_state(GrpcState::Serving).await;
+        assert!(health.check().await.is_ready());
+
+        // Artifacts cannot be stored under a file
+        std::fs::remove_dir_all(&root).unwrap();
+        std::fs::write(&root, b"not a directory").unwrap();
+        let report = health.check().await;
+        assert!(report.prover_network_ready() && !report.artifact_store_ready());
+        std::fs::remove_file(&root).unwrap();
+
+        health.set_grpc_state(GrpcState::Stopped).await;
+        assert!(!health.is_live());
+        assert!(Health::default().is_live() && Health::default().check().await.is_ready());
+    }
+}
@@ -1,52 +1,100 @@
-use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use bytes::Bytes;
+use std::time::Instant;
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    body::Body,
+    extract::{MatchedPath, Path, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::Response,
     routing::{get, put},
     Router,
 };
+use tokio_stream::StreamExt;
+use tokio_util::io::{ReaderStream, StreamReader};
+
+use crate::server::health::Health;
+use crate::server::telemetry::Telemetry;
+use crate::storage::{is_valid_artifact_id, parse_artifact_type, ArtifactBackend, MemoryArtifactBackend};
 
 /// HTTP server for handling artifact uploads via PUT requests
 #[derive(Debug, Clone)]
 pub struct HttpServer {
-    /// In-memory storage for uploaded artifacts
-    pub storage: Arc<Mutex<HashMap<String, Bytes>>>,
-    pub port: u16,
+    /// Backend where uploaded artifacts are stored
+    pub storage: Arc<dyn ArtifactBackend>,
+    pub addr: SocketAddr,
+    /// Records the HTTP requests and artifact transfers, served on `/metrics`
+    pub telemetry: Telemetry,
+    /// Served on `/health/live` and `/health/ready`
+    pub health: Health,
+}
+
+/// State shared by the request handlers
+#[derive(Debug, Clone)]
+struct HttpState {
+    storage: Arc<dyn ArtifactBackend>,
+    telemetry: Telemetry,
+    health: Health,
 }
 
 impl HttpServer {
+    /// Create a server keeping the artifacts in memory
     pub fn new(port: u16) -> Self {
-        Self {
-            storage: Arc::new(Mutex::new(HashMap::new())),
-            port,
-        }
+        Self::with_backend(port, Arc::new(MemoryArtifactBackend::new()))
+    }
+
+    /// Create a server storing the artifacts in the given backend
+    pub fn with_backend(port: u16, storage: Arc<dyn ArtifactBackend>) -> Self {
+        Self { storage, addr: ([0, 0, 0, 0], port).into(), telemetry: Telemetry::default(), health: Health::default() }
+    }
+
+    /// Listen on `addr` instead of all interfaces
+    pub fn with_addr(mut self, addr: SocketAddr) -> Self {
+        self.addr = addr;
+        self
+    }
+
+    /// Record metrics in, and export, these telemetry metrics
+    pub fn with_telemetry(mut self, telemetry: Telemetry) -> Self {
+        self.telemetry = telemetry;
+        self
+    }
+
+    /// Answer the health checks from this state
+    pub fn with_health(mut self, health: Health) -> Self {
+        self.health = health;
+        self
     }
 
     /// Start the HTTP server that handles PUT requests
     pub async fn start(self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let storage = self.storage.clone();
-        
+        let mut health = self.health.clone();
+        if !health.has_artifacts() {
+            health = health.with_artifacts(self.storage.clone());
+        }
+        let state = HttpState { storage: self.storage.clone(), telemetry: self.telemetry.clone(), health };
+
         // Build the application with routes
         let app = Router::new()
             .route("/artifacts/{artifact_type}/{artifact_id}", put(upload_artifact))
             .route("/artifacts/{artifact_type}/{artifact_id}", get(download_artifact))
             .route("/health", get(health_check))
-            .with_state(storage);
+            .route("/health/live", get(health_check))
+            .route("/health/ready", get(readiness_check))
+            .route("/metrics", get(metrics))
+            .route_layer(middleware::from_fn_with_state(state.clone(), record_request))
+            .with_state(state);
 
-        let addr = format!("0.0.0.0:{}", self.port);
-        tracing::info!("HTTP: Starting HTTP server on {}", addr);
+        tracing::info!("HTTP: Starting HTTP server on {}", self.addr);
 
-        let listener = tokio::net::TcpListener::bind(&addr).await?;
+        let listener = tokio::net::TcpListener::bind(self.addr).await?;
         axum::serve(listener, app).await?;
 
         Ok(())
     }
 
     /// Get the storage reference for integration with other services
-    pub fn get_storage(&self) -> Arc<Mutex<HashMap<String, Bytes>>> {
+    pub fn get_storage(&self) -> Arc<dyn ArtifactBackend> {
         self.storage.clone()
     }
 }
@@ -54,62 +102,126 @@ impl HttpServer {
 /// Handler for PUT /artifacts/:artifact_id
 async fn upload_artifact(
     Path((artifact_type, artifact_id)): Path<(String, String)>,
-    State(storage): State<Arc<Mutex<HashMap<String, Bytes>>>>,
-    body: Bytes,
+    State(state): State<HttpState>,
+    body: Body,
 ) -> Result<&'static str, StatusCode> {
     tracing::info!("HTTP: Received PUT request for artifact: {}/{}", artifact_type, artifact_id);
-    tracing::debug!("HTTP: Body size: {} bytes", body.len());
-    
-    // Store the bytes in memory
-    let key: String = format!("{}/{}", artifact_type, artifact_id);
-    storage.lock().await.insert(key, body);
-    
-    tracing::debug!("HTTP: Successfully stored artifact: {}", artifact_id);
-    
+    let artifact_type = parse_artifact_type(&artifact_type).ok_or(StatusCode::BAD_REQUEST)?;
+    if !is_valid_artifact_id(&artifact_id) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // Stream the body to the backend without buffering it in memory
+    let started = Instant::now();
+    let stream = body.into_data_stream().map(|chunk| chunk.map_err(std::io::Error::other));
+    let size = state
+        .storage
+        .put(artifact_type, &artifact_id, Box::pin(StreamReader::new(stream)))
+        .await
+        .map_err(|e| {
+            tracing::error!("HTTP: Failed to store artifact {}: {}", artifact_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    state.telemetry.record_artifact_upload(artifact_type, size, started.elapsed());
+    tracing::debug!("HTTP: Successfully stored artifact: {} ({} bytes)", artifact_id, size);
+
     Ok("Artifact uploaded successfully")
 }
 
 /// Handler for GET /artifacts/:artifact_id
 async fn download_artifact(
     Path((artifact_type, artifact_id)): Path<(String, String)>,
-    State(storage): State<Arc<Mutex<HashMap<String, Bytes>>>>,
-) -> Result<(StatusCode, Vec<u8>), StatusCode> {
+    State(state): State<HttpState>,
+) -> Result<Response, StatusCode> {
     tracing::info!("HTTP: Received GET request for artifact: {}/{}", artifact_type, artifact_id);
-    
-    let storage_guard = storage.lock().await;
-    let key: String = format!("{}/{}", artifact_type, artifact_id);
-    if let Some(data) = storage_guard.get(&key) {
-        tracing::debug!("HTTP: Found artifact: {} ({} bytes)", artifact_id, data.len());
-        Ok((StatusCode::OK, data.to_vec()))
+    let artifact_type = parse_artifact_type(&artifact_type).ok_or(StatusCode::BAD_REQUEST)?;
+    if !is_valid_artifact_id(&artifact_id) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let timer = state.telemetry.time_artifact_download(artifact_type);
+    let artifact = state.storage.get(artifact_type, &artifact_id).await.map_err(|e| {
+        tracing::error!("HTTP: Failed to read artifact {}: {}", artifact_id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if let Some((size, reader)) = artifact {
+        tracing::debug!("HTTP: Found artifact: {} ({} bytes)", artifact_id, size);
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_LENGTH, size)
+            // The download is timed until the stream is done with, and the timer dropped
+            .body(Body::from_stream(ReaderStream::new(reader).map(move |chunk| {
+                let _ = &timer;
+                chunk
+            })))
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
     } else {
         tracing::error!("HTTP: Artifact not found: {}", artifact_id);
+        timer.stop_and_discard();
         Err(StatusCode::NOT_FOUND)
     }
 }
 
-/// Handler for GET /health
-async fn health_check() -> &'static str {
+/// Handler for GET /metrics, in the Prometheus text format
+async fn metrics(State(state): State<HttpState>) -> Result<Response, StatusCode> {
+    let text = state.telemetry.render().map_err(|e| {
+        tracing::error!("HTTP: Failed to gather metrics: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Response::builder()
+        .header(header::CONTENT_TYPE, "text/plain; version=0.0.4")
+        .body(Body::from(text))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Count every routed request by route and status code
+async fn record_request(State(state): State<HttpState>, request: Request, next: Next) -> Response {
+    let route = request.extensions().get::<MatchedPath>().map(|path| path.as_str().to_string()).unwrap_or_default();
+    let started = Instant::now();
+    let response = next.run(request).await;
+    state.telemetry.record_http(&route, response.status().as_u16(), started.elapsed());
+    response
+}
+
+/// Handler for GET /health and /health/live, failing once the gRPC server has stopped
+async fn health_check(State(state): State<HttpState>) -> (StatusCode, &'static str) {
     tracing::debug!("HTTP: Health check requested");
-    "OK"
+    if state.health.is_live() {
+        (StatusCode::OK, "OK")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "gRPC server stopped")
+    }
+}
+
+/// Handler for GET /health/ready, listing the state of each check
+async fn readiness_check(State(state): State<HttpState>) -> (StatusCode, String) {
+    let report = state.health.check().await;
+    let status = if report.is_ready() { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, report.summary())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rpc_types::ArtifactType;
+    use tokio::io::AsyncReadExt;
 
     #[tokio::test]
     async fn test_storage_operations() {
         let server = HttpServer::new(0); // Use port 0 for testing
         let storage = server.get_storage();
-        
-        let test_data = Bytes::from("test data");
+
+        let test_data = b"test data".to_vec();
         let artifact_id = "test_artifact_123".to_string();
-        
+
         // Store data
-        storage.lock().await.insert(artifact_id.clone(), test_data.clone());
-        
+        storage.put(ArtifactType::Stdin, &artifact_id, Box::pin(std::io::Cursor::new(test_data.clone()))).await.unwrap();
+
         // Retrieve data
-        let retrieved = storage.lock().await.get(&artifact_id).cloned();
-        assert_eq!(retrieved, Some(test_data));
+        let (_, mut reader) = storage.get(ArtifactType::Stdin, &artifact_id).await.unwrap().unwrap();
+        let mut retrieved = Vec::new();
+        reader.read_to_end(&mut retrieved).await.unwrap();
+        assert_eq!(retrieved, test_data);
     }
 }
@@ -0,0 +1,408 @@
+use rpc_types::*;
+use tonic::Status;
+
+use crate::server::auction::parse_amount;
+use crate::storage::Storage;
+
+/// Counterpart of the credits added with `add_credit` and withdrawn, its balance is minus the credits in circulation
+pub const ISSUANCE_ACCOUNT: &[u8] = b"issuance";
+/// Holds the maximum cost of proof requests until they are settled
+pub const ESCROW_ACCOUNT: &[u8] = b"escrow";
+
+/// Double-entry credit ledger.
+///
+/// Every operation posts balance logs whose amounts sum to zero, so credits
+/// only move between accounts. Accounts can't go negative, except the
+/// issuance account.
+///
+/// Proof requests escrow their maximum cost when they are created. Once
+/// settled, fulfilled requests pay the base fee to the treasury and the gas
+/// used at the request price to their fulfiller, and the rest goes back to
+/// the requester.
+#[derive(Debug, Clone)]
+pub struct Ledger {
+    storage: Storage,
+    treasury: Vec<u8>,
+}
+
+impl Ledger {
+    pub fn new(storage: Storage) -> Self {
+        Self { storage, treasury: vec![0; 20] }
+    }
+
+    /// Account receiving the base fees
+    pub fn with_treasury(mut self, treasury: Vec<u8>) -> Self {
+        self.treasury = treasury;
+        self
+    }
+
+    pub fn balance(&self, address: &[u8]) -> Result<i128, Status> {
+        Ok(self.storage.get_balance(address)?)
+    }
+
+    pub fn add_credit(&self, address: &[u8], amount: u128, tx_hash: &[u8], now: u64) -> Result<(), Status> {
+        self.post(
+            vec![
+                (address.to_vec(), BalanceOperation::Deposit, signed(amount)?),
+                (ISSUANCE_ACCOUNT.to_vec(), BalanceOperation::Deposit, -signed(amount)?),
+            ],
+            tx_hash,
+            now,
+        )
+    }
+
+    /// Move `amount` from `from` to `to`, and the fee amount from `from` to the fee recipient
+    pub fn transfer(&self, from: &[u8], to: &[u8], amount: u128, (fee_recipient, fee): (&[u8], u128), tx_hash: &[u8], now: u64) -> Result<(), Status> {
+        self.post(
+            vec![
+                (from.to_vec(), BalanceOperation::TransferOut, -signed(amount)?),
+                (to.to_vec(), BalanceOperation::TransferIn, signed(amount)?),
+                (from.to_vec(), BalanceOperation::TransferFee, -signed(fee)?),
+                (fee_recipient.to_vec(), BalanceOperation::TransferFee, signed(fee)?),
+            ],
+            tx_hash,
+            now,
+        )
+    }
+
+    /// Transfer credits from a prover to the staking vault together with the stake logs
+    /// recording them, nothing is written unless both apply
+    #[allow(clippy::too_many_arguments)]
+    pub fn lock_stake(
+        &self,
+        from: &[u8],
+        vault: &[u8],
+        amount: u128,
+        (fee_recipient, fee): (&[u8], u128),
+        stake_logs: &[StakeBalanceLog],
+        tx_hash: &[u8],
+        now: u64,
+    ) -> Result<(), Status> {
+        self.post_with_stake(
+            vec![
+                (from.to_vec(), BalanceOperation::TransferOut, -signed(amount)?),
+                (vault.to_vec(), BalanceOperation::TransferIn, signed(amount)?),
+                (from.to_vec(), BalanceOperation::TransferFee, -signed(fee)?),
+                (fee_recipient.to_vec(), BalanceOperation::TransferFee, signed(fee)?),
+            ],
+            stake_logs,
+            tx_hash,
+            now,
+        )
+    }
+
+    /// Move a fee from `from` to the fee recipient, logged as `operation`
+    pub fn pay_fee(&self, operation: BalanceOperation, from: &[u8], fee_recipient: &[u8], fee: u128, tx_hash: &[u8], now: u64) -> Result<(), Status> {
+        self.post(
+            vec![
+                (from.to_vec(), operation, -signed(fee)?),
+                (fee_recipient.to_vec(), operation, signed(fee)?),
+            ],
+            tx_hash,
+            now,
+        )
+    }
+
+    /// Take `amount` out of circulation from `account`, and the fee amount from `account` to the fee recipient
+    pub fn withdraw(&self, account: &[u8], amount: u128, (fee_recipient, fee): (&[u8], u128), tx_hash: &[u8], now: u64) -> Result<(), Status> {
+        self.post(
+            vec![
+                (account.to_vec(), BalanceOperation::WithdrawRequest, -signed(amount)?),
+                (ISSUANCE_ACCOUNT.to_vec(), BalanceOperation::WithdrawRequest, signed(amount)?),
+                (account.to_vec(), BalanceOperation::WithdrawFee, -signed(fee)?),
+                (fee_recipient.to_vec(), BalanceOperation::WithdrawFee, signed(fee)?),
+            ],
+            tx_hash,
+            now,
+        )
+    }
+
+    /// Move `amount` from `from` to the treasury, applying `stake_logs` in the same write
+    pub fn pay_treasury(&self, from: &[u8], amount: u128, stake_logs: &[StakeBalanceLog], tx_hash: &[u8], now: u64) -> Result<(), Status> {
+        self.post_with_stake(
+            vec![
+                (from.to_vec(), BalanceOperation::TransferOut, -signed(amount)?),
+                (self.treasury.clone(), BalanceOperation::TransferIn, signed(amount)?),
+            ],
+            stake_logs,
+            tx_hash,
+            now,
+        )
+    }
+
+    /// Move `amount` from `from` back to `to`, and the fee amount from `to` to the fee recipient,
+    /// applying `stake_logs` in the same write
+    #[allow(clippy::too_many_arguments)]
+    pub fn release_stake(
+        &self,
+        from: &[u8],
+        to: &[u8],
+        amount: u128,
+        (fee_recipient, fee): (&[u8], u128),
+        stake_logs: &[StakeBalanceLog],
+        tx_hash: &[u8],
+        now: u64,
+    ) -> Result<(), Status> {
+        self.post_with_stake(
+            vec![
+                (from.to_vec(), BalanceOperation::TransferOut, -signed(amount)?),
+                (to.to_vec(), BalanceOperation::TransferIn, signed(amount)?),
+                (to.to_vec(), BalanceOperation::WithdrawFee, -signed(fee)?),
+                (fee_recipient.to_vec(), BalanceOperation::WithdrawFee, signed(fee)?),
+            ],
+            stake_logs,
+            tx_hash,
+            now,
+        )
+    }
+
+    /// Escrow the maximum cost of a new request from its requester
+    pub fn escrow_request(&self, requester: &[u8], amount: u128, tx_hash: &[u8], now: u64) -> Result<(), Status> {
+        self.post(
+            vec![
+                (requester.to_vec(), BalanceOperation::Request, -signed(amount)?),
+                (ESCROW_ACCOUNT.to_vec(), BalanceOperation::Request, signed(amount)?),
+            ],
+            tx_hash,
+            now,
+        )
+    }
+
+    /// Give back an escrow taken for a request that was then not stored
+    pub fn refund_escrow(&self, requester: &[u8], amount: u128, tx_hash: &[u8], now: u64) -> Result<(), Status> {
+        self.post(
+            vec![
+                (ESCROW_ACCOUNT.to_vec(), BalanceOperation::Request, -signed(amount)?),
+                (requester.to_vec(), BalanceOperation::Request, signed(amount)?),
+            ],
+            tx_hash,
+            now,
+        )
+    }
+
+    /// Release the escrow of a fulfilled or unfulfillable request, filling its `refund_amount`.
+    /// Requests are only settled once, unfinished requests are left alone.
+    pub fn settle_request(&self, request_id: &[u8], now: u64) -> Result<(), Status> {
+        // The request is only marked settled together with its balance logs, so a failed
+        // posting leaves it to be settled again
+        let settlement = self.storage.update_proof_request_with_balance_logs(request_id, ISSUANCE_ACCOUNT, |request, status| {
+            let fulfilled = request.fulfillment_status == FulfillmentStatus::Fulfilled as i32;
+            let unfulfillable = request.fulfillment_status == FulfillmentStatus::Unfulfillable as i32;
+            if request.refund_amount.is_some() || !(fulfilled || unfulfillable) {
+                return Ok::<_, Status>((None, Vec::new()));
+            }
+            let escrowed = request.deduction_amount.as_deref().and_then(parse_amount).unwrap_or(0);
+            let (fee, gas_cost) = if fulfilled { request_cost(request, escrowed) } else { (0, 0) };
+            let refund = escrowed - fee - gas_cost;
+            request.refund_amount = Some(refund.to_string());
+            let tx_hash = status.fulfill_tx_hash.clone().unwrap_or_else(|| request.tx_hash.clone());
+            let entries = vec![
+                (ESCROW_ACCOUNT.to_vec(), BalanceOperation::Request, -signed(fee + gas_cost + refund)?),
+                (self.treasury.clone(), BalanceOperation::Reward, signed(fee)?),
+                (request.fulfiller.clone().unwrap_or_default(), BalanceOperation::Reward, signed(gas_cost)?),
+                (request.requester.clone(), BalanceOperation::Request, signed(refund)?),
+            ];
+            Ok((Some((fee, gas_cost, refund)), balance_logs(entries, &tx_hash, now)))
+        })?;
+        let Some(settlement) = settlement else {
+            return Err(Status::failed_precondition("Insufficient balance"));
+        };
+        if let Some((fee, gas_cost, refund)) = settlement {
+            tracing::info!(
+                "LEDGER: Settled request {}: fee {}, gas cost {}, refund {}",
+                hex::encode(request_id),
+                fee,
+                gas_cost,
+                refund
+            );
+        }
+        Ok(())
+    }
+
+    /// Post balanced entries, entries of zero are left out
+    fn post(&self, entries: Vec<(Vec<u8>, BalanceOperation, i128)>, tx_hash: &[u8], now: u64) -> Result<(), Status> {
+        self.post_with_stake(entries, &[], tx_hash, now)
+    }
+
+    /// Post balanced entries together with stake logs, nothing is written unless both apply
+    fn post_with_stake(&self, entries: Vec<(Vec<u8>, BalanceOperation, i128)>, stake_logs: &[StakeBalanceLog], tx_hash: &[u8], now: u64) -> Result<(), Status> {
+        let logs = balance_logs(entries, tx_hash, now);
+        if !self.storage.apply_balance_and_stake_logs(&logs, stake_logs, ISSUANCE_ACCOUNT)? {
+            let missing = if stake_logs.is_empty() { "balance" } else { "balance or stake" };
+            return Err(Status::failed_precondition(format!("Insufficient {}", missing)));
+        }
+        Ok(())
+    }
+}
+
+/// Most a request can cost: the base fee plus its gas limit at its max price per PGU
+pub fn max_request_cost(request: &ProofRequest) -> u128 {
+    let base_fee = request.base_fee.as_deref().and_then(parse_amount).unwrap_or(0);
+    let gas_limit = if request.gas_limit == 0 { request.cycle_limit } else { request.gas_limit };
+    let max_price = request.max_price_per_pgu.as_deref().and_then(parse_amount).unwrap_or(0);
+    base_fee.saturating_add((gas_limit as u128).saturating_mul(max_price))
+}
+
+/// Base fee and gas cost of a fulfilled request, at the winning bid price for auctions
+/// and at the max price otherwise, capped by what was escrowed
+pub(crate) fn request_cost(request: &ProofRequest, escrowed: u128) -> (u128, u128) {
+    let base_fee = request.base_fee.as_deref().and_then(parse_amount).unwrap_or(0).min(escrowed);
+    let price = match request.gas_price {
+        Some(price) => price as u128,
+        None => request.max_price_per_pgu.as_deref().and_then(parse_amount).unwrap_or(0),
+    };
+    let gas = request.gas_used.or(request.cycles).unwrap_or(0) as u128;
+    (base_fee, gas.saturating_mul(price).min(escrowed - base_fee))
+}
+
+/// Logs of balanced entries, entries of zero are left out
+fn balance_logs(entries: Vec<(Vec<u8>, BalanceOperation, i128)>, tx_hash: &[u8], now: u64) -> Vec<BalanceLog> {
+    debug_assert_eq!(entries.iter().map(|(_, _, amount)| amount).sum::<i128>(), 0, "unbalanced ledger entries");
+    entries
+        .into_iter()
+        .filter(|(_, _, amount)| *amount != 0)
+        .map(|(address, operation, amount)| BalanceLog {
+            address,
+            operation: operation as i32,
+            amount: amount.to_string(),
+            tx_hash: tx_hash.to_vec(),
+            created_at: now,
+        })
+        .collect()
+}
+
+pub(crate) fn signed(amount: u128) -> Result<i128, Status> {
+    i128::try_from(amount).map_err(|_| Status::invalid_argument(format!("Amount {} is too large", amount)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_escrow_and_settlement() {
+        let storage = Storage::default();
+        let ledger = Ledger::new(storage.clone()).with_treasury(vec![3; 20]);
+        let (requester, prover) = (vec![1; 20], vec![2; 20]);
+
+        let request = ProofRequest {
+            request_id: vec![9; 32],
+            requester: requester.clone(),
+            fulfiller: Some(prover.clone()),
+            base_fee: Some("10".to_string()),
+            max_price_per_pgu: Some("2".to_string()),
+            gas_limit: 100,
+            ..Default::default()
+        };
+        let max_cost = max_request_cost(&request);
+        assert_eq!(max_cost, 210);
+        assert_eq!(ledger.escrow_request(&requester, max_cost, &[0; 32], 1).unwrap_err().code(), tonic::Code::FailedPrecondition);
+
+        ledger.add_credit(&requester, 1000, &[0; 32], 1).unwrap();
+        ledger.escrow_request(&requester, max_cost, &[0; 32], 2).unwrap();
+        ledger.refund_escrow(&requester, max_cost, &[0; 32], 2).unwrap();
+        assert_eq!(ledger.balance(&requester).unwrap(), 1000);
+        ledger.escrow_request(&requester, max_cost, &[0; 32], 2).unwrap();
+        let request = ProofRequest { deduction_amount: Some(max_cost.to_string()), ..request };
+        storage.put_proof_request(&request, &GetProofRequestStatusResponse::default()).unwrap();
+
+        // Not finished yet
+        ledger.settle_request(&request.request_id, 3).unwrap();
+        assert_eq!(ledger.balance(ESCROW_ACCOUNT).unwrap(), 210);
+
+        storage
+            .update_proof_request(&request.request_id, |request, _| {
+                request.fulfillment_status = FulfillmentStatus::Fulfilled as i32;
+                request.gas_used = Some(40);
+                Ok::<_, Status>(())
+            })
+            .unwrap();
+        ledger.settle_request(&request.request_id, 4).unwrap();
+        ledger.settle_request(&request.request_id, 5).unwrap();
+
+        assert_eq!(ledger.balance(&requester).unwrap(), 1000 - 10 - 80);
+        assert_eq!(ledger.balance(&prover).unwrap(), 80);
+        assert_eq!(ledger.balance(&[3; 20]).unwrap(), 10);
+        assert_eq!(ledger.balance(ESCROW_ACCOUNT).unwrap(), 0);
+        assert_eq!(ledger.balance(ISSUANCE_ACCOUNT).unwrap(), -1000);
+        let (settled, _) = storage.get_proof_request(&request.request_id).unwrap().unwrap();
+        assert_eq!(settled.refund_amount.as_deref(), Some("120"));
+
+        // Every operation is balanced
+        let logs = storage.balance_logs().unwrap();
+        assert_eq!(logs.iter().map(|log| log.amount.parse::<i128>().unwrap()).sum::<i128>(), 0);
+    }
+
+    #[test]
+    fn test_settlement_adds_to_the_prover_earnings() {
+        let storage = Storage::default();
+        let ledger = Ledger::new(storage.clone()).with_treasury(vec![3; 20]);
+        let (requester, prover) = (vec![1; 20], vec![2; 20]);
+        ledger.add_credit(&requester, 1000, &[0; 32], 1).unwrap();
+        ledger.add_credit(&prover, 50, &[0; 32], 1).unwrap();
+        storage.update_prover(&prover, |stats, _| stats.total_earnings = "7".to_string()).unwrap();
+
+        let request = ProofRequest {
+            request_id: vec![9; 32],
+            requester: requester.clone(),
+            fulfiller: Some(prover.clone()),
+            fulfillment_status: FulfillmentStatus::Fulfilled as i32,
+            base_fee: Some("10".to_string()),
+            max_price_per_pgu: Some("2".to_string()),
+            gas_limit: 100,
+            gas_used: Some(40),
+            deduction_amount: Some("210".to_string()),
+            ..Default::default()
+        };
+        ledger.escrow_request(&requester, 210, &[0; 32], 2).unwrap();
+        storage.put_proof_request(&request, &GetProofRequestStatusResponse::default()).unwrap();
+
+        let before = ledger.balance(&prover).unwrap();
+        ledger.settle_request(&request.request_id, 3).unwrap();
+        ledger.settle_request(&request.request_id, 4).unwrap();
+        let earned = ledger.balance(&prover).unwrap() - before;
+        assert_eq!(earned, 80);
+        let (stats, _) = storage.get_prover(&prover).unwrap().unwrap();
+        assert_eq!(stats.total_earnings, (7 + earned).to_string());
+        // The treasury is no prover
+        assert!(storage.get_prover(&[3; 20]).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_failed_settlement_leaves_request_unsettled() {
+        let storage = Storage::default();
+        let ledger = Ledger::new(storage.clone());
+        // Nothing was escrowed for the request
+        let request = ProofRequest {
+            request_id: vec![9; 32],
+            requester: vec![1; 20],
+            fulfillment_status: FulfillmentStatus::Unfulfillable as i32,
+            deduction_amount: Some("100".to_string()),
+            ..Default::default()
+        };
+        storage.put_proof_request(&request, &GetProofRequestStatusResponse::default()).unwrap();
+
+        assert_eq!(ledger.settle_request(&request.request_id, 2).unwrap_err().code(), tonic::Code::FailedPrecondition);
+        let (unsettled, _) = storage.get_proof_request(&request.request_id).unwrap().unwrap();
+        assert_eq!(unsettled.refund_amount, None);
+        assert!(storage.balance_logs().unwrap().is_empty());
+
+        ledger.add_credit(ESCROW_ACCOUNT, 100, &[0; 32], 3).unwrap();
+        ledger.settle_request(&request.request_id, 4).unwrap();
+        assert_eq!(ledger.balance(&[1; 20]).unwrap(), 100);
+    }
+
+    #[test]
+    fn test_withdraw_takes_credits_out_of_circulation() {
+        let storage = Storage::default();
+        let ledger = Ledger::new(storage.clone());
+        let (prover, auctioneer) = (vec![1; 20], vec![2; 20]);
+        ledger.add_credit(&prover, 100, &[0; 32], 1).unwrap();
+
+        assert_eq!(ledger.withdraw(&prover, 100, (&auctioneer, 1), &[0; 32], 2).unwrap_err().code(), tonic::Code::FailedPrecondition);
+        ledger.withdraw(&prover, 60, (&auctioneer, 1), &[0; 32], 2).unwrap();
+        assert_eq!(ledger.balance(&prover).unwrap(), 39);
+        assert_eq!(ledger.balance(&auctioneer).unwrap(), 1);
+        assert_eq!(ledger.balance(ISSUANCE_ACCOUNT).unwrap(), -40);
+    }
+}
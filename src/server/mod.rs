@@ -1,9 +1,42 @@
+#[allow(clippy::module_inception)]
 pub mod server;
 pub mod prover_network_service;
 pub mod artifacts_service;
+pub mod analytics;
+pub mod assignment;
+pub mod auction;
+pub mod auth;
+pub mod deadline;
+pub mod execution;
+pub mod health;
 pub mod http_server;
+pub mod ledger;
+pub mod params;
+pub mod provers;
+pub mod config;
+pub mod retention;
+pub mod search;
+pub mod staking;
+pub mod telemetry;
+pub mod verifier_service;
 
 pub use server::*;
 pub use prover_network_service::*;
 pub use artifacts_service::*;
+pub use analytics::*;
+pub use assignment::*;
+pub use auction::*;
+pub use auth::*;
+pub use deadline::*;
+pub use execution::*;
+pub use health::*;
 pub use http_server::*;
+pub use ledger::*;
+pub use params::*;
+pub use provers::*;
+pub use config::*;
+pub use retention::*;
+pub use search::*;
+pub use staking::*;
+pub use telemetry::*;
+pub use verifier_service::*;
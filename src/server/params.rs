@@ -0,0 +1,277 @@
+use anyhow::Result;
+use rpc_types::*;
+use serde::{Deserialize, Deserializer};
+use tonic::Status;
+
+use crate::server::auction::parse_amount;
+use crate::server::auth::default_domain;
+
+/// Longest minimum auction period a request can ask for, one day
+const MAX_AUCTION_PERIOD_SECS: u64 = 24 * 60 * 60;
+/// Furthest a request deadline can be from when it is made, 30 days
+const MAX_DEADLINE_SECS: u64 = 30 * 24 * 60 * 60;
+
+/// Network parameters that requesters sign into their proof requests.
+///
+/// They are served by `get_proof_request_params`, so stock SDK clients build
+/// bodies with them, and `request_proof` rejects bodies that don't use them.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkParams {
+    /// Domain separator of every signed body
+    #[serde(deserialize_with = "hex_bytes")]
+    pub domain: Vec<u8>,
+    /// Only account allowed to manage reservations
+    #[serde(deserialize_with = "hex_bytes")]
+    pub owner: Vec<u8>,
+    /// Only account allowed to settle auctions
+    #[serde(deserialize_with = "hex_bytes")]
+    pub auctioneer: Vec<u8>,
+    /// Only account allowed to report executions, the auctioneer when unset
+    #[serde(deserialize_with = "hex_bytes")]
+    pub executor: Vec<u8>,
+    #[serde(deserialize_with = "hex_bytes")]
+    pub verifier: Vec<u8>,
+    #[serde(deserialize_with = "hex_bytes")]
+    pub treasury: Vec<u8>,
+    /// Highest max price per prover gas unit a request can set, also used when a request sets none
+    pub max_price_per_pgu: String,
+    /// Lowest base fee a request can set, the same for every proof mode
+    pub base_fee: String,
+    /// Lowest fee `set_delegation` pays to the auctioneer
+    pub delegation_fee: String,
+    /// Lowest fee `withdraw` pays to the auctioneer
+    pub withdraw_fee: String,
+    /// Escrow the maximum cost of proof requests from the credits of their requester,
+    /// requests are free otherwise
+    pub metering: bool,
+}
+
+impl Default for NetworkParams {
+    fn default() -> Self {
+        Self {
+            domain: default_domain(),
+            owner: vec![0; 20],
+            auctioneer: vec![0; 20],
+            executor: vec![0; 20],
+            verifier: vec![0; 20],
+            treasury: vec![0; 20],
+            max_price_per_pgu: "1000000000".to_string(),
+            base_fee: "0".to_string(),
+            delegation_fee: "0".to_string(),
+            withdraw_fee: "0".to_string(),
+            metering: false,
+        }
+    }
+}
+
+impl NetworkParams {
+    /// Override the parameters with the `SPN_DOMAIN`, `SPN_OWNER`, `SPN_AUCTIONEER`, `SPN_EXECUTOR`,
+    /// `SPN_VERIFIER`, `SPN_TREASURY`, `SPN_MAX_PRICE_PER_PGU`, `SPN_BASE_FEE`, `SPN_DELEGATION_FEE`,
+    /// `SPN_WITHDRAW_FEE` and `SPN_METERING` environment variables
+    pub fn apply_env(&mut self) -> Result<()> {
+        let params = self;
+        let hex_var = |name: &str, value: &mut Vec<u8>| -> Result<()> {
+            if let Ok(v) = std::env::var(name) {
+                *value = hex::decode(v.trim().trim_start_matches("0x")).map_err(|e| anyhow::anyhow!("Invalid {}: {}", name, e))?;
+            }
+            Ok(())
+        };
+        hex_var("SPN_DOMAIN", &mut params.domain)?;
+        hex_var("SPN_OWNER", &mut params.owner)?;
+        hex_var("SPN_AUCTIONEER", &mut params.auctioneer)?;
+        hex_var("SPN_EXECUTOR", &mut params.executor)?;
+        hex_var("SPN_VERIFIER", &mut params.verifier)?;
+        hex_var("SPN_TREASURY", &mut params.treasury)?;
+        let amount_var = |name: &str, value: &mut String| -> Result<()> {
+            if let Ok(v) = std::env::var(name) {
+                parse_amount(&v).ok_or_else(|| anyhow::anyhow!("Invalid {}: {}", name, v))?;
+                *value = v.trim().to_string();
+            }
+            Ok(())
+        };
+        amount_var("SPN_MAX_PRICE_PER_PGU", &mut params.max_price_per_pgu)?;
+        amount_var("SPN_BASE_FEE", &mut params.base_fee)?;
+        amount_var("SPN_DELEGATION_FEE", &mut params.delegation_fee)?;
+        amount_var("SPN_WITHDRAW_FEE", &mut params.withdraw_fee)?;
+        if let Ok(metering) = std::env::var("SPN_METERING") {
+            params.metering = metering.trim().parse().map_err(|_| anyhow::anyhow!("Invalid SPN_METERING: {}", metering))?;
+        }
+        Ok(())
+    }
+
+    /// Refuse to start without the accounts the network can't run without, they
+    /// would otherwise default to the zero address nobody holds the key of
+    pub fn validate(&self) -> Result<()> {
+        // Manages reservations and mints credits, without which metered requests can't be paid
+        if is_unset(&self.owner) {
+            let reason = if self.metering { ", it mints the credits metered requests are paid with" } else { "" };
+            anyhow::bail!("The owner address is required{}, set SPN_OWNER or network.owner", reason);
+        }
+        if is_unset(&self.auctioneer) {
+            anyhow::bail!("The auctioneer address is required, set SPN_AUCTIONEER or network.auctioneer");
+        }
+        Ok(())
+    }
+
+    /// Account reporting executions, proofs are only accepted for requests it has executed
+    pub fn executor(&self) -> &[u8] {
+        if is_unset(&self.executor) {
+            &self.auctioneer
+        } else {
+            &self.executor
+        }
+    }
+
+    /// Parameters to sign into a request of the given mode
+    pub fn to_response(&self, _mode: ProofMode) -> GetProofRequestParamsResponse {
+        GetProofRequestParamsResponse {
+            domain: self.domain.clone(),
+            auctioneer: self.auctioneer.clone(),
+            executor: self.executor().to_vec(),
+            verifier: self.verifier.clone(),
+            max_price_per_pgu: self.max_price_per_pgu.clone(),
+            base_fee: self.base_fee.clone(),
+            treasury: self.treasury.clone(),
+        }
+    }
+
+    /// Reject request bodies whose parameters don't match ours, or whose auction period or
+    /// deadline reach too far past `now`. The domain is checked with the signature, see
+    /// [`crate::server::auth::verify_domain`].
+    pub fn check_request(&self, body: &RequestProofRequestBody, now: u64) -> Result<(), Status> {
+        for (role, value, expected) in [
+            ("auctioneer", &body.auctioneer, &self.auctioneer),
+            ("executor", &body.executor, &self.executor().to_vec()),
+            ("verifier", &body.verifier, &self.verifier),
+            ("treasury", &body.treasury, &self.treasury),
+        ] {
+            if value != expected {
+                return Err(Status::invalid_argument(format!(
+                    "Invalid {} {}, expected {}",
+                    role,
+                    hex::encode(value),
+                    hex::encode(expected)
+                )));
+            }
+        }
+
+        // Proofs of requests without a mode or version could not be verified later
+        if !matches!(
+            ProofMode::try_from(body.mode),
+            Ok(ProofMode::Core | ProofMode::Compressed | ProofMode::Plonk | ProofMode::Groth16)
+        ) {
+            return Err(Status::invalid_argument(format!("Invalid proof mode: {}", body.mode)));
+        }
+        if body.version.is_empty() {
+            return Err(Status::invalid_argument("A version is required"));
+        }
+
+        let base_fee = if body.base_fee.is_empty() { Some(0) } else { parse_amount(&body.base_fee) };
+        let base_fee = base_fee.ok_or_else(|| Status::invalid_argument(format!("Invalid base fee: {}", body.base_fee)))?;
+        if base_fee < parse_amount(&self.base_fee).unwrap_or(0) {
+            return Err(Status::invalid_argument(format!("Base fee {} is below {}", base_fee, self.base_fee)));
+        }
+
+        if !body.max_price_per_pgu.is_empty() {
+            let max_price = parse_amount(&body.max_price_per_pgu)
+                .ok_or_else(|| Status::invalid_argument(format!("Invalid max price per PGU: {}", body.max_price_per_pgu)))?;
+            if max_price > parse_amount(&self.max_price_per_pgu).unwrap_or(u128::MAX) {
+                return Err(Status::invalid_argument(format!(
+                    "Max price per PGU {} is above {}",
+                    max_price, self.max_price_per_pgu
+                )));
+            }
+        }
+
+        if body.min_auction_period > MAX_AUCTION_PERIOD_SECS {
+            return Err(Status::invalid_argument(format!(
+                "Min auction period {} is above {}",
+                body.min_auction_period, MAX_AUCTION_PERIOD_SECS
+            )));
+        }
+        if body.deadline > now.saturating_add(MAX_DEADLINE_SECS) {
+            return Err(Status::invalid_argument(format!("Deadline {} is more than {}s away", body.deadline, MAX_DEADLINE_SECS)));
+        }
+        Ok(())
+    }
+}
+
+/// Empty or zero addresses are not configured
+pub(crate) fn is_unset(address: &[u8]) -> bool {
+    address.iter().all(|b| *b == 0)
+}
+
+/// Byte fields are written as `0x` prefixed hex strings
+pub(crate) fn hex_bytes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    hex::decode(value.trim_start_matches("0x")).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_request() {
+        let params = NetworkParams { base_fee: "10".to_string(), ..Default::default() };
+        let response = params.to_response(ProofMode::Groth16);
+        let mut body = RequestProofRequestBody {
+            domain: response.domain,
+            auctioneer: response.auctioneer,
+            executor: response.executor,
+            verifier: response.verifier,
+            treasury: response.treasury,
+            base_fee: response.base_fee,
+            max_price_per_pgu: response.max_price_per_pgu,
+            mode: ProofMode::Groth16 as i32,
+            version: "sp1-v5.0.0".to_string(),
+            ..Default::default()
+        };
+        assert!(params.check_request(&body, 1000).is_ok());
+
+        body.mode = ProofMode::UnspecifiedProofMode as i32;
+        assert_eq!(params.check_request(&body, 1000).unwrap_err().code(), tonic::Code::InvalidArgument);
+        body.mode = 42;
+        assert_eq!(params.check_request(&body, 1000).unwrap_err().code(), tonic::Code::InvalidArgument);
+        body.mode = ProofMode::Groth16 as i32;
+        body.version = String::new();
+        assert_eq!(params.check_request(&body, 1000).unwrap_err().code(), tonic::Code::InvalidArgument);
+        body.version = "sp1-v5.0.0".to_string();
+
+        body.base_fee = "9".to_string();
+        assert_eq!(params.check_request(&body, 1000).unwrap_err().code(), tonic::Code::InvalidArgument);
+
+        body.base_fee = "10".to_string();
+        body.max_price_per_pgu = "1000000001".to_string();
+        assert_eq!(params.check_request(&body, 1000).unwrap_err().code(), tonic::Code::InvalidArgument);
+
+        body.max_price_per_pgu = String::new();
+        body.verifier = vec![1; 20];
+        assert_eq!(params.check_request(&body, 1000).unwrap_err().code(), tonic::Code::InvalidArgument);
+
+        // Auction periods and deadlines too far away, which would overflow once added to a time
+        body.verifier = params.verifier.clone();
+        body.min_auction_period = u64::MAX;
+        assert_eq!(params.check_request(&body, 1000).unwrap_err().code(), tonic::Code::InvalidArgument);
+        body.min_auction_period = MAX_AUCTION_PERIOD_SECS;
+        assert!(params.check_request(&body, 1000).is_ok());
+        body.deadline = u64::MAX;
+        assert_eq!(params.check_request(&body, 1000).unwrap_err().code(), tonic::Code::InvalidArgument);
+        body.deadline = 1000 + MAX_DEADLINE_SECS;
+        assert!(params.check_request(&body, 1000).is_ok());
+    }
+
+    #[test]
+    fn test_required_addresses() {
+        let params = NetworkParams { owner: vec![3; 20], auctioneer: vec![1; 20], executor: vec![2; 20], ..Default::default() };
+        assert!(params.validate().is_ok());
+        assert!(NetworkParams { owner: vec![0; 20], ..params.clone() }.validate().is_err());
+        assert!(NetworkParams { auctioneer: vec![0; 20], ..params.clone() }.validate().is_err());
+        // The auctioneer reports executions when no executor is set
+        let params = NetworkParams { executor: Vec::new(), ..params };
+        assert!(params.validate().is_ok());
+        assert_eq!(params.executor(), [1; 20]);
+        assert_eq!(params.to_response(ProofMode::Groth16).executor, vec![1; 20]);
+    }
+}
@@ -1,20 +1,307 @@
 use anyhow::Result;
 use rpc_types::*;
-use std::collections::HashMap;
-use tokio::sync::Mutex;
 use tonic::{Request, Response, Status};
-use ethers_core::types::{Signature};
-use ethers_core::utils::hash_message; // adds the EIP-191 prefix
-use eyre;
 use rand::random;
-use prost::Message;
+use std::sync::Arc;
+
+use crate::server::analytics::{graph, Analytics};
+use crate::server::artifacts_service::{generate_artifact_id, generate_presigned_url};
+use crate::server::config::DEFAULT_PUBLIC_URL;
+use crate::server::execution::{apply_execution, require_executed};
+use crate::server::assignment::Assigner;
+use crate::server::auction::{parse_amount, Auctioneer};
+use crate::server::auth::{authenticate, require_signer, verify_domain, SignedRequest};
+use crate::server::deadline::require_before_deadline;
+use crate::server::ledger::{max_request_cost, Ledger, ESCROW_ACCOUNT, ISSUANCE_ACCOUNT};
+use crate::server::params::NetworkParams;
+use crate::server::provers::ProverRegistry;
+use crate::server::search::SearchIndex;
+use crate::server::staking::{Staking, StakingParams};
+use crate::server::telemetry::Telemetry;
+use crate::server::verifier_service::{check_proof, LocalVerifier, ProofVerifier};
+use crate::storage::{ProofRequestIndex, S3Presigner, Storage, StorageError};
+
+/// Proof requests buffered per subscriber before the stream applies backpressure
+const SUBSCRIPTION_BUFFER: usize = 64;
+
+/// Request metadata resuming `get_filtered_proof_requests` after the page that returned it
+pub const CURSOR_METADATA: &str = "x-cursor";
+/// Response metadata of `get_filtered_proof_requests` when more requests may follow
+pub const NEXT_CURSOR_METADATA: &str = "x-next-cursor";
+
+/// Index entries read at once while looking for proof requests matching a filter
+const SCAN_BATCH: usize = 256;
+
+/// Entries returned by a list RPC when no limit is given
+const DEFAULT_PAGE_LIMIT: u32 = 50;
+/// Most entries returned by one call of a list RPC
+const MAX_PAGE_LIMIT: u32 = 100;
 
 /// Real gRPC service implementation for ProverNetwork
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct ProverNetworkServiceImpl {
-    /// TODO Store proof requests in memory (in real implementation this would be a database)  
-    proof_requests: Mutex<HashMap<Vec<u8>, (ProofRequest, GetProofRequestStatusResponse)>>,
-    programs: Mutex<HashMap<Vec<u8>, Program>>,
+    /// Proof requests and programs
+    storage: Storage,
+    /// Proof contest of auction strategy requests
+    auctioneer: Auctioneer,
+    /// Fulfillers of hosted and reserved strategy requests
+    assigner: Assigner,
+    /// Provers learnt from their signed requests
+    provers: ProverRegistry,
+    /// Credit balances, charged by proof requests
+    ledger: Ledger,
+    /// Prover stakes, held in the staking vault
+    staking: Staking,
+    /// Serves `get_search_results` and `get_prover_search_results`
+    search: SearchIndex,
+    /// Buckets behind the proof request metrics and graphs
+    analytics: Analytics,
+    /// Prometheus metrics of fulfillments and subscriptions
+    telemetry: Telemetry,
+    /// When set, proofs are stored in the object store and public URIs are presigned download URLs
+    s3: Option<S3Presigner>,
+    /// Parameters that requesters sign into their proof requests
+    params: NetworkParams,
+    /// Base URL of our HTTP server as seen by provers
+    public_url: String,
+    /// Checks the proofs submitted to `fulfill_proof`
+    verifier: Arc<dyn ProofVerifier>,
+}
+
+impl Default for ProverNetworkServiceImpl {
+    fn default() -> Self {
+        Self::new(Storage::default())
+    }
+}
+
+impl ProverNetworkServiceImpl {
+    pub fn new(storage: Storage) -> Self {
+        let ledger = Ledger::new(storage.clone());
+        let staking = Staking::new(storage.clone(), ledger.clone());
+        // The assigner picks hosted provers among the ones the service learns
+        let provers = ProverRegistry::new(storage.clone());
+        Self {
+            auctioneer: Auctioneer::new(storage.clone()),
+            assigner: Assigner::new(storage.clone(), provers.clone(), staking.clone()),
+            provers,
+            ledger,
+            staking,
+            search: SearchIndex::new(storage.clone()),
+            analytics: Analytics::new(storage.clone()),
+            telemetry: Telemetry::default(),
+            storage,
+            s3: None,
+            params: NetworkParams::default(),
+            public_url: DEFAULT_PUBLIC_URL.to_string(),
+            verifier: Arc::new(LocalVerifier),
+        }
+    }
+
+    /// Serve and enforce these network parameters
+    pub fn with_params(mut self, params: NetworkParams) -> Self {
+        self.ledger = self.ledger.with_treasury(params.treasury.clone());
+        self.params = params;
+        let staking = self.staking.clone();
+        self.with_staking_of(staking)
+    }
+
+    /// Minimum stake, slashing and staking vault of the provers
+    pub fn with_staking(self, params: StakingParams) -> Self {
+        let staking = self.staking.clone().with_params(params);
+        self.with_staking_of(staking)
+    }
+
+    /// Share `staking`, paying slashes through our ledger, with the assigner
+    fn with_staking_of(mut self, staking: Staking) -> Self {
+        self.staking = staking.with_ledger(self.ledger.clone());
+        self.assigner = self.assigner.with_staking(self.staking.clone());
+        self
+    }
+
+    /// Ledger sharing the service storage, to settle requests finished in the background
+    pub fn ledger(&self) -> Ledger {
+        self.ledger.clone()
+    }
+
+    /// Staking sharing the service storage, to slash provers missing deadlines in the background
+    pub fn staking(&self) -> Staking {
+        self.staking.clone()
+    }
+
+    /// Base URL of the HTTP server that proofs are uploaded to when S3 is not used
+    pub fn with_public_url(mut self, public_url: impl Into<String>) -> Self {
+        self.public_url = public_url.into();
+        self
+    }
+
+    /// Record fulfillments and subscriptions in these metrics
+    pub fn with_telemetry(mut self, telemetry: Telemetry) -> Self {
+        self.telemetry = telemetry;
+        self
+    }
+
+    /// Verify fulfilled proofs with this verifier
+    pub fn with_verifier(mut self, verifier: Arc<dyn ProofVerifier>) -> Self {
+        self.verifier = verifier;
+        self
+    }
+
+    /// Provers fulfilling hosted strategy requests
+    pub fn with_hosted_provers(mut self, provers: Vec<Vec<u8>>) -> Self {
+        self.assigner = self.assigner.with_hosted_provers(provers);
+        self
+    }
+
+    /// Provers not seen for `window` are not assigned hosted requests anymore
+    pub fn with_prover_liveness(mut self, window: std::time::Duration) -> Self {
+        self.provers = self.provers.with_liveness_window(window);
+        self.assigner = self.assigner.with_registry(self.provers.clone());
+        self
+    }
+
+    /// Assigner sharing the service storage, to assign pending hosted requests in the background
+    pub fn assigner(&self) -> Assigner {
+        self.assigner.clone()
+    }
+
+    /// Search index sharing the service storage, to keep it up to date in the background
+    pub fn search(&self) -> SearchIndex {
+        self.search.clone()
+    }
+
+    /// Auctioneer sharing the service storage, to settle auctions in the background
+    pub fn auctioneer(&self) -> Auctioneer {
+        self.auctioneer.clone()
+    }
+
+    /// Use an S3-compatible object store for artifacts
+    pub fn with_s3(mut self, s3: Option<S3Presigner>) -> Self {
+        self.s3 = s3;
+        self
+    }
+
+    /// Check the signature, domain and variant of a request and consume the nonce
+    /// of its signer, so the same signed body can't be replayed. The nonce is given
+    /// back if the request is rejected before the handler commits it.
+    fn authenticate<R: SignedRequest>(&self, request: R) -> Result<(R::Body, Vec<u8>, NonceClaim), Status> {
+        let (body, signer) = authenticate(request)?;
+        verify_domain::<R>(&body, &self.params.domain)?;
+        let nonce = R::nonce(&body);
+        if !self.storage.use_nonce(&signer, nonce)? {
+            let expected = self.storage.next_nonce(&signer)?;
+            tracing::warn!("PROVER_NETWORK: Rejected nonce {} from {}, expected {}", nonce, hex::encode(&signer), expected);
+            return Err(Status::invalid_argument(format!("Invalid nonce {}, expected {}", nonce, expected)));
+        }
+        let claim = NonceClaim { storage: self.storage.clone(), signer: signer.clone(), nonce, committed: false };
+        Ok((body, signer, claim))
+    }
+
+    /// Request as served, with download URLs presigned now
+    fn presign_request(&self, request: ProofRequest) -> ProofRequest {
+        presign_request(self.s3.as_ref(), request)
+    }
+
+    /// Delegates act for their owner without signing the delegation, so addresses that already
+    /// act for themselves, known provers or fulfillers of assigned requests, can't be taken over.
+    fn check_delegate(&self, delegate: &[u8]) -> Result<(), Status> {
+        if self.storage.get_prover(delegate)?.is_some()
+            || self.storage.count_proof_requests(&ProofRequestIndex::AssignedTo(delegate.to_vec()))? > 0
+        {
+            return Err(Status::failed_precondition(format!("{} is a prover and can't be a delegate", hex::encode(delegate))));
+        }
+        Ok(())
+    }
+
+    /// Up to `limit` requests matching `filter` from the index position `start` on, after skipping `skip` of them.
+    /// Also returns the position to resume from when the limit was reached.
+    fn find_proof_requests(&self, filter: &GetFilteredProofRequestsRequest, mut start: Vec<u8>, mut skip: usize, limit: usize) -> Result<(Vec<ProofRequest>, Option<Vec<u8>>), Status> {
+        let index = select_index(filter);
+        let mut requests = Vec::new();
+        if limit == 0 {
+            return Ok((requests, None));
+        }
+        loop {
+            let batch = self.storage.scan_proof_requests(&index, &start, SCAN_BATCH)?;
+            let Some((last, _, _)) = batch.last() else {
+                return Ok((requests, None));
+            };
+            start = [last.as_slice(), &[0]].concat();
+            for (position, request, _) in batch {
+                // Indexes are ordered by creation time past their value
+                if filter.to.is_some_and(|to| request.created_at > to) {
+                    return Ok((requests, None));
+                }
+                if !matches_filter(&request, filter) || !not_bid_by(&self.storage, &request, filter)? {
+                    continue;
+                }
+                if skip > 0 {
+                    skip -= 1;
+                    continue;
+                }
+                requests.push(request);
+                if requests.len() == limit {
+                    return Ok((requests, Some(position)));
+                }
+            }
+        }
+    }
+
+    /// Fee signed into a body, which must be at least `minimum` and go to our auctioneer
+    fn check_fee(&self, fee: &str, minimum: &str, auctioneer: &[u8]) -> Result<u128, Status> {
+        let amount = if fee.is_empty() { Some(0) } else { parse_amount(fee) };
+        let amount = amount.ok_or_else(|| Status::invalid_argument(format!("Invalid fee: {}", fee)))?;
+        if amount < parse_amount(minimum).unwrap_or(0) {
+            return Err(Status::invalid_argument(format!("Fee {} is below {}", amount, minimum)));
+        }
+        if amount > 0 && auctioneer != self.params.auctioneer.as_slice() {
+            return Err(Status::invalid_argument(format!("Invalid auctioneer {}", hex::encode(auctioneer))));
+        }
+        Ok(amount)
+    }
+
+    /// Owner an address acts for: the owner that delegated to it, or the address itself
+    fn owner_of(&self, address: &[u8]) -> Result<Vec<u8>, Status> {
+        Ok(self.storage.get_delegation_owner(address)?.unwrap_or_else(|| address.to_vec()))
+    }
+
+    /// Stake balance logs matching the filters, newest first
+    fn filter_stake_logs(&self, address: Option<Vec<u8>>, operation: Option<i32>, minimum_timestamp: Option<u64>, maximum_timestamp: Option<u64>) -> Result<Vec<StakeBalanceLog>, Status> {
+        let mut logs: Vec<StakeBalanceLog> = self
+            .storage
+            .stake_logs()?
+            .into_iter()
+            .filter(|log| address.as_ref().is_none_or(|a| a.is_empty() || &log.address == a))
+            .filter(|log| operation.is_none_or(|op| log.operation == op))
+            .filter(|log| minimum_timestamp.is_none_or(|t| log.created_at >= t))
+            .filter(|log| maximum_timestamp.is_none_or(|t| log.created_at <= t))
+            .collect();
+        logs.reverse();
+        Ok(logs)
+    }
+
+    /// Generate the location of a new proof: (upload URL, proof URI)
+    fn generate_proof_location(&self) -> (String, String) {
+        let artifact_id = generate_artifact_id();
+        match &self.s3 {
+            Some(s3) => {
+                let key = S3Presigner::artifact_key(&ArtifactType::Proof, &artifact_id);
+                (s3.presign_put(&key), s3.artifact_uri(&ArtifactType::Proof, &artifact_id))
+            }
+            None => {
+                let url = generate_presigned_url(&self.public_url, &ArtifactType::Proof, &artifact_id);
+                (url.clone(), url)
+            }
+        }
+    }
+}
+
+impl From<StorageError> for Status {
+    fn from(e: StorageError) -> Self {
+        match e {
+            StorageError::NotFound => Status::not_found("Proof request not found"),
+            e => Status::internal(format!("Storage error: {}", e)),
+        }
+    }
 }
 
 #[tonic::async_trait]
@@ -26,7 +313,12 @@ impl prover_network_server::ProverNetwork for ProverNetworkServiceImpl {
         let req = request.into_inner();
         tracing::debug!("PROVER_NETWORK: Server Request params: {:?}", req);
         tracing::debug!("PROVER_NETWORK: Server Signature received: {:?}", hex::encode(&req.signature));
-        
+        let (body, requester, nonce) = self.authenticate(req)?;
+        tracing::info!("PROVER_NETWORK: Server Recovered requester address: {:?}", hex::encode(&requester));
+        let now = chrono::Utc::now().timestamp() as u64;
+        self.params.check_request(&body, now)?;
+        let fulfiller = self.assigner.assign(body.strategy, &requester, now)?;
+
         // Generate a unique request ID
         let request_id = random::<[u8; 32]>().to_vec();
         tracing::info!("PROVER_NETWORK: Server Request_id: {:?}", hex::encode(&request_id));
@@ -38,56 +330,69 @@ impl prover_network_server::ProverNetwork for ProverNetworkServiceImpl {
                 request_id: request_id.clone(),
             }),
         };
-        
+
+        // Auction requests wait for bids, the other strategies are assigned right away
+        let fulfillment_status = if fulfiller.is_some() { FulfillmentStatus::Assigned } else { FulfillmentStatus::Requested };
+
         // Store the request for status tracking
         let status_response = GetProofRequestStatusResponse {
-            fulfillment_status: FulfillmentStatus::Assigned as i32,
+            fulfillment_status: fulfillment_status as i32,
             execution_status: ExecutionStatus::Unexecuted as i32,
             request_tx_hash: response.tx_hash.clone(),
-            deadline: req.body.as_ref().map(|b| b.deadline).unwrap_or_default(),
+            deadline: body.deadline,
             fulfill_tx_hash: None,
             proof_uri: None,
             public_values_hash: None,
             proof_public_uri: None,
         };
-        let msg_bytes: Vec<u8> = encode_body_for_signing(req.format, req.body.as_ref().ok_or_else(|| Status::invalid_argument("Request body is required"))?)
-            .map_err(|e| Status::internal(format!("Failed to encode body for signing: {}", e)))?;
-        let requester = match req.body.as_ref() {
-            Some(_body) => recover_signer_addr(msg_bytes, &req.signature)
-                .map_err(|e| Status::invalid_argument(format!("Failed to recover signer address: {}", e)))?,
-            None => return Err(Status::invalid_argument("Request body is required")),
-        };
-        tracing::info!("PROVER_NETWORK: Server Recovered requester address: {:?}", hex::encode(&requester));
-        let now = chrono::Utc::now().timestamp() as u64;
-        let vk_hash = req.body.as_ref().map(|b| b.vk_hash.clone()).unwrap_or_default();
-        let programs = self.programs.lock().await;
-        let program = programs.get(&vk_hash);
+        let program = self.storage.get_program(&body.vk_hash)?;
+        let program = program.as_ref();
         let proof_request = ProofRequest {
                 request_id: request_id.clone(),
-                vk_hash: vk_hash,
-                version: req.body.as_ref().map(|b| b.version.clone()).unwrap_or_default(),
-                mode:    req.body.as_ref().map(|b| b.mode.clone()).unwrap_or_default(),
-                strategy: req.body.as_ref().map(|b| b.strategy.clone()).unwrap_or_default(),
-                deadline: req.body.as_ref().map(|b| b.deadline.clone()).unwrap_or_default(),
-                cycle_limit: req.body.as_ref().map(|b| b.cycle_limit.clone()).unwrap_or_default(),
-                fulfillment_status: status_response.fulfillment_status.clone(),
-                execution_status: status_response.execution_status.clone(),
+                vk_hash: body.vk_hash.clone(),
+                version: body.version.clone(),
+                mode: body.mode,
+                strategy: body.strategy,
+                deadline: body.deadline,
+                cycle_limit: body.cycle_limit,
+                fulfillment_status: status_response.fulfillment_status,
+                execution_status: status_response.execution_status,
                 created_at: now,
                 updated_at: now,
                 tx_hash: response.tx_hash.clone(),
-                public_values_hash: req.body.as_ref().map(|b| b.public_values_hash.clone()).unwrap_or_default(),
-                gas_limit: req.body.as_ref().map(|b| b.gas_limit.clone()).unwrap_or_default(),
-                min_auction_period: req.body.as_ref().map(|b| b.min_auction_period.clone()).unwrap_or_default(),
-                whitelist: req.body.as_ref().map(|b| b.whitelist.clone()).unwrap_or_default(),
+                public_values_hash: body.public_values_hash.clone(),
+                gas_limit: body.gas_limit,
+                min_auction_period: body.min_auction_period,
+                whitelist: body.whitelist.clone(),
                 requester: requester.clone(),
-                fulfiller: Some(requester.clone()),
+                fulfiller,
+                settlement_status: SettlementStatus::Unsettled as i32,
+                base_fee: Some(body.base_fee.clone()).filter(|f| !f.is_empty()),
+                max_price_per_pgu: Some(if body.max_price_per_pgu.is_empty() {
+                    self.params.max_price_per_pgu.clone()
+                } else {
+                    body.max_price_per_pgu.clone()
+                }),
                 program_uri: program.map(|p| p.program_uri.clone()).unwrap_or_default(),
+                // Presigned URLs expire, so they are generated when the request is read
                 program_public_uri: program.map(|p| p.program_uri.clone()).unwrap_or_default(),
-                stdin_uri: req.body.as_ref().map(|b| b.stdin_uri.clone()).unwrap_or_default(),
-                stdin_public_uri: req.body.as_ref().map(|b| b.stdin_uri.clone()).unwrap_or_default(),
+                stdin_uri: body.stdin_uri.clone(),
+                stdin_public_uri: body.stdin_uri.clone(),
                 ..Default::default()
             };
-        self.proof_requests.lock().await.insert(request_id, (proof_request, status_response));
+
+        // With metering the requester pays at most the base fee plus the gas limit at the max price
+        let max_cost = if self.params.metering { max_request_cost(&proof_request) } else { 0 };
+        self.ledger.escrow_request(&requester, max_cost, &tx_hash_bytes, now)?;
+        let proof_request = ProofRequest { deduction_amount: Some(max_cost.to_string()), ..proof_request };
+        if let Err(e) = self.storage.put_proof_request(&proof_request, &status_response) {
+            // No request holds the escrow
+            if let Err(refund_error) = self.ledger.refund_escrow(&requester, max_cost, &tx_hash_bytes, now) {
+                tracing::error!("PROVER_NETWORK: Failed to refund the escrow of {}: {}", hex::encode(&requester), refund_error.message());
+            }
+            return Err(e.into());
+        }
+        nonce.commit();
         
         Ok(Response::new(response))
     }
@@ -99,9 +404,9 @@ impl prover_network_server::ProverNetwork for ProverNetworkServiceImpl {
         let req = request.into_inner();
         tracing::info!("PROVER_NETWORK: Server Received status request for ID: {:?}", hex::encode(&req.request_id));
         
-        let requests = self.proof_requests.lock().await;
-        if let Some((_, status)) = requests.get(&req.request_id) {
-            Ok(Response::new(status.clone()))
+        if let Some((_, mut status)) = self.storage.get_proof_request(&req.request_id)? {
+            status.proof_public_uri = status.proof_public_uri.map(|uri| presign_uri(self.s3.as_ref(), &uri));
+            Ok(Response::new(status))
         } else {
             Err(Status::not_found("Proof request not found"))
         }
@@ -110,91 +415,156 @@ impl prover_network_server::ProverNetwork for ProverNetworkServiceImpl {
     // Implement all other required methods with unimplemented status for now
     async fn fulfill_proof(&self, request: Request<FulfillProofRequest>) -> Result<Response<FulfillProofResponse>, Status> {
         tracing::info!("PROVER_NETWORK: fulfill_proof method called");
-        let req = request.into_inner();
-        let msg_bytes: Vec<u8> = encode_body_for_signing(req.format, req.body.as_ref().ok_or_else(|| Status::invalid_argument("Request body is required"))?)
-            .map_err(|e| Status::internal(format!("Failed to encode body for signing: {}", e)))?;
-        let requester = match req.body.as_ref() {
-            Some(_body) => recover_signer_addr(msg_bytes, &req.signature)
-                .map_err(|e| Status::invalid_argument(format!("Failed to recover signer address: {}", e)))?,
-            None => return Err(Status::invalid_argument("Request body is required")),
-        };
+        let (body, requester, nonce) = self.authenticate(request.into_inner())?;
         tracing::info!("PROVER_NETWORK: Server fulfill_proof method Recovered requester address: {:?}", hex::encode(&requester));
+        // Delegates fulfill on behalf of their owner
+        let prover = self.owner_of(&requester)?;
 
+        tracing::debug!("PROVER_NETWORK: request_id: {}, nonce: {}, reserved_metadata: {:?}", hex::encode(&body.request_id), body.nonce, body.reserved_metadata);
+        let tx_hash_bytes = random::<[u8; 32]>().to_vec();
+        let (proof_request, _) = self.storage.get_proof_request(&body.request_id)?
+            .ok_or_else(|| Status::not_found("Proof request not found"))?;
+        authorize_fulfiller(&proof_request, &prover)?;
+        require_assigned(&proof_request)?;
+        require_executed(&proof_request)?;
+        require_before_deadline(&proof_request, chrono::Utc::now().timestamp() as u64)?;
+
+        // Check the proof against the program before accepting it. Without the program
+        // there is no verification key to check against, which is not the prover's fault.
+        let program = self.storage.get_program(&proof_request.vk_hash)?
+            .ok_or_else(|| Status::failed_precondition("Program of the proof request is not registered"))?;
+        let verification = VerifyProofRequest {
+            proof: body.proof.clone(),
+            vkey: program.vk,
+            mode: proof_request.mode,
+            version: proof_request.version.clone(),
+            public_values_hash: proof_request.public_values_hash.clone(),
+        };
+        let verification_error = check_proof(self.verifier.as_ref(), verification)
+            .await
+            .map_err(|e| Status::unavailable(format!("Failed to verify proof: {}", e)))?;
+        if let Some(error) = verification_error {
+            tracing::warn!("PROVER_NETWORK: Rejected proof of {} for request {}: {}", hex::encode(&prover), hex::encode(&body.request_id), error.as_str_name());
+            self.storage.update_proof_request(&body.request_id, |proof_request, status| {
+                authorize_fulfiller(proof_request, &prover)?;
+                require_assigned(proof_request)?;
+                proof_request.error = error as i32;
+                proof_request.fulfillment_status = FulfillmentStatus::Unfulfillable as i32;
+                proof_request.updated_at = chrono::Utc::now().timestamp() as u64;
+                status.fulfillment_status = proof_request.fulfillment_status;
+                Ok::<_, Status>(())
+            })?;
+            nonce.commit();
+            let now = chrono::Utc::now().timestamp() as u64;
+            self.ledger.settle_request(&body.request_id, now)?;
+            self.staking.slash(&prover, &body.request_id, now)?;
+            return Err(Status::invalid_argument(format!("Invalid proof: {}", error.as_str_name())));
+        }
 
+        // Upload proof
+        let (upload_url, proof_uri) = self.generate_proof_location();
+        let client = reqwest::Client::new();
+        let upload_response = client
+            .put(upload_url)
+            .header("Content-Type", "application/binary")
+            .body(body.proof.clone())
+            .send()
+            .await
+            .map_err(|e| Status::internal(format!("Failed to upload proof: {}", e)))?;
+
+        if upload_response.status().is_success() {
+            tracing::debug!("✓ Proof uploaded successfully!");
+        } else {
+            tracing::error!("✗ Failed to upload proof. Status: {}", upload_response.status());
+            tracing::error!("Response: {:?}", upload_response.text().await);
+            Err(Status::internal("Failed to upload proof"))?;
+        }
 
-        let body = req.body.ok_or_else(|| Status::invalid_argument("Request body is required"))?;
-        tracing::debug!("PROVER_NETWORK: domain: {}, request_id: {}, variant: {}, nonce: {}, reserved_metadata: {:?}", hex::encode(&body.domain), hex::encode(&body.request_id), body.variant, body.nonce, body.reserved_metadata);
-        let tx_hash_bytes = random::<[u8; 32]>().to_vec();
-        let mut requests = self.proof_requests.lock().await;
-        if let Some((proof_request, status)) = requests.get_mut(&body.request_id) {
-            // Upload proof
-            let url = generate_proof_url();
-            let client = reqwest::Client::new();
-            let upload_response = client
-                .put(url.clone())
-                .header("Content-Type", "application/binary")
-                .body(body.proof.clone())
-                .send()
-                .await
-                .map_err(|e| Status::internal(format!("Failed to upload proof: {}", e)))?;
-
-            if upload_response.status().is_success() {
-                tracing::debug!("✓ Proof uploaded successfully!");
-            } else {
-                tracing::error!("✗ Failed to upload proof. Status: {}", upload_response.status());
-                tracing::error!("Response: {:?}", upload_response.text().await);
-                Err(Status::internal("Failed to upload proof"))?;
-            }
+        let now = chrono::Utc::now().timestamp() as u64;
+        let gas = self.storage.update_proof_request(&body.request_id, |proof_request, status| {
+            authorize_fulfiller(proof_request, &prover)?;
+            require_assigned(proof_request)?;
+            require_executed(proof_request)?;
+            require_before_deadline(proof_request, now)?;
             // Update fulfillment status to Fulfilled
             status.fulfillment_status = FulfillmentStatus::Fulfilled as i32;
             status.fulfill_tx_hash = Some(tx_hash_bytes.clone());
-            status.proof_uri = Some(url.clone());
-            status.proof_public_uri = Some(url.clone());
-            status.execution_status = ExecutionStatus::Executed as i32;
-            // status.public_values_hash = 
+            status.proof_uri = Some(proof_uri.clone());
+            status.proof_public_uri = Some(proof_uri.clone());
 
-            let now = chrono::Utc::now().timestamp() as u64;
             proof_request.fulfillment_status = status.fulfillment_status;
             proof_request.updated_at = now;
-            proof_request.fulfiller = Some(requester);
             proof_request.fulfilled_at = Some(now);
-            proof_request.execution_status = ExecutionStatus::Executed as i32;
-            
-            let response = FulfillProofResponse {
-                tx_hash: tx_hash_bytes.clone(),
-                body: Some(FulfillProofResponseBody {}),
-            };
-            return Ok(Response::new(response));
+            Ok::<_, Status>(proof_request.gas_used.or(proof_request.cycles).unwrap_or(0))
+        })?;
+        nonce.commit();
+        self.provers.record_fulfillment(&prover, gas, now)?;
+        self.ledger.settle_request(&body.request_id, now)?;
+        if let Some((settled, _)) = self.storage.get_proof_request(&body.request_id)? {
+            self.analytics.record_proof(&settled, now)?;
+            self.telemetry.record_fulfillment(&settled);
         }
-        Err(Status::not_found("Proof request not found"))
+
+        let response = FulfillProofResponse {
+            tx_hash: tx_hash_bytes,
+            body: Some(FulfillProofResponseBody {}),
+        };
+        Ok(Response::new(response))
     }
 
-    async fn execute_proof(&self, _request: Request<ExecuteProofRequest>) -> Result<Response<ExecuteProofResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: execute_proof method called but not implemented");
-        Err(Status::unimplemented("execute_proof not implemented"))
+    async fn execute_proof(&self, request: Request<ExecuteProofRequest>) -> Result<Response<ExecuteProofResponse>, Status> {
+        let (body, signer, nonce) = self.authenticate(request.into_inner())?;
+        // Only the execution oracle reports executions
+        require_signer(&signer, self.params.executor(), "executor")?;
+        if let Some(punishment) = &body.punishment {
+            tracing::info!("PROVER_NETWORK: Executor requested a punishment of {} for request {}", punishment, hex::encode(&body.request_id));
+        }
+
+        let now = chrono::Utc::now().timestamp() as u64;
+        let execution_status = self.storage.update_proof_request(&body.request_id, |proof_request, status| {
+            apply_execution(proof_request, status, &body, now)
+        })?;
+        nonce.commit();
+        tracing::info!(
+            "PROVER_NETWORK: Request {} execution: {}, cycles: {:?}, pgus: {:?}",
+            hex::encode(&body.request_id),
+            execution_status.as_str_name(),
+            body.cycles,
+            body.pgus
+        );
+        // Failed executions release the escrow of the request
+        self.ledger.settle_request(&body.request_id, now)?;
+
+        Ok(Response::new(ExecuteProofResponse {
+            tx_hash: random::<[u8; 32]>().to_vec(),
+            body: Some(ExecuteProofResponseBody {}),
+        }))
     }
 
     async fn fail_fulfillment(&self, request: Request<FailFulfillmentRequest>) -> Result<Response<FailFulfillmentResponse>, Status> {
-        // TODO validate signature
-        // Extract body safely from Option
-        let body = request.into_inner().body.ok_or_else(|| Status::invalid_argument("Request body is required"))?;
-        
-        let mut requests = self.proof_requests.lock().await;
-        if let Some((proof_request, status)) = requests.get_mut(&body.request_id) {
+        let (body, signer, nonce) = self.authenticate(request.into_inner())?;
+        let prover = self.owner_of(&signer)?;
+
+        let response = self.storage.update_proof_request(&body.request_id, |proof_request, status| {
+            // Only the assigned fulfiller, or its delegate, can give up on a request
+            authorize_fulfiller(proof_request, &prover)?;
+            // Requests already fulfilled or failed keep their outcome
+            require_assigned(proof_request)?;
             // Update fulfillment status to Unfulfillable
             status.fulfillment_status = FulfillmentStatus::Unfulfillable as i32;
             let now = chrono::Utc::now().timestamp() as u64;
             proof_request.fulfillment_status = status.fulfillment_status;
             proof_request.updated_at = now;
             proof_request.error = body.error.unwrap_or(0); // Unwrap Option<i32> to i32, default to 0
-            
-            let response = FailFulfillmentResponse {
+
+            Ok::<_, Status>(FailFulfillmentResponse {
                 tx_hash: proof_request.tx_hash.clone(),
                 body: Some(FailFulfillmentResponseBody {}),
-            };
-            return Ok(Response::new(response));
-        }
-        Err(Status::not_found("Proof request not found"))
+            })
+        })?;
+        nonce.commit();
+        self.ledger.settle_request(&body.request_id, chrono::Utc::now().timestamp() as u64)?;
+        Ok(Response::new(response))
     }
 
     async fn get_proof_request_details(&self, _request: Request<GetProofRequestDetailsRequest>) -> Result<Response<GetProofRequestDetailsResponse>, Status> {
@@ -202,10 +572,9 @@ impl prover_network_server::ProverNetwork for ProverNetworkServiceImpl {
         let req_inner = _request.into_inner();
         tracing::info!("PROVER_NETWORK: Request ID received: {:?}", hex::encode(&req_inner.request_id));
         
-        let requests = self.proof_requests.lock().await;
-        if let Some((request, _)) = requests.get(&req_inner.request_id) {            
+        if let Some((request, _)) = self.storage.get_proof_request(&req_inner.request_id)? {
             let response = GetProofRequestDetailsResponse {
-                request: Some(request.clone()),
+                request: Some(self.presign_request(request)),
             };
             tracing::debug!("PROVER_NETWORK: Found request, returning details");
             Ok(Response::new(response))
@@ -216,179 +585,147 @@ impl prover_network_server::ProverNetwork for ProverNetworkServiceImpl {
     }
 
     async fn get_filtered_proof_requests(&self, _request: Request<GetFilteredProofRequestsRequest>) -> Result<Response<GetFilteredProofRequestsResponse>, Status> {
-        // Clone the request data for logging before consuming the request
-        let request_data = _request.get_ref().clone();
-        let req_inner = _request.into_inner();
-        let requests = self.proof_requests.lock().await;
-        let mut filtered_requests: Vec<ProofRequest> = requests
-            .values()
-            .map(|(req, _)| req.clone())
-            .filter(|req| {
-            // Filter by requester if provided
-            if let Some(ref filter_requester) = req_inner.requester {
-            if !filter_requester.is_empty() && req.requester != *filter_requester {
-                tracing::debug!("PROVER_NETWORK: Received get_filtered_proof_requests request: {:?}. Filtering by requester. Not matching: {:?}", request_data, req.requester);
-                return false;
-            }
-            }
-            
-            // Filter by fulfillment status if provided
-            if req_inner.fulfillment_status.is_some() && req.fulfillment_status != req_inner.fulfillment_status.unwrap() {
-                tracing::debug!("PROVER_NETWORK: Received get_filtered_proof_requests request: {:?}. Filtering by fulfillment_status. Not matching: {:?}", request_data, req.fulfillment_status);
-                return false;
-            }
-            
-            // Filter by execution status if provided
-            if req_inner.execution_status.is_some() && req.execution_status != req_inner.execution_status.unwrap() {
-                tracing::debug!("PROVER_NETWORK: Received get_filtered_proof_requests request: {:?}. Filtering by execution_status. Not matching: {:?}", request_data, req.execution_status);
-                return false;
-            }
-            
-            // Filter by vk_hash if provided
-            if let Some(ref filter_vk_hash) = req_inner.vk_hash {
-                if !filter_vk_hash.is_empty() && req.vk_hash != *filter_vk_hash {
-                    tracing::debug!("PROVER_NETWORK: Received get_filtered_proof_requests request: {:?}. Filtering by vk_hash. Not matching: {:?}", request_data, req.vk_hash);
-                    return false;
-                }
-            }
-            
-            // Filter by version if provided
-            if let Some(ref filter_version) = req_inner.version {
-                if !filter_version.is_empty() && req.version != *filter_version {
-                    tracing::debug!("PROVER_NETWORK: Received get_filtered_proof_requests request: {:?}. Filtering by version. Not matching: {:?}", request_data, req.version);
-                    return false;
-                }
-            }
-            
-            // Filter by mode if provided
-            if req_inner.mode.is_some() && req.mode != req_inner.mode.unwrap() {
-                tracing::debug!("PROVER_NETWORK: Received get_filtered_proof_requests request: {:?}. Filtering by mode. Not matching: {:?}", request_data, req.mode);
-                return false;
-            }
-
-            // Filter by minimum_deadline if provided
-            if req_inner.minimum_deadline.is_some() && req.deadline <= req_inner.minimum_deadline.unwrap() {
-                tracing::debug!("PROVER_NETWORK: Received get_filtered_proof_requests request: {:?}. Filtering by minimum_deadline. Not matching: received in the request {:?} and stored in the proof_request {:?}", request_data, req_inner.minimum_deadline, req.deadline);
-                return false;
-            }
-
-            // Filter by fulfiller if provided
-            if let Some(ref filter_fulfiller) = req_inner.fulfiller {
-                if req.fulfiller.as_ref() != Some(filter_fulfiller) {
-                    tracing::debug!("PROVER_NETWORK: Received get_filtered_proof_requests request: {:?}. Filtering by fulfiller. Not matching: {:?}", request_data, req.fulfiller);
-                    return false;
-                }
+        let cursor = match _request.metadata().get(CURSOR_METADATA) {
+            Some(value) => {
+                let cursor = value.to_str().ok().and_then(|v| hex::decode(v).ok());
+                Some(cursor.ok_or_else(|| Status::invalid_argument("Invalid cursor"))?)
             }
+            None => None,
+        };
+        let req_inner = _request.into_inner();
 
-            // Filter by from if provided
-            if req_inner.from.is_some() {
-                tracing::debug!("PROVER_NETWORK: Received get_filtered_proof_requests request: {:?}. Filtering by from. Not implemented, ignoring... {:?}", request_data, req_inner.from);
-            }
+        // Requests are returned oldest first, resuming after the cursor or skipping the previous pages
+        let limit = req_inner.limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT) as usize;
+        let (start, skip) = match cursor {
+            Some(cursor) => ([cursor.as_slice(), &[0]].concat(), 0),
+            None => (req_inner.from.map(|from| from.to_be_bytes().to_vec()).unwrap_or_default(), (req_inner.page.unwrap_or(0) as usize).saturating_mul(limit)),
+        };
+        let (requests, next_cursor) = self.find_proof_requests(&req_inner, start, skip, limit)?;
+        let requests: Vec<ProofRequest> = requests.into_iter().map(|request| self.presign_request(request)).collect();
+        tracing::info!("PROVER_NETWORK: Returning {} requests", requests.len());
+
+        let mut response = Response::new(GetFilteredProofRequestsResponse { requests });
+        if let Some(next_cursor) = next_cursor {
+            let value = hex::encode(next_cursor).parse().map_err(|_| Status::internal("Invalid cursor"))?;
+            response.metadata_mut().insert(NEXT_CURSOR_METADATA, value);
+        }
+        Ok(response)
+    }
 
-            // Filter by to if provided
-            if req_inner.to.is_some() {
-                tracing::debug!("PROVER_NETWORK: Received get_filtered_proof_requests request: {:?}. Filtering by to. Not implemented, ignoring... {:?}", request_data, req_inner.to);
-            }
+    type SubscribeProofRequestsStream = std::pin::Pin<Box<dyn tokio_stream::Stream<Item = Result<ProofRequest, Status>> + Send>>;
 
-            // Filter by not_bid_by if provided
-            if req_inner.not_bid_by.is_some() {
-                tracing::debug!("PROVER_NETWORK: Received get_filtered_proof_requests request: {:?}. Filtering by not_bid_by. Not implemented, ignoring... {:?}", request_data, req_inner.not_bid_by);
-            }
+    async fn subscribe_proof_requests(&self, request: Request<GetFilteredProofRequestsRequest>) -> Result<Response<Self::SubscribeProofRequestsStream>, Status> {
+        let filter = request.into_inner();
+        tracing::info!("PROVER_NETWORK: New proof requests subscription: {:?}", filter);
 
-            // Filter by execute_fail_cause if provided
-            if req_inner.execute_fail_cause.is_some() && req.execute_fail_cause != req_inner.execute_fail_cause.unwrap() {
-                tracing::debug!("PROVER_NETWORK: Received get_filtered_proof_requests request: {:?}. Filtering by execute_fail_cause. Not matching: {:?}", request_data, req.execute_fail_cause);
-                return false;
+        // Subscribe before reading the existing requests so no update falls in between
+        let mut updates = self.storage.subscribe_proof_requests();
+        let mut existing: Vec<ProofRequest> = Vec::new();
+        for (req, _) in self.storage.proof_requests()? {
+            if matches_filter(&req, &filter) && not_bid_by(&self.storage, &req, &filter)? {
+                existing.push(req);
             }
-
-            // Filter by settlement_status if provided
-            if req_inner.settlement_status.is_some() && req.settlement_status != req_inner.settlement_status.unwrap() {
-                tracing::debug!("PROVER_NETWORK: Received get_filtered_proof_requests request: {:?}. Filtering by settlement_status. Not matching: {:?}", request_data, req.settlement_status);
-                return false;
+        }
+        existing.sort_by_key(|req| req.created_at);
+
+        let storage = self.storage.clone();
+        let s3 = self.s3.clone();
+        let subscription = self.telemetry.track_subscription();
+        let (tx, rx) = tokio::sync::mpsc::channel(SUBSCRIPTION_BUFFER);
+        tokio::spawn(async move {
+            let _subscription = subscription;
+            for req in existing {
+                if tx.send(Ok(presign_request(s3.as_ref(), req))).await.is_err() {
+                    return;
+                }
             }
-
-            // Filter by error if provided
-            if req_inner.error.is_some() && req.error != req_inner.error.unwrap() {
-                tracing::debug!("PROVER_NETWORK: Received get_filtered_proof_requests request: {:?}. Filtering by error. Not matching: {:?}", request_data, req.error);
-                return false;
+            loop {
+                let update = tokio::select! {
+                    // Stop as soon as the subscriber goes away, not at the next update
+                    _ = tx.closed() => return,
+                    update = updates.recv() => update,
+                };
+                match update {
+                    Ok(req) => {
+                        let matches = matches_filter(&req, &filter) && not_bid_by(&storage, &req, &filter).unwrap_or(false);
+                        if matches && tx.send(Ok(presign_request(s3.as_ref(), req))).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(missed)) => {
+                        // Updates were lost, the subscriber has to resubscribe to see the current state again
+                        tracing::warn!("PROVER_NETWORK: Subscriber fell behind, {} proof request updates skipped", missed);
+                        let _ = tx.send(Err(Status::data_loss(format!("{} proof request updates missed, resubscribe", missed)))).await;
+                        return;
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => return,
+                }
             }
+        });
 
-            true
-            })
-            .collect();
-        
-        // Sort by created_at in ascending order (oldest first)
-        filtered_requests.sort_by(|a, b| a.created_at.cmp(&b.created_at));
-        
-        // Apply pagination
-        let page = req_inner.page.unwrap_or(0) as usize;
-        let limit = req_inner.limit.unwrap_or(50) as usize; // Default limit of 50
-        let offset = page * limit as usize; // Default page size of 50
-        
-        // Calculate total count before pagination
-        let total_count = filtered_requests.len();
-        
-        // Apply offset and limit
-        let paginated_requests: Vec<ProofRequest> = filtered_requests
-            .into_iter()
-            .skip(offset)
-            .take(limit)
-            .collect();
-        
-        tracing::info!("PROVER_NETWORK: Returning {} requests out of {} total", paginated_requests.len(), total_count);
-        
-        let filtered_requests = paginated_requests;
-        Ok(Response::new(GetFilteredProofRequestsResponse {
-            requests: filtered_requests,
-        }))
+        Ok(Response::new(Box::pin(tokio_stream::wrappers::ReceiverStream::new(rx))))
     }
 
-    type SubscribeProofRequestsStream = std::pin::Pin<Box<dyn tokio_stream::Stream<Item = Result<ProofRequest, Status>> + Send>>;
-
-    async fn subscribe_proof_requests(&self, _request: Request<GetFilteredProofRequestsRequest>) -> Result<Response<Self::SubscribeProofRequestsStream>, Status> {
-        // // TODO implemente the filtering logic
-        // let requests = self.proof_requests.lock().await;
-        // let all_requests: Vec<ProofRequest> = requests.values().map(|(req, _)| req.clone()).collect();
-        // drop(requests);
-        
-        // let stream = tokio_stream::iter(all_requests.into_iter().map(Ok));
-        // Ok(Response::new(Box::pin(stream)))
-        tracing::info!("PROVER_NETWORK: subscribe_proof_requests method called but not implemented");
-        Err(Status::unimplemented("subscribe_proof_requests not implemented"))
-    }
-
-    async fn get_search_results(&self, _request: Request<GetSearchResultsRequest>) -> Result<Response<GetSearchResultsResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: get_search_results method called but not implemented");
-        Err(Status::unimplemented("get_search_results not implemented"))
+    async fn get_search_results(&self, request: Request<GetSearchResultsRequest>) -> Result<Response<GetSearchResultsResponse>, Status> {
+        let results = self.search.search(&request.into_inner().query);
+        Ok(Response::new(GetSearchResultsResponse {
+            requests: results.requests,
+            programs: results.programs,
+            requesters: results.requesters,
+        }))
     }
 
-    async fn get_proof_request_metrics(&self, _request: Request<GetProofRequestMetricsRequest>) -> Result<Response<GetProofRequestMetricsResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: get_proof_request_metrics method called but not implemented");
-        Err(Status::unimplemented("get_proof_request_metrics not implemented"))
+    async fn get_proof_request_metrics(&self, request: Request<GetProofRequestMetricsRequest>) -> Result<Response<GetProofRequestMetricsResponse>, Status> {
+        let address = request.into_inner().address.unwrap_or_default();
+        let totals = self.analytics.totals(&address)?;
+        let saturate = |value: u128| value.min(u64::MAX as u128) as u64;
+        Ok(Response::new(GetProofRequestMetricsResponse {
+            total_proofs: saturate(totals.proofs),
+            total_programs: saturate(totals.programs),
+            total_gas: saturate(totals.gas),
+        }))
     }
 
-    async fn get_proof_request_graph(&self, _request: Request<GetProofRequestGraphRequest>) -> Result<Response<GetProofRequestGraphResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: get_proof_request_graph method called but not implemented");
-        Err(Status::unimplemented("get_proof_request_graph not implemented"))
+    async fn get_proof_request_graph(&self, request: Request<GetProofRequestGraphRequest>) -> Result<Response<GetProofRequestGraphResponse>, Status> {
+        let req = request.into_inner();
+        let now = chrono::Utc::now().timestamp() as u64;
+        let series = self.analytics.series(&req.address.unwrap_or_default(), req.range_interval_days, now)?;
+        Ok(Response::new(GetProofRequestGraphResponse { data: graph(&series, |c| c.proofs) }))
     }
 
-    async fn get_analytics_graphs(&self, _request: Request<GetAnalyticsGraphsRequest>) -> Result<Response<GetAnalyticsGraphsResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: get_analytics_graphs method called but not implemented");
-        Err(Status::unimplemented("get_analytics_graphs not implemented"))
+    async fn get_analytics_graphs(&self, request: Request<GetAnalyticsGraphsRequest>) -> Result<Response<GetAnalyticsGraphsResponse>, Status> {
+        let req = request.into_inner();
+        let now = chrono::Utc::now().timestamp() as u64;
+        let series = self.analytics.series(&req.address.unwrap_or_default(), req.range_interval_days, now)?;
+        Ok(Response::new(GetAnalyticsGraphsResponse {
+            // Percent of the auctions that ended in the bucket which found a prover
+            auction_success_rate: graph(&series, |c| (c.auctions_won * 100).checked_div(c.auctions_won + c.auctions_lost).unwrap_or(0)),
+            proofs: graph(&series, |c| c.proofs),
+            // Credits earned per million prover gas units
+            rewards_mgas: graph(&series, |c| c.rewards.saturating_mul(1_000_000).checked_div(c.gas).unwrap_or(0)),
+        }))
     }
 
-    async fn get_overview_graphs(&self, _request: Request<GetOverviewGraphsRequest>) -> Result<Response<GetOverviewGraphsResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: get_overview_graphs method called but not implemented");
-        Err(Status::unimplemented("get_overview_graphs not implemented"))
+    async fn get_overview_graphs(&self, request: Request<GetOverviewGraphsRequest>) -> Result<Response<GetOverviewGraphsResponse>, Status> {
+        let address = request.into_inner().address.unwrap_or_default();
+        let now = chrono::Utc::now().timestamp() as u64;
+        let series = self.analytics.series(&address, None, now)?;
+        Ok(Response::new(GetOverviewGraphsResponse {
+            proofs: graph(&series, |c| c.proofs),
+            programs: graph(&series, |c| c.programs),
+            gas: graph(&series, |c| c.gas),
+        }))
     }
 
-    async fn get_proof_request_params(&self, _request: Request<GetProofRequestParamsRequest>) -> Result<Response<GetProofRequestParamsResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: get_proof_request_params method called but not implemented");
-        Err(Status::unimplemented("get_proof_request_params not implemented"))
+    async fn get_proof_request_params(&self, request: Request<GetProofRequestParamsRequest>) -> Result<Response<GetProofRequestParamsResponse>, Status> {
+        let mode = ProofMode::try_from(request.into_inner().mode).unwrap_or(ProofMode::UnspecifiedProofMode);
+        Ok(Response::new(self.params.to_response(mode)))
     }
 
-    async fn get_nonce(&self, _request: Request<GetNonceRequest>) -> Result<Response<GetNonceResponse>, Status> {
-        Ok(Response::new(GetNonceResponse { nonce: 0 }))
+    async fn get_nonce(&self, request: Request<GetNonceRequest>) -> Result<Response<GetNonceResponse>, Status> {
+        let address = request.into_inner().address;
+        let nonce = self.storage.next_nonce(&address)?;
+        Ok(Response::new(GetNonceResponse { nonce }))
     }
 
     async fn set_account_name(&self, _request: Request<SetAccountNameRequest>) -> Result<Response<SetAccountNameResponse>, Status> {
@@ -416,20 +753,18 @@ impl prover_network_server::ProverNetwork for ProverNetworkServiceImpl {
         Err(Status::unimplemented("get_account not implemented"))
     }
 
-    async fn get_owner(&self, _request: Request<GetOwnerRequest>) -> Result<Response<GetOwnerResponse>, Status> {
-        let acct = _request.into_inner().address;
-        Ok(Response::new(GetOwnerResponse { owner: acct.clone() }))
+    async fn get_owner(&self, request: Request<GetOwnerRequest>) -> Result<Response<GetOwnerResponse>, Status> {
+        let owner = self.owner_of(&request.into_inner().address)?;
+        Ok(Response::new(GetOwnerResponse { owner }))
     }
 
     async fn get_program(&self, _request: Request<GetProgramRequest>) -> Result<Response<GetProgramResponse>, Status> {
         let request_inner = _request.into_inner();
         tracing::info!("PROVER_NETWORK: Received get_program request: {:?}", hex::encode(&request_inner.vk_hash));
         // Check if the requested vk_hash exists
-        let programs: tokio::sync::MutexGuard<'_, HashMap<Vec<u8>, Program>> = self.programs.lock().await;
-
-        if let Some(program) = programs.get(&request_inner.vk_hash) {            
+        if let Some(program) = self.storage.get_program(&request_inner.vk_hash)? {
             let response = GetProgramResponse {
-                program: Some(program.clone()),
+                program: Some(program),
             };
             Ok(Response::new(response))
         } else {
@@ -438,13 +773,7 @@ impl prover_network_server::ProverNetwork for ProverNetworkServiceImpl {
     }
 
     async fn create_program(&self, _request: Request<CreateProgramRequest>) -> Result<Response<CreateProgramResponse>, Status> {
-        let request_inner = _request.into_inner();
-        let body: CreateProgramRequestBody = request_inner.body.ok_or_else(|| Status::invalid_argument("Request body is required"))?;
-        let msg_bytes: Vec<u8> = encode_body_for_signing(request_inner.format, &body)
-            .map_err(|e| Status::internal(format!("Failed to encode body for signing: {}", e)))?;
-        let requester = recover_signer_addr(msg_bytes, &request_inner.signature)
-            .map_err(|e| Status::invalid_argument(format!("Failed to recover signer address: {}", e)))?;
-        let vk_hash_key = body.vk_hash.clone();
+        let (body, requester, nonce) = self.authenticate(_request.into_inner())?;
         let program = rpc_types::Program {
             vk_hash: body.vk_hash,
             vk: body.vk,
@@ -453,8 +782,9 @@ impl prover_network_server::ProverNetwork for ProverNetworkServiceImpl {
             owner: requester.clone(),
             created_at: chrono::Utc::now().timestamp() as u64,
         };
-        let mut programs: tokio::sync::MutexGuard<'_, HashMap<Vec<u8>, Program>> = self.programs.lock().await;
-        programs.insert(vk_hash_key, program.clone());
+        self.storage.put_program(&program)?;
+        nonce.commit();
+        self.analytics.record_program(&program, program.created_at)?;
 
         let response = CreateProgramResponse {
             tx_hash: random::<[u8; 32]>().to_vec(),
@@ -468,19 +798,47 @@ impl prover_network_server::ProverNetwork for ProverNetworkServiceImpl {
         Err(Status::unimplemented("set_program_name not implemented"))
     }
 
-    async fn get_balance(&self, _request: Request<GetBalanceRequest>) -> Result<Response<GetBalanceResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: get_balance method called but not implemented");
-        Err(Status::unimplemented("get_balance not implemented"))
+    async fn get_balance(&self, request: Request<GetBalanceRequest>) -> Result<Response<GetBalanceResponse>, Status> {
+        let address = request.into_inner().address;
+        let amount = self.ledger.balance(&address)?;
+        Ok(Response::new(GetBalanceResponse { amount: amount.to_string() }))
     }
 
-    async fn get_filtered_balance_logs(&self, _request: Request<GetFilteredBalanceLogsRequest>) -> Result<Response<GetFilteredBalanceLogsResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: get_filtered_balance_logs method called but not implemented");
-        Err(Status::unimplemented("get_filtered_balance_logs not implemented"))
-    }
+    async fn get_filtered_balance_logs(&self, request: Request<GetFilteredBalanceLogsRequest>) -> Result<Response<GetFilteredBalanceLogsResponse>, Status> {
+        let req = request.into_inner();
+        let mut logs: Vec<BalanceLog> = self
+            .storage
+            .balance_logs()?
+            .into_iter()
+            .filter(|log| req.address.as_ref().is_none_or(|a| a.is_empty() || &log.address == a))
+            .filter(|log| req.operation.is_none_or(|op| log.operation == op))
+            .filter(|log| req.minimum_timestamp.is_none_or(|t| log.created_at >= t))
+            .filter(|log| req.maximum_timestamp.is_none_or(|t| log.created_at <= t))
+            .collect();
+        // Newest first
+        logs.reverse();
+
+        let page = req.page.unwrap_or(0) as usize;
+        let limit = req.limit.unwrap_or(10).min(100) as usize;
+        let logs = logs.into_iter().skip(page * limit).take(limit).collect();
+        Ok(Response::new(GetFilteredBalanceLogsResponse { logs }))
+    }
+
+    async fn add_credit(&self, request: Request<AddCreditRequest>) -> Result<Response<AddCreditResponse>, Status> {
+        let (body, signer, nonce) = self.authenticate(request.into_inner())?;
+        // Credits are minted by the owner only
+        require_signer(&signer, &self.params.owner, "owner")?;
+        let amount = parse_amount(&body.amount).ok_or_else(|| Status::invalid_argument(format!("Invalid amount: {}", body.amount)))?;
+        if body.address.is_empty() {
+            return Err(Status::invalid_argument("Address is required"));
+        }
 
-    async fn add_credit(&self, _request: Request<AddCreditRequest>) -> Result<Response<AddCreditResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: add_credit method called but not implemented");
-        Err(Status::unimplemented("add_credit not implemented"))
+        let tx_hash = random::<[u8; 32]>().to_vec();
+        self.ledger.add_credit(&body.address, amount, &tx_hash, chrono::Utc::now().timestamp() as u64)?;
+        nonce.commit();
+        tracing::info!("PROVER_NETWORK: Added {} credits to {}", amount, hex::encode(&body.address));
+
+        Ok(Response::new(AddCreditResponse { tx_hash, body: Some(AddCreditResponseBody {}) }))
     }
 
     async fn get_latest_bridge_block(&self, _request: Request<GetLatestBridgeBlockRequest>) -> Result<Response<GetLatestBridgeBlockResponse>, Status> {
@@ -523,49 +881,184 @@ impl prover_network_server::ProverNetwork for ProverNetworkServiceImpl {
         Err(Status::unimplemented("get_usage_summary not implemented"))
     }
 
-    async fn transfer(&self, _request: Request<TransferRequest>) -> Result<Response<TransferResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: transfer method called but not implemented");
-        Err(Status::unimplemented("transfer not implemented"))
+    async fn transfer(&self, request: Request<TransferRequest>) -> Result<Response<TransferResponse>, Status> {
+        let (body, signer, nonce) = self.authenticate(request.into_inner())?;
+        let amount = parse_amount(&body.amount).ok_or_else(|| Status::invalid_argument(format!("Invalid amount: {}", body.amount)))?;
+        let fee = self.check_fee(&body.fee, "0", &body.auctioneer)?;
+        // Only accounts can receive credits, not the ledger's internal accounts
+        if body.to.len() != 20 || body.to == ESCROW_ACCOUNT || body.to == ISSUANCE_ACCOUNT {
+            return Err(Status::invalid_argument(format!("Invalid recipient {}", hex::encode(&body.to))));
+        }
+
+        let tx_hash = random::<[u8; 32]>().to_vec();
+        let now = chrono::Utc::now().timestamp() as u64;
+        // Credits sent to the staking vault are staked by their sender
+        if self.staking.is_vault(&body.to) {
+            self.staking.transfer_stake(&signer, amount, (&self.params.auctioneer, fee), &tx_hash, now)?;
+        } else {
+            self.ledger.transfer(&signer, &body.to, amount, (&self.params.auctioneer, fee), &tx_hash, now)?;
+        }
+        nonce.commit();
+        tracing::info!("PROVER_NETWORK: Transferred {} credits from {} to {}", amount, hex::encode(&signer), hex::encode(&body.to));
+
+        Ok(Response::new(TransferResponse { tx_hash, body: Some(TransferResponseBody {}) }))
     }
 
     async fn get_withdraw_params(&self, _request: Request<GetWithdrawParamsRequest>) -> Result<Response<GetWithdrawParamsResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: get_withdraw_params method called but not implemented");
-        Err(Status::unimplemented("get_withdraw_params not implemented"))
+        Ok(Response::new(GetWithdrawParamsResponse {
+            auctioneer: self.params.auctioneer.clone(),
+            fee: self.params.withdraw_fee.clone(),
+        }))
     }
 
-    async fn withdraw(&self, _request: Request<rpc_types::WithdrawRequest>) -> Result<Response<WithdrawResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: withdraw method called but not implemented");
-        Err(Status::unimplemented("withdraw not implemented"))
-    }
+    async fn withdraw(&self, request: Request<rpc_types::WithdrawRequest>) -> Result<Response<WithdrawResponse>, Status> {
+        let (body, signer, nonce) = self.authenticate(request.into_inner())?;
+        let account = if body.account.is_empty() { signer.clone() } else { body.account.clone() };
+        // Withdrawing from the staking vault unstakes credits of the signer
+        if self.staking.is_vault(&account) {
+            let amount = parse_amount(&body.amount).ok_or_else(|| Status::invalid_argument(format!("Invalid amount: {}", body.amount)))?;
+            if amount == 0 {
+                return Err(Status::invalid_argument("Amount must be positive"));
+            }
+            let fee = self.check_fee(&body.fee, &self.params.withdraw_fee, &body.auctioneer)?;
+            let tx_hash = random::<[u8; 32]>().to_vec();
+            let now = chrono::Utc::now().timestamp() as u64;
+            self.staking.unstake(&signer, amount, (&self.params.auctioneer, fee), &tx_hash, now)?;
+            nonce.commit();
+            return Ok(Response::new(WithdrawResponse { tx_hash, body: Some(WithdrawResponseBody {}) }));
+        }
+        // Prover earnings are withdrawn by the prover owner, other accounts by themselves
+        let withdrawal_type = match self.storage.get_prover(&account)? {
+            Some((stats, _)) => {
+                require_signer(&signer, &stats.owner, "prover owner")?;
+                WithdrawalType::Prover
+            }
+            None => {
+                require_signer(&signer, &account, "account")?;
+                WithdrawalType::Owner
+            }
+        };
+        let amount = parse_amount(&body.amount).ok_or_else(|| Status::invalid_argument(format!("Invalid amount: {}", body.amount)))?;
+        if amount == 0 {
+            return Err(Status::invalid_argument("Amount must be positive"));
+        }
+        let fee = self.check_fee(&body.fee, &self.params.withdraw_fee, &body.auctioneer)?;
+
+        let tx_hash = random::<[u8; 32]>().to_vec();
+        let now = chrono::Utc::now().timestamp() as u64;
+        self.ledger.withdraw(&account, amount, (&self.params.auctioneer, fee), &tx_hash, now)?;
+        nonce.commit();
+        self.storage.put_withdrawal_receipt(&WithdrawalReceipt {
+            tx_hash: tx_hash.clone(),
+            amount: amount.to_string(),
+            created_at: now,
+            withdrawal_type: withdrawal_type as i32,
+            account: account.clone(),
+            operation: BalanceOperation::WithdrawRequest as i32,
+            sender: Some(signer),
+        })?;
+        tracing::info!("PROVER_NETWORK: Withdrew {} credits from {}", amount, hex::encode(&account));
+
+        Ok(Response::new(WithdrawResponse { tx_hash, body: Some(WithdrawResponseBody {}) }))
+    }
+
+    async fn get_filtered_reservations(&self, request: Request<GetFilteredReservationsRequest>) -> Result<Response<GetFilteredReservationsResponse>, Status> {
+        let req = request.into_inner();
+        let mut reservations: Vec<Reservation> = self
+            .storage
+            .reservations()?
+            .into_iter()
+            .filter(|r| req.requester.as_ref().is_none_or(|requester| requester.is_empty() || &r.requester == requester))
+            .collect();
+        reservations.sort_by_key(|r| r.created_at);
 
-    async fn get_filtered_reservations(&self, _request: Request<GetFilteredReservationsRequest>) -> Result<Response<GetFilteredReservationsResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: get_filtered_reservations method called but not implemented");
-        Err(Status::unimplemented("get_filtered_reservations not implemented"))
+        let page = req.page.unwrap_or(0) as usize;
+        let limit = req.limit.unwrap_or(10).min(100) as usize;
+        let reservations = reservations.into_iter().skip(page * limit).take(limit).collect();
+        Ok(Response::new(GetFilteredReservationsResponse { reservations }))
     }
 
-    async fn add_reservation(&self, _request: Request<AddReservationRequest>) -> Result<Response<AddReservationResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: add_reservation method called but not implemented");
-        Err(Status::unimplemented("add_reservation not implemented"))
+    async fn add_reservation(&self, request: Request<AddReservationRequest>) -> Result<Response<AddReservationResponse>, Status> {
+        let (body, signer, nonce) = self.authenticate(request.into_inner())?;
+        require_signer(&signer, &self.params.owner, "owner")?;
+        if body.requester.is_empty() || body.fulfiller.is_empty() {
+            return Err(Status::invalid_argument("Requester and fulfiller are required"));
+        }
+        tracing::info!("PROVER_NETWORK: Reserved {} for requester {}", hex::encode(&body.fulfiller), hex::encode(&body.requester));
+
+        self.storage.put_reservation(&Reservation {
+            requester: body.requester,
+            fulfiller: body.fulfiller,
+            created_at: chrono::Utc::now().timestamp() as u64,
+            ..Default::default()
+        })?;
+        nonce.commit();
+
+        Ok(Response::new(AddReservationResponse {
+            tx_hash: random::<[u8; 32]>().to_vec(),
+            body: Some(AddReservationResponseBody {}),
+        }))
     }
 
-    async fn remove_reservation(&self, _request: Request<RemoveReservationRequest>) -> Result<Response<RemoveReservationResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: remove_reservation method called but not implemented");
-        Err(Status::unimplemented("remove_reservation not implemented"))
+    async fn remove_reservation(&self, request: Request<RemoveReservationRequest>) -> Result<Response<RemoveReservationResponse>, Status> {
+        let (body, signer, nonce) = self.authenticate(request.into_inner())?;
+        require_signer(&signer, &self.params.owner, "owner")?;
+        if !self.storage.remove_reservation(&body.requester)? {
+            return Err(Status::not_found("Reservation not found"));
+        }
+        nonce.commit();
+        tracing::info!("PROVER_NETWORK: Removed the reservation of requester {}", hex::encode(&body.requester));
+
+        Ok(Response::new(RemoveReservationResponse {
+            tx_hash: random::<[u8; 32]>().to_vec(),
+            body: Some(RemoveReservationResponseBody {}),
+        }))
     }
 
-    async fn bid(&self, _request: Request<BidRequest>) -> Result<Response<BidResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: bid method called but not implemented");
-        Err(Status::unimplemented("bid not implemented"))
+    async fn bid(&self, request: Request<BidRequest>) -> Result<Response<BidResponse>, Status> {
+        let (body, signer, nonce) = self.authenticate(request.into_inner())?;
+        // Delegates bid on behalf of their owner, which fulfills and earns the request
+        let owner = self.owner_of(&signer)?;
+        let prover = if body.prover.is_empty() { owner.clone() } else { body.prover.clone() };
+        require_signer(&owner, &prover, "prover")?;
+        tracing::info!("PROVER_NETWORK: bid from {} on request {}: {}", hex::encode(&prover), hex::encode(&body.request_id), body.amount);
+
+        self.staking.require_min_stake(&prover)?;
+        let now = chrono::Utc::now().timestamp() as u64;
+        let placed = self.auctioneer.place_bid(&body.request_id, &prover, &body.amount, now)?;
+        nonce.commit();
+        if placed {
+            self.provers.record_bid(&prover, now)?;
+        } else {
+            self.provers.record_activity(&prover, now, |_| {})?;
+        }
+
+        Ok(Response::new(BidResponse {
+            tx_hash: random::<[u8; 32]>().to_vec(),
+            body: Some(BidResponseBody {}),
+        }))
     }
 
-    async fn settle(&self, _request: Request<SettleRequest>) -> Result<Response<SettleResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: settle method called but not implemented");
-        Err(Status::unimplemented("settle not implemented"))
+    async fn settle(&self, request: Request<SettleRequest>) -> Result<Response<SettleResponse>, Status> {
+        let (body, signer, nonce) = self.authenticate(request.into_inner())?;
+        tracing::info!("PROVER_NETWORK: settle of request {} by {}", hex::encode(&body.request_id), hex::encode(&signer));
+        require_signer(&signer, &self.params.auctioneer, "auctioneer")?;
+
+        let now = chrono::Utc::now().timestamp() as u64;
+        let winner = self.auctioneer.settle(&body.request_id, &body.winner, now)?;
+        nonce.commit();
+        tracing::info!("PROVER_NETWORK: Request {} assigned to {}", hex::encode(&body.request_id), hex::encode(&winner));
+
+        Ok(Response::new(SettleResponse {
+            tx_hash: random::<[u8; 32]>().to_vec(),
+            body: Some(SettleResponseBody {}),
+        }))
     }
 
-    async fn get_provers_by_uptime(&self, _request: Request<GetProversByUptimeRequest>) -> Result<Response<GetProversByUptimeResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: get_provers_by_uptime method called but not implemented");
-        Err(Status::unimplemented("get_provers_by_uptime not implemented"))
+    async fn get_provers_by_uptime(&self, request: Request<GetProversByUptimeRequest>) -> Result<Response<GetProversByUptimeResponse>, Status> {
+        let now = chrono::Utc::now().timestamp() as u64;
+        let provers = self.provers.provers_by_uptime(now, request.into_inner().high_availability_only)?;
+        Ok(Response::new(GetProversByUptimeResponse { provers }))
     }
 
     async fn sign_in(&self, _request: Request<SignInRequest>) -> Result<Response<SignInResponse>, Status> {
@@ -723,9 +1216,21 @@ impl prover_network_server::ProverNetwork for ProverNetworkServiceImpl {
         Err(Status::unimplemented("claim_gpu not implemented"))
     }
 
-    async fn set_gpu_variant(&self, _request: Request<SetGpuVariantRequest>) -> Result<Response<SetGpuVariantResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: set_gpu_variant method called but not implemented");
-        Err(Status::unimplemented("set_gpu_variant not implemented"))
+    /// Provers announce themselves with it, and stay live for hosted requests by sending it within the liveness window
+    async fn set_gpu_variant(&self, request: Request<SetGpuVariantRequest>) -> Result<Response<SetGpuVariantResponse>, Status> {
+        let (body, signer, nonce) = self.authenticate(request.into_inner())?;
+        // Delegates keep their owner live
+        let prover = self.owner_of(&signer)?;
+        let variant = GpuVariant::try_from(body.variant).map(|v| v.as_str_name()).unwrap_or("UNKNOWN");
+        tracing::debug!("PROVER_NETWORK: set_gpu_variant from {}: {}", hex::encode(&prover), variant);
+
+        self.provers.record_activity(&prover, chrono::Utc::now().timestamp() as u64, |_| {})?;
+        nonce.commit();
+
+        Ok(Response::new(SetGpuVariantResponse {
+            tx_hash: random::<[u8; 32]>().to_vec(),
+            body: Some(SetGpuVariantResponseBody {}),
+        }))
     }
 
     async fn link_whitelisted_twitter(&self, _request: Request<LinkWhitelistedTwitterRequest>) -> Result<Response<LinkWhitelistedTwitterResponse>, Status> {
@@ -828,29 +1333,63 @@ impl prover_network_server::ProverNetwork for ProverNetworkServiceImpl {
         Err(Status::unimplemented("get_config_values not implemented"))
     }
 
-    async fn get_prover_stats(&self, _request: Request<GetProverStatsRequest>) -> Result<Response<GetProverStatsResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: get_prover_stats method called but not implemented");
-        Err(Status::unimplemented("get_prover_stats not implemented"))
+    async fn get_prover_stats(&self, request: Request<GetProverStatsRequest>) -> Result<Response<GetProverStatsResponse>, Status> {
+        // Stats of a single prover when an address is given, of the whole network otherwise
+        let address = request.into_inner().address.filter(|a| !a.is_empty());
+        let now = chrono::Utc::now().timestamp() as u64;
+        let provers: Vec<ProverStats> = self
+            .storage
+            .provers()?
+            .into_iter()
+            .map(|(stats, _)| stats)
+            .filter(|stats| address.as_ref().is_none_or(|a| &stats.address == a))
+            .collect();
+
+        // Cycles are counted by the analytics as requests are fulfilled, gas by the prover stats
+        let total_cycles = self.analytics.totals(address.as_deref().unwrap_or_default())?.cycles;
+        let total_gas_proved: u128 = provers.iter().filter_map(|s| parse_amount(&s.total_gas_proved)).sum();
+        let total_earnings: u128 = provers.iter().filter_map(|s| parse_amount(&s.total_earnings)).sum();
+
+        Ok(Response::new(GetProverStatsResponse {
+            total_earnings: total_earnings.to_string(),
+            total_cycles: total_cycles.to_string(),
+            total_gas_proved: total_gas_proved.to_string(),
+            active_provers: provers.iter().filter(|s| self.provers.is_live(s, now)).count().to_string(),
+            total_proofs_won: provers.iter().map(|s| s.successful_requests).sum::<u64>().to_string(),
+        }))
     }
 
-    async fn get_filtered_prover_stats(&self, _request: Request<GetFilteredProverStatsRequest>) -> Result<Response<GetFilteredProverStatsResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: get_filtered_prover_stats method called but not implemented");
-        Err(Status::unimplemented("get_filtered_prover_stats not implemented"))
+    async fn get_filtered_prover_stats(&self, request: Request<GetFilteredProverStatsRequest>) -> Result<Response<GetFilteredProverStatsResponse>, Status> {
+        let req = request.into_inner();
+        let mut stats: Vec<ProverStats> = self.storage.provers()?.into_iter().map(|(stats, _)| stats).collect();
+        stats.sort_by(|a, b| b.successful_requests.cmp(&a.successful_requests).then(a.created_at.cmp(&b.created_at)));
+
+        let page = req.page.unwrap_or(0) as usize;
+        let limit = req.limit.unwrap_or(10).min(100) as usize;
+        let stats = stats.into_iter().skip(page * limit).take(limit).collect();
+        Ok(Response::new(GetFilteredProverStatsResponse { stats }))
     }
 
-    async fn get_prover_stats_detail(&self, _request: Request<GetProverStatsDetailRequest>) -> Result<Response<GetProverStatsDetailResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: get_prover_stats_detail method called but not implemented");
-        Err(Status::unimplemented("get_prover_stats_detail not implemented"))
+    async fn get_prover_stats_detail(&self, request: Request<GetProverStatsDetailRequest>) -> Result<Response<GetProverStatsDetailResponse>, Status> {
+        let address = request.into_inner().address;
+        let (stats, _) = self.storage.get_prover(&address)?.ok_or_else(|| Status::not_found("Prover not found"))?;
+        Ok(Response::new(GetProverStatsDetailResponse { stats: Some(stats) }))
     }
 
-    async fn get_prover_search_results(&self, _request: Request<GetProverSearchResultsRequest>) -> Result<Response<GetProverSearchResultsResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: get_prover_search_results method called but not implemented");
-        Err(Status::unimplemented("get_prover_search_results not implemented"))
+    async fn get_prover_search_results(&self, request: Request<GetProverSearchResultsRequest>) -> Result<Response<GetProverSearchResultsResponse>, Status> {
+        let provers = self.search.search(&request.into_inner().query).provers;
+        Ok(Response::new(GetProverSearchResultsResponse { provers }))
     }
 
-    async fn get_filtered_bid_history(&self, _request: Request<GetFilteredBidHistoryRequest>) -> Result<Response<GetFilteredBidHistoryResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: get_filtered_bid_history method called but not implemented");
-        Err(Status::unimplemented("get_filtered_bid_history not implemented"))
+    async fn get_filtered_bid_history(&self, request: Request<GetFilteredBidHistoryRequest>) -> Result<Response<GetFilteredBidHistoryResponse>, Status> {
+        let req = request.into_inner();
+        let bids = self.auctioneer.bid_history(&req.request_id, req.winner_first.unwrap_or(false))?;
+
+        // Apply pagination
+        let page = req.page.unwrap_or(0) as usize;
+        let limit = req.limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT) as usize;
+        let bids = bids.into_iter().skip(page.saturating_mul(limit)).take(limit).collect();
+        Ok(Response::new(GetFilteredBidHistoryResponse { bids }))
     }
 
     async fn get_tee_whitelist_status(&self, _request: Request<GetTeeWhitelistStatusRequest>) -> Result<Response<GetTeeWhitelistStatusResponse>, Status> {
@@ -868,118 +1407,662 @@ impl prover_network_server::ProverNetwork for ProverNetworkServiceImpl {
         Err(Status::unimplemented("get_filtered_settlement_requests not implemented"))
     }
 
-    async fn get_filtered_provers(&self, _request: Request<GetFilteredProversRequest>) -> Result<Response<GetFilteredProversResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: get_filtered_provers method called but not implemented");
-        Err(Status::unimplemented("get_filtered_provers not implemented"))
+    async fn get_filtered_provers(&self, request: Request<GetFilteredProversRequest>) -> Result<Response<GetFilteredProversResponse>, Status> {
+        let req = request.into_inner();
+        let mut stats: Vec<ProverStats> = self.storage.provers()?.into_iter().map(|(stats, _)| stats).collect();
+        stats.sort_by_key(|s| s.created_at);
+
+        let page = req.page.unwrap_or(0) as usize;
+        let limit = req.limit.unwrap_or(10).min(100) as usize;
+        let provers = stats
+            .into_iter()
+            .skip(page * limit)
+            .take(limit)
+            .map(|s| Prover {
+                address: s.address,
+                owner: s.owner,
+                name: s.name,
+                block_number: s.block_number,
+                tx_hash: s.tx_hash,
+                staker_fee_bips: s.staker_fee_bips.unwrap_or_else(|| "0".to_string()),
+            })
+            .collect();
+        Ok(Response::new(GetFilteredProversResponse { provers }))
     }
 
-    async fn get_prover_stake_balance(&self, _request: Request<GetProverStakeBalanceRequest>) -> Result<Response<GetProverStakeBalanceResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: get_prover_stake_balance method called but not implemented");
-        Err(Status::unimplemented("get_prover_stake_balance not implemented"))
+    async fn get_prover_stake_balance(&self, request: Request<GetProverStakeBalanceRequest>) -> Result<Response<GetProverStakeBalanceResponse>, Status> {
+        let req = request.into_inner();
+        let amount = self.staking.stake_of(&req.prover)?;
+        Ok(Response::new(GetProverStakeBalanceResponse { amount: amount.to_string() }))
     }
 
-    async fn get_filtered_staker_stake_balance_logs(&self, _request: Request<GetFilteredStakerStakeBalanceLogsRequest>) -> Result<Response<GetFilteredStakerStakeBalanceLogsResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: get_filtered_staker_stake_balance_logs method called but not implemented");
-        Err(Status::unimplemented("get_filtered_staker_stake_balance_logs not implemented"))
+    async fn get_filtered_staker_stake_balance_logs(&self, request: Request<GetFilteredStakerStakeBalanceLogsRequest>) -> Result<Response<GetFilteredStakerStakeBalanceLogsResponse>, Status> {
+        let req = request.into_inner();
+        // Stakers only see their stakes and unstakes, not what happened to the stake while proving
+        let staker_operations = [StakeBalanceOperation::Stake as i32, StakeBalanceOperation::Unstake as i32];
+        let logs: Vec<StakeBalanceLog> = self
+            .filter_stake_logs(req.address, req.operation, req.minimum_timestamp, req.maximum_timestamp)?
+            .into_iter()
+            .filter(|log| staker_operations.contains(&log.operation))
+            .collect();
+
+        let page = req.page.unwrap_or(0) as usize;
+        let limit = req.limit.unwrap_or(10).min(100) as usize;
+        let logs = logs.into_iter().skip(page * limit).take(limit).collect();
+        Ok(Response::new(GetFilteredStakerStakeBalanceLogsResponse { logs }))
     }
 
-    async fn get_filtered_prover_stake_balance_logs(&self, _request: Request<GetFilteredProverStakeBalanceLogsRequest>) -> Result<Response<GetFilteredProverStakeBalanceLogsResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: get_filtered_prover_stake_balance_logs method called but not implemented");
-        Err(Status::unimplemented("get_filtered_prover_stake_balance_logs not implemented"))
+    async fn get_filtered_prover_stake_balance_logs(&self, request: Request<GetFilteredProverStakeBalanceLogsRequest>) -> Result<Response<GetFilteredProverStakeBalanceLogsResponse>, Status> {
+        let req = request.into_inner();
+        let logs = self.filter_stake_logs(req.address, req.operation, req.minimum_timestamp, req.maximum_timestamp)?;
+
+        let page = req.page.unwrap_or(0) as usize;
+        let limit = req.limit.unwrap_or(10).min(100) as usize;
+        let logs = logs.into_iter().skip(page * limit).take(limit).collect();
+        Ok(Response::new(GetFilteredProverStakeBalanceLogsResponse { logs }))
     }
 
     async fn get_delegation_params(&self, _request: Request<GetDelegationParamsRequest>) -> Result<Response<GetDelegationParamsResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: get_delegation_params method called but not implemented");
-        Err(Status::unimplemented("get_delegation_params not implemented"))
+        Ok(Response::new(GetDelegationParamsResponse {
+            auctioneer: self.params.auctioneer.clone(),
+            fee: self.params.delegation_fee.clone(),
+        }))
+    }
+
+    async fn set_delegation(&self, request: Request<SetDelegationRequest>) -> Result<Response<SetDelegationResponse>, Status> {
+        let (body, signer, nonce) = self.authenticate(request.into_inner())?;
+        if body.delegate.is_empty() {
+            return Err(Status::invalid_argument("Delegate is required"));
+        }
+        // Owners offer a delegation, which the delegate accepts by naming its owner as the prover
+        let accepting = body.delegate == signer && !body.prover.is_empty() && body.prover != signer;
+        // Provers are owned by the key they were learnt from, which alone can delegate them
+        let owner = if body.prover.is_empty() { signer.clone() } else { body.prover.clone() };
+        if !accepting {
+            require_signer(&signer, &owner, "prover owner")?;
+        }
+        // Delegations don't chain: delegates can't delegate and owners can't be delegates
+        if self.storage.get_delegation_owner(&owner)?.is_some() {
+            return Err(Status::invalid_argument(format!("{} is a delegate and can't delegate", hex::encode(&owner))));
+        }
+        if body.delegate == owner || self.storage.get_delegation(&body.delegate)?.is_some() {
+            return Err(Status::invalid_argument(format!("{} can't be a delegate", hex::encode(&body.delegate))));
+        }
+        self.check_delegate(&body.delegate)?;
+        if self.storage.get_delegation_owner(&body.delegate)?.is_some_and(|o| o != owner) {
+            return Err(Status::already_exists(format!("{} is already a delegate of another owner", hex::encode(&body.delegate))));
+        }
+        if accepting && self.storage.get_delegation_offer(&owner)?.is_none_or(|offer| offer.delegate != body.delegate) {
+            return Err(Status::failed_precondition(format!(
+                "{} has not offered a delegation to {}",
+                hex::encode(&owner),
+                hex::encode(&body.delegate)
+            )));
+        }
+        // The owner pays the delegation fee when offering it
+        let minimum_fee = if accepting { "0" } else { self.params.delegation_fee.as_str() };
+        let fee = self.check_fee(&body.fee, minimum_fee, &body.auctioneer)?;
+
+        let tx_hash = random::<[u8; 32]>().to_vec();
+        let now = chrono::Utc::now().timestamp() as u64;
+        self.ledger.pay_fee(BalanceOperation::DelegateFee, &signer, &self.params.auctioneer, fee, &tx_hash, now)?;
+        let delegation = Delegation { owner: owner.clone(), delegate: body.delegate.clone(), created_at: now };
+        if !accepting {
+            self.storage.put_delegation_offer(&delegation)?;
+            nonce.commit();
+            tracing::info!("PROVER_NETWORK: {} offered a delegation to {}", hex::encode(&owner), hex::encode(&body.delegate));
+        } else if self.storage.put_delegation(&delegation)? {
+            nonce.commit();
+            tracing::info!("PROVER_NETWORK: {} delegated to {}", hex::encode(&owner), hex::encode(&body.delegate));
+        } else {
+            return Err(Status::already_exists(format!("{} is already a delegate of another owner", hex::encode(&body.delegate))));
+        }
+
+        Ok(Response::new(SetDelegationResponse { tx_hash, body: Some(SetDelegationResponseBody {}) }))
     }
 
-    async fn set_delegation(&self, _request: Request<SetDelegationRequest>) -> Result<Response<SetDelegationResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: set_delegation method called but not implemented");
-        Err(Status::unimplemented("set_delegation not implemented"))
+    async fn get_delegation(&self, request: Request<GetDelegationRequest>) -> Result<Response<GetDelegationResponse>, Status> {
+        let delegation = self.storage.get_delegation(&request.into_inner().prover)?;
+        Ok(Response::new(GetDelegationResponse { delegation }))
     }
 
-    async fn get_delegation(&self, _request: Request<GetDelegationRequest>) -> Result<Response<GetDelegationResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: get_delegation method called but not implemented");
-        Err(Status::unimplemented("get_delegation not implemented"))
+    async fn get_filtered_withdrawal_receipts(&self, request: Request<GetFilteredWithdrawalReceiptsRequest>) -> Result<Response<GetFilteredWithdrawalReceiptsResponse>, Status> {
+        let req = request.into_inner();
+        let mut receipts: Vec<WithdrawalReceipt> = self
+            .storage
+            .withdrawal_receipts()?
+            .into_iter()
+            .filter(|receipt| req.address.as_ref().is_none_or(|a| a.is_empty() || &receipt.account == a))
+            .collect();
+        // Newest first
+        receipts.reverse();
+
+        let page = req.page.unwrap_or(0) as usize;
+        let limit = req.limit.unwrap_or(10).min(100) as usize;
+        let receipts = receipts.into_iter().skip(page * limit).take(limit).collect();
+        Ok(Response::new(GetFilteredWithdrawalReceiptsResponse { receipts }))
     }
+}
 
-    async fn get_filtered_withdrawal_receipts(&self, _request: Request<GetFilteredWithdrawalReceiptsRequest>) -> Result<Response<GetFilteredWithdrawalReceiptsResponse>, Status> {
-        tracing::info!("PROVER_NETWORK: get_filtered_withdrawal_receipts method called but not implemented");
-        Err(Status::unimplemented("get_filtered_withdrawal_receipts not implemented"))
+/// Nonce consumed by [`ProverNetworkServiceImpl::authenticate`], given back when
+/// dropped before the handler commits the state change of its request
+struct NonceClaim {
+    storage: Storage,
+    signer: Vec<u8>,
+    nonce: u64,
+    committed: bool,
+}
+
+impl NonceClaim {
+    /// Keep the nonce consumed, once the request changed the state
+    fn commit(mut self) {
+        self.committed = true;
     }
 }
 
-fn encode_body_for_signing<T: Message>(format: i32, body: &T) -> eyre::Result<Vec<u8>> {
-    let fmt = MessageFormat::try_from(format).unwrap_or(MessageFormat::Binary);
-    match fmt {
-        MessageFormat::Binary => {
-            // Protobuf canonical binary
-            let mut buf = Vec::new();
-            body.encode(&mut buf)?;
-            Ok(buf)
-        }
-        // MessageFormat::Json => {
-        //     // Only use if your client truly signed JSON and both sides enforce a canonical form.
-        //     // If you control both ends, prefer Binary to avoid JSON canonicalization traps.
-        //     #[derive(Serialize)]
-        //     struct Canon<'a> {
-        //         nonce: u64,
-        //         vk_hash: &'a [u8],
-        //         version: &'a str,
-        //         mode: i32,
-        //         strategy: i32,
-        //         stdin_uri: &'a str,
-        //         deadline: u64,
-        //         cycle_limit: u64,
-        //         gas_limit: u64,
-        //     }
-        //     // Map your fields EXACTLY as the client did:
-        //     let c = Canon {
-        //         nonce: body.nonce,
-        //         vk_hash: &body.vk_hash,
-        //         version: &body.version,
-        //         mode: body.mode,
-        //         strategy: body.strategy,
-        //         stdin_uri: &body.stdin_uri,
-        //         deadline: body.deadline,
-        //         cycle_limit: body.cycle_limit,
-        //         gas_limit: body.gas_limit,
-        //     };
-        //     Ok(serde_json::to_vec(&c)?)
-        // }
-        // Fallbacks if your enum has others:
-        _ => {
-            // Default to protobuf binary unless you KNOW another format was used.
-            let mut buf = Vec::new();
-            body.encode(&mut buf)?;
-            Ok(buf)
+impl Drop for NonceClaim {
+    fn drop(&mut self) {
+        if self.committed {
+            return;
+        }
+        if let Err(e) = self.storage.release_nonce(&self.signer, self.nonce) {
+            tracing::error!("PROVER_NETWORK: Failed to give back nonce {} of {}: {}", self.nonce, hex::encode(&self.signer), e);
         }
     }
 }
 
-pub fn recover_signer_addr(msg_bytes: Vec<u8>, sig_bytes: &[u8]) -> eyre::Result<Vec<u8>> {
-    // Apply EIP-191 prefix (Ethereum personal message format)
-    let msg_hash = hash_message(&msg_bytes); // This applies EIP-191 prefix and hashes
+/// Reject the request unless `signer` is the fulfiller assigned to it
+fn authorize_fulfiller(request: &ProofRequest, signer: &[u8]) -> Result<(), Status> {
+    match &request.fulfiller {
+        Some(fulfiller) => require_signer(signer, fulfiller, "assigned fulfiller"),
+        None => Err(Status::failed_precondition("Proof request is not assigned to a fulfiller")),
+    }
+}
+
+/// Reject the request unless it is assigned and still waiting for its proof
+fn require_assigned(request: &ProofRequest) -> Result<(), Status> {
+    if request.fulfillment_status != FulfillmentStatus::Assigned as i32 {
+        return Err(Status::failed_precondition("Proof request is not waiting for a proof"));
+    }
+    Ok(())
+}
 
-    // Parse the signature and recover the address
-    let sig = Signature::try_from(sig_bytes)?;
-    let address = sig.recover(msg_hash)?;
-    let address_bytes = address.as_bytes().to_vec();
-    Ok(address_bytes)
+/// URL that provers can download an artifact from, `s3://` URIs are presigned
+fn presign_uri(s3: Option<&S3Presigner>, uri: &str) -> String {
+    match s3 {
+        Some(s3) => s3.public_uri(uri),
+        None => uri.to_string(),
+    }
 }
 
-// pub fn recover_address_from_personal_sign(message: impl AsRef<[u8]>, sig_hex: &str) -> Result<Address> {
-//     // Parse 0x… signature; v can be 27/28 or 0/1 — ethers handles both.
-//     let sig = Signature::from_str(sig_hex)?;
-//     // Keccak256("\x19Ethereum Signed Message:\n{len(m)}" || m)
-//     let digest = hash_message(message);
-//     // Recover the address that signed the digest
-//     let addr = sig.recover(digest)?;
-//     Ok(addr)
-// }
-
-fn generate_proof_url() -> String {
-    // Generate a URL pointing to our HTTP server
-    // The client will use this URL to PUT the artifact data
-    format!("http://localhost:8082/artifacts/Proof/{}", hex::encode(random::<[u8; 16]>()))
-}
\ No newline at end of file
+/// Stored requests keep the `s3://` URIs of their artifacts, presigned each time they are served
+fn presign_request(s3: Option<&S3Presigner>, mut request: ProofRequest) -> ProofRequest {
+    request.program_public_uri = presign_uri(s3, &request.program_public_uri);
+    request.stdin_public_uri = presign_uri(s3, &request.stdin_public_uri);
+    request
+}
+
+/// Most selective index for a filter: a single fulfiller, requester or program, then a status
+fn select_index(filter: &GetFilteredProofRequestsRequest) -> ProofRequestIndex {
+    let given = |value: &Option<Vec<u8>>| value.clone().filter(|v| !v.is_empty());
+    if let Some(fulfiller) = filter.fulfiller.clone() {
+        return ProofRequestIndex::Fulfiller(fulfiller);
+    }
+    if let Some(requester) = given(&filter.requester) {
+        return ProofRequestIndex::Requester(requester);
+    }
+    if let Some(vk_hash) = given(&filter.vk_hash) {
+        return ProofRequestIndex::VkHash(vk_hash);
+    }
+    match filter.fulfillment_status {
+        Some(status) => ProofRequestIndex::FulfillmentStatus(status),
+        None => ProofRequestIndex::CreatedAt,
+    }
+}
+
+/// Whether the `not_bid_by` address of a filter, if any, has no bid on the request
+fn not_bid_by(storage: &Storage, req: &ProofRequest, filter: &GetFilteredProofRequestsRequest) -> Result<bool, StorageError> {
+    match &filter.not_bid_by {
+        Some(bidder) if !bidder.is_empty() => Ok(storage.bids(&req.request_id)?.iter().all(|bid| &bid.bidder != bidder)),
+        _ => Ok(true),
+    }
+}
+
+/// Whether a proof request matches the filters of `get_filtered_proof_requests`, pagination aside
+fn matches_filter(req: &ProofRequest, filter: &GetFilteredProofRequestsRequest) -> bool {
+    // Filter by requester if provided
+    if let Some(ref filter_requester) = filter.requester {
+        if !filter_requester.is_empty() && req.requester != *filter_requester {
+            tracing::debug!("PROVER_NETWORK: Received get_filtered_proof_requests request: {:?}. Filtering by requester. Not matching: {:?}", filter, req.requester);
+            return false;
+        }
+    }
+
+    // Filter by fulfillment status if provided
+    if filter.fulfillment_status.is_some() && req.fulfillment_status != filter.fulfillment_status.unwrap() {
+        tracing::debug!("PROVER_NETWORK: Received get_filtered_proof_requests request: {:?}. Filtering by fulfillment_status. Not matching: {:?}", filter, req.fulfillment_status);
+        return false;
+    }
+
+    // Filter by execution status if provided
+    if filter.execution_status.is_some() && req.execution_status != filter.execution_status.unwrap() {
+        tracing::debug!("PROVER_NETWORK: Received get_filtered_proof_requests request: {:?}. Filtering by execution_status. Not matching: {:?}", filter, req.execution_status);
+        return false;
+    }
+
+    // Filter by vk_hash if provided
+    if let Some(ref filter_vk_hash) = filter.vk_hash {
+        if !filter_vk_hash.is_empty() && req.vk_hash != *filter_vk_hash {
+            tracing::debug!("PROVER_NETWORK: Received get_filtered_proof_requests request: {:?}. Filtering by vk_hash. Not matching: {:?}", filter, req.vk_hash);
+            return false;
+        }
+    }
+
+    // Filter by version if provided
+    if let Some(ref filter_version) = filter.version {
+        if !filter_version.is_empty() && req.version != *filter_version {
+            tracing::debug!("PROVER_NETWORK: Received get_filtered_proof_requests request: {:?}. Filtering by version. Not matching: {:?}", filter, req.version);
+            return false;
+        }
+    }
+
+    // Filter by mode if provided
+    if filter.mode.is_some() && req.mode != filter.mode.unwrap() {
+        tracing::debug!("PROVER_NETWORK: Received get_filtered_proof_requests request: {:?}. Filtering by mode. Not matching: {:?}", filter, req.mode);
+        return false;
+    }
+
+    // Filter by minimum_deadline if provided
+    if filter.minimum_deadline.is_some() && req.deadline <= filter.minimum_deadline.unwrap() {
+        tracing::debug!("PROVER_NETWORK: Received get_filtered_proof_requests request: {:?}. Filtering by minimum_deadline. Not matching: received in the request {:?} and stored in the proof_request {:?}", filter, filter.minimum_deadline, req.deadline);
+        return false;
+    }
+
+    // Filter by fulfiller if provided
+    if let Some(ref filter_fulfiller) = filter.fulfiller {
+        if req.fulfiller.as_ref() != Some(filter_fulfiller) {
+            tracing::debug!("PROVER_NETWORK: Received get_filtered_proof_requests request: {:?}. Filtering by fulfiller. Not matching: {:?}", filter, req.fulfiller);
+            return false;
+        }
+    }
+
+    // Filter by from if provided
+    if filter.from.is_some() && req.created_at < filter.from.unwrap() {
+        tracing::debug!("PROVER_NETWORK: Received get_filtered_proof_requests request: {:?}. Filtering by from. Not matching: {:?}", filter, req.created_at);
+        return false;
+    }
+
+    // Filter by to if provided
+    if filter.to.is_some() && req.created_at > filter.to.unwrap() {
+        tracing::debug!("PROVER_NETWORK: Received get_filtered_proof_requests request: {:?}. Filtering by to. Not matching: {:?}", filter, req.created_at);
+        return false;
+    }
+
+    // Filter by execute_fail_cause if provided
+    if filter.execute_fail_cause.is_some() && req.execute_fail_cause != filter.execute_fail_cause.unwrap() {
+        tracing::debug!("PROVER_NETWORK: Received get_filtered_proof_requests request: {:?}. Filtering by execute_fail_cause. Not matching: {:?}", filter, req.execute_fail_cause);
+        return false;
+    }
+
+    // Filter by settlement_status if provided
+    if filter.settlement_status.is_some() && req.settlement_status != filter.settlement_status.unwrap() {
+        tracing::debug!("PROVER_NETWORK: Received get_filtered_proof_requests request: {:?}. Filtering by settlement_status. Not matching: {:?}", filter, req.settlement_status);
+        return false;
+    }
+
+    // Filter by error if provided
+    if filter.error.is_some() && req.error != filter.error.unwrap() {
+        tracing::debug!("PROVER_NETWORK: Received get_filtered_proof_requests request: {:?}. Filtering by error. Not matching: {:?}", filter, req.error);
+        return false;
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::signers::{LocalWallet, Signer};
+    use crate::server::auth::default_domain;
+    use ethers_core::utils::hash_message;
+    use prost::Message;
+    use prover_network_server::ProverNetwork;
+    use tokio_stream::StreamExt;
+
+    /// Service over empty storage, with a wallet to sign its requests
+    fn test_service() -> (ProverNetworkServiceImpl, Storage, LocalWallet) {
+        let storage = Storage::default();
+        let wallet = LocalWallet::new(&mut ethers::core::rand::thread_rng());
+        (ProverNetworkServiceImpl::new(storage.clone()), storage, wallet)
+    }
+
+    /// `body` signed by `wallet`, as clients send it
+    fn signed_request<R: SignedRequest>(wallet: &LocalWallet, body: R::Body) -> Request<R> {
+        let signature = wallet.sign_hash(hash_message(body.encode_to_vec())).unwrap().to_vec();
+        Request::new(R::from_parts(MessageFormat::Binary as i32, signature, Some(body)))
+    }
+
+    /// First fulfillment of request `[1; 32]` sent by `wallet`
+    fn fulfill_request(wallet: &LocalWallet) -> Request<FulfillProofRequest> {
+        let body = FulfillProofRequestBody {
+            nonce: 0,
+            request_id: vec![1; 32],
+            proof: vec![1, 2, 3],
+            reserved_metadata: None,
+            domain: default_domain(),
+            variant: TransactionVariant::FulfillVariant as i32,
+        };
+        signed_request(wallet, body)
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_proof_requests() {
+        let storage = Storage::default();
+        let service = ProverNetworkServiceImpl::new(storage.clone());
+        let request = |id: u8, status: FulfillmentStatus| ProofRequest {
+            request_id: vec![id; 32],
+            fulfillment_status: status as i32,
+            ..Default::default()
+        };
+        let status = GetProofRequestStatusResponse::default();
+        storage.put_proof_request(&request(1, FulfillmentStatus::Requested), &status).unwrap();
+        storage.put_proof_request(&request(2, FulfillmentStatus::Fulfilled), &status).unwrap();
+
+        let filter = GetFilteredProofRequestsRequest {
+            fulfillment_status: Some(FulfillmentStatus::Requested as i32),
+            ..Default::default()
+        };
+        let mut stream = service.subscribe_proof_requests(Request::new(filter)).await.unwrap().into_inner();
+
+        // Existing matching requests come first
+        assert_eq!(stream.next().await.unwrap().unwrap().request_id, vec![1; 32]);
+
+        // Then updates, filtered the same way
+        storage.put_proof_request(&request(3, FulfillmentStatus::Assigned), &status).unwrap();
+        storage.put_proof_request(&request(4, FulfillmentStatus::Requested), &status).unwrap();
+        assert_eq!(stream.next().await.unwrap().unwrap().request_id, vec![4; 32]);
+    }
+
+    #[tokio::test]
+    async fn test_lagging_subscriber_stream_ends() {
+        let storage = Storage::default();
+        let service = ProverNetworkServiceImpl::new(storage.clone());
+        let mut stream = service.subscribe_proof_requests(Request::new(Default::default())).await.unwrap().into_inner();
+
+        // More updates than the storage keeps for a subscriber that doesn't read
+        for id in 0..1100u32 {
+            let request = ProofRequest { request_id: id.to_be_bytes().to_vec(), ..Default::default() };
+            storage.put_proof_request(&request, &GetProofRequestStatusResponse::default()).unwrap();
+        }
+        assert_eq!(stream.next().await.unwrap().unwrap_err().code(), tonic::Code::DataLoss);
+        assert!(stream.next().await.is_none());
+    }
+
+    #[test]
+    fn test_provers_cant_be_delegates() {
+        let storage = Storage::default();
+        let service = ProverNetworkServiceImpl::new(storage.clone());
+        assert!(service.check_delegate(&[1; 20]).is_ok());
+
+        // Known provers
+        storage.update_prover(&[1; 20], |_, _| ()).unwrap();
+        assert_eq!(service.check_delegate(&[1; 20]).unwrap_err().code(), tonic::Code::FailedPrecondition);
+
+        // Fulfillers of assigned requests
+        let request = ProofRequest {
+            request_id: vec![1; 32],
+            fulfiller: Some(vec![2; 20]),
+            fulfillment_status: FulfillmentStatus::Assigned as i32,
+            ..Default::default()
+        };
+        storage.put_proof_request(&request, &GetProofRequestStatusResponse::default()).unwrap();
+        assert_eq!(service.check_delegate(&[2; 20]).unwrap_err().code(), tonic::Code::FailedPrecondition);
+    }
+
+    #[tokio::test]
+    async fn test_delegations_need_the_delegate_to_accept() {
+        let (service, storage, owner) = test_service();
+        let delegate = LocalWallet::new(&mut ethers::core::rand::thread_rng());
+        let (owner_address, delegate_address) = (owner.address().as_bytes().to_vec(), delegate.address().as_bytes().to_vec());
+        let body = |nonce: u64, prover: &[u8]| SetDelegationRequestBody {
+            nonce,
+            delegate: delegate_address.clone(),
+            prover: prover.to_vec(),
+            domain: default_domain(),
+            variant: TransactionVariant::DelegateVariant as i32,
+            ..Default::default()
+        };
+
+        // Accepting a delegation nobody offered
+        let status = service.set_delegation(signed_request(&delegate, body(0, &owner_address))).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+
+        // Offered but never accepted, the delegate doesn't act for the owner
+        service.set_delegation(signed_request(&owner, body(0, &[]))).await.unwrap();
+        assert_eq!(storage.get_delegation(&owner_address).unwrap(), None);
+        assert_eq!(service.owner_of(&delegate_address).unwrap(), delegate_address);
+
+        service.set_delegation(signed_request(&delegate, body(0, &owner_address))).await.unwrap();
+        assert_eq!(storage.get_delegation(&owner_address).unwrap().unwrap().delegate, delegate_address);
+        assert_eq!(service.owner_of(&delegate_address).unwrap(), owner_address);
+        assert_eq!(storage.get_delegation_offer(&owner_address).unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_transfers_need_an_account_recipient() {
+        let (service, storage, wallet) = test_service();
+        let sender = wallet.address().as_bytes().to_vec();
+        service.ledger().add_credit(&sender, 100, &[0; 32], 1).unwrap();
+        let body = |to: &[u8]| TransferRequestBody {
+            nonce: 0,
+            to: to.to_vec(),
+            amount: "10".to_string(),
+            domain: default_domain(),
+            variant: TransactionVariant::TransferVariant as i32,
+            ..Default::default()
+        };
+
+        for to in [&[][..], &[1; 19], &[1; 32], ESCROW_ACCOUNT, ISSUANCE_ACCOUNT] {
+            let status = service.transfer(signed_request(&wallet, body(to))).await.unwrap_err();
+            assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        }
+        assert_eq!(storage.get_balance(&sender).unwrap(), 100);
+
+        service.transfer(signed_request(&wallet, body(&[1; 20]))).await.unwrap();
+        assert_eq!(storage.get_balance(&[1; 20]).unwrap(), 10);
+    }
+
+    #[tokio::test]
+    async fn test_artifact_uris_are_presigned_when_read() {
+        let storage = Storage::default();
+        let s3 = S3Presigner::new(crate::storage::S3Config {
+            endpoint: "http://localhost:9000".to_string(),
+            region: "us-east-1".to_string(),
+            bucket: "artifacts".to_string(),
+            access_key_id: "key".to_string(),
+            secret_access_key: "secret".to_string(),
+            path_style: true,
+            presign_expiry: std::time::Duration::from_secs(3600),
+        })
+        .unwrap();
+        let service = ProverNetworkServiceImpl::new(storage.clone()).with_s3(Some(s3));
+        let request = ProofRequest {
+            request_id: vec![1; 32],
+            stdin_uri: "s3://artifacts/Stdin/abc".to_string(),
+            stdin_public_uri: "s3://artifacts/Stdin/abc".to_string(),
+            ..Default::default()
+        };
+        let status = GetProofRequestStatusResponse {
+            proof_public_uri: Some("s3://artifacts/Proof/abc".to_string()),
+            ..Default::default()
+        };
+        storage.put_proof_request(&request, &status).unwrap();
+
+        let details = service
+            .get_proof_request_details(Request::new(GetProofRequestDetailsRequest { request_id: vec![1; 32] }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(details.request.unwrap().stdin_public_uri.starts_with("http://localhost:9000/artifacts/Stdin/abc?X-Amz-Algorithm="));
+        let status = service
+            .get_proof_request_status(Request::new(GetProofRequestStatusRequest { request_id: vec![1; 32] }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(status.proof_public_uri.unwrap().starts_with("http://localhost:9000/artifacts/Proof/abc?"));
+
+        // The stored request keeps the URI, which does not expire
+        let (stored, _) = storage.get_proof_request(&[1; 32]).unwrap().unwrap();
+        assert_eq!(stored.stdin_public_uri, "s3://artifacts/Stdin/abc");
+    }
+
+    #[tokio::test]
+    async fn test_filtered_proof_requests_cursor_pagination() {
+        let storage = Storage::default();
+        let service = ProverNetworkServiceImpl::new(storage.clone());
+        let status = GetProofRequestStatusResponse::default();
+        for id in 1..=6u8 {
+            let request = ProofRequest {
+                request_id: vec![id; 32],
+                fulfillment_status: FulfillmentStatus::Requested as i32,
+                created_at: 100 + id as u64,
+                ..Default::default()
+            };
+            storage.put_proof_request(&request, &status).unwrap();
+        }
+        // The request assigned since then leaves the status index
+        storage
+            .update_proof_request(&[6; 32], |request, _| {
+                request.fulfillment_status = FulfillmentStatus::Assigned as i32;
+                Ok::<_, Status>(())
+            })
+            .unwrap();
+        storage.put_bid(&[2; 32], &BidHistory { bidder: vec![9; 20], ..Default::default() }).unwrap();
+
+        let filter = GetFilteredProofRequestsRequest {
+            fulfillment_status: Some(FulfillmentStatus::Requested as i32),
+            from: Some(102),
+            not_bid_by: Some(vec![9; 20]),
+            limit: Some(2),
+            ..Default::default()
+        };
+        let first = service.get_filtered_proof_requests(Request::new(filter.clone())).await.unwrap();
+        let ids: Vec<u8> = first.get_ref().requests.iter().map(|r| r.request_id[0]).collect();
+        assert_eq!(ids, vec![3, 4]);
+
+        let mut next = Request::new(filter);
+        next.metadata_mut().insert(CURSOR_METADATA, first.metadata().get(NEXT_CURSOR_METADATA).unwrap().clone());
+        let second = service.get_filtered_proof_requests(next).await.unwrap();
+        let ids: Vec<u8> = second.get_ref().requests.iter().map(|r| r.request_id[0]).collect();
+        assert_eq!(ids, vec![5]);
+        assert!(second.metadata().get(NEXT_CURSOR_METADATA).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_fail_fulfillment_keeps_finished_requests() {
+        let (service, storage, wallet) = test_service();
+        let request = ProofRequest {
+            request_id: vec![1; 32],
+            fulfiller: Some(wallet.address().as_bytes().to_vec()),
+            fulfillment_status: FulfillmentStatus::Fulfilled as i32,
+            ..Default::default()
+        };
+        storage.put_proof_request(&request, &GetProofRequestStatusResponse::default()).unwrap();
+
+        let body = FailFulfillmentRequestBody { nonce: 0, request_id: vec![1; 32], error: Some(ProofRequestError::UnknownFailure as i32) };
+        let status = service.fail_fulfillment(signed_request(&wallet, body)).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+
+        let (stored, _) = storage.get_proof_request(&[1; 32]).unwrap().unwrap();
+        assert_eq!(stored.fulfillment_status, FulfillmentStatus::Fulfilled as i32);
+        assert_eq!(stored.error, request.error);
+    }
+
+    #[tokio::test]
+    async fn test_fulfilled_requests_cant_be_fulfilled_again() {
+        let (service, storage, wallet) = test_service();
+        let request = ProofRequest {
+            request_id: vec![1; 32],
+            fulfiller: Some(wallet.address().as_bytes().to_vec()),
+            fulfillment_status: FulfillmentStatus::Fulfilled as i32,
+            execution_status: ExecutionStatus::Executed as i32,
+            deadline: u64::MAX,
+            fulfilled_at: Some(1),
+            ..Default::default()
+        };
+        storage.put_proof_request(&request, &GetProofRequestStatusResponse::default()).unwrap();
+
+        let status = service.fulfill_proof(fulfill_request(&wallet)).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+
+        let (stored, _) = storage.get_proof_request(&[1; 32]).unwrap().unwrap();
+        assert_eq!(stored.fulfilled_at, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_proofs_of_unregistered_programs_are_not_slashed() {
+        let (service, storage, wallet) = test_service();
+        let request = ProofRequest {
+            request_id: vec![1; 32],
+            vk_hash: vec![2; 32],
+            fulfiller: Some(wallet.address().as_bytes().to_vec()),
+            fulfillment_status: FulfillmentStatus::Assigned as i32,
+            execution_status: ExecutionStatus::Executed as i32,
+            deadline: u64::MAX,
+            ..Default::default()
+        };
+        storage.put_proof_request(&request, &GetProofRequestStatusResponse::default()).unwrap();
+
+        let status = service.fulfill_proof(fulfill_request(&wallet)).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+
+        let (stored, _) = storage.get_proof_request(&[1; 32]).unwrap().unwrap();
+        assert_eq!(stored.fulfillment_status, FulfillmentStatus::Assigned as i32);
+        assert!(storage.stake_logs().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unverifiable_proofs_are_not_slashed() {
+        let (service, storage, wallet) = test_service();
+        storage.put_program(&Program { vk_hash: vec![2; 32], vk: vec![3; 32], ..Default::default() }).unwrap();
+        // Requests stored before the mode and version were required
+        let request = ProofRequest {
+            request_id: vec![1; 32],
+            vk_hash: vec![2; 32],
+            fulfiller: Some(wallet.address().as_bytes().to_vec()),
+            fulfillment_status: FulfillmentStatus::Assigned as i32,
+            execution_status: ExecutionStatus::Executed as i32,
+            deadline: u64::MAX,
+            ..Default::default()
+        };
+        storage.put_proof_request(&request, &GetProofRequestStatusResponse::default()).unwrap();
+
+        let status = service.fulfill_proof(fulfill_request(&wallet)).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unavailable);
+
+        let (stored, _) = storage.get_proof_request(&[1; 32]).unwrap().unwrap();
+        assert_eq!(stored.fulfillment_status, FulfillmentStatus::Assigned as i32);
+        assert!(storage.stake_logs().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unstaked_live_provers_are_not_assigned() {
+        let (service, storage, wallet) = test_service();
+        let request = ProofRequest {
+            request_id: vec![1; 32],
+            strategy: FulfillmentStrategy::Hosted as i32,
+            fulfillment_status: FulfillmentStatus::Requested as i32,
+            ..Default::default()
+        };
+        storage.put_proof_request(&request, &GetProofRequestStatusResponse::default()).unwrap();
+
+        // Any signer becomes live by sending set_gpu_variant
+        let body = SetGpuVariantRequestBody { nonce: 0, variant: 0 };
+        service.set_gpu_variant(signed_request(&wallet, body)).await.unwrap();
+
+        let now = chrono::Utc::now().timestamp() as u64;
+        assert_eq!(service.assigner().assign_pending(now).unwrap(), 0);
+        let (stored, _) = storage.get_proof_request(&[1; 32]).unwrap().unwrap();
+        assert_eq!(stored.fulfiller, None);
+    }
+}
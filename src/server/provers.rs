@@ -0,0 +1,135 @@
+use rpc_types::*;
+use std::time::Duration;
+
+use crate::server::auction::parse_amount;
+use crate::storage::{Storage, StorageError};
+
+/// Provers not seen for this long are not live anymore when none is configured
+pub const DEFAULT_LIVENESS_WINDOW: Duration = Duration::from_secs(300);
+
+/// Uptime of the provers returned by `get_provers_by_uptime` with `high_availability_only`
+const HIGH_AVAILABILITY_UPTIME: f64 = 0.95;
+
+/// Provers known to the coordinator.
+///
+/// Provers are learnt from the signed RPCs they send (`bid`, `fulfill_proof`,
+/// `set_gpu_variant`). A prover is live while it was active within the liveness window, and its
+/// uptime is the share of its lifetime it was live.
+#[derive(Debug, Clone)]
+pub struct ProverRegistry {
+    storage: Storage,
+    liveness_window: Duration,
+}
+
+impl ProverRegistry {
+    pub fn new(storage: Storage) -> Self {
+        Self { storage, liveness_window: DEFAULT_LIVENESS_WINDOW }
+    }
+
+    pub fn with_liveness_window(mut self, window: Duration) -> Self {
+        self.liveness_window = window;
+        self
+    }
+
+    /// Record that `prover` bid on a request for the first time
+    pub fn record_bid(&self, prover: &[u8], now: u64) -> Result<(), StorageError> {
+        self.record_activity(prover, now, |stats| stats.total_auction_requests += 1)
+    }
+
+    /// Record that `prover` fulfilled a request using `gas` prover gas units
+    pub fn record_fulfillment(&self, prover: &[u8], gas: u64, now: u64) -> Result<(), StorageError> {
+        self.record_activity(prover, now, |stats| {
+            stats.successful_requests += 1;
+            let total = parse_amount(&stats.total_gas_proved).unwrap_or(0) + gas as u128;
+            stats.total_gas_proved = total.to_string();
+        })
+    }
+
+    /// Mark `prover` active at `now`, counting the time since its last activity as up within the liveness window
+    pub fn record_activity(&self, prover: &[u8], now: u64, f: impl FnOnce(&mut ProverStats)) -> Result<(), StorageError> {
+        let window = self.liveness_window.as_secs();
+        self.storage.update_prover(prover, |stats, up_secs| {
+            if stats.created_at == 0 {
+                stats.created_at = now;
+                stats.total_gas_proved = "0".to_string();
+                // Earnings may be paid before the first activity
+                if stats.total_earnings.is_empty() {
+                    stats.total_earnings = "0".to_string();
+                }
+                tracing::info!("PROVERS: New prover {}", hex::encode(prover));
+            } else {
+                *up_secs += now.saturating_sub(stats.last_active_at).min(window);
+            }
+            stats.last_active_at = stats.last_active_at.max(now);
+            f(stats);
+        })
+    }
+
+    pub fn is_live(&self, stats: &ProverStats, now: u64) -> bool {
+        stats.created_at != 0 && now.saturating_sub(stats.last_active_at) <= self.liveness_window.as_secs()
+    }
+
+    /// Share of its lifetime a prover was live, between 0 and 1
+    pub fn uptime(&self, stats: &ProverStats, up_secs: u64, now: u64) -> f64 {
+        let lifetime = now.saturating_sub(stats.created_at);
+        if lifetime == 0 {
+            return 1.0;
+        }
+        let since_last = now.saturating_sub(stats.last_active_at).min(self.liveness_window.as_secs());
+        ((up_secs + since_last) as f64 / lifetime as f64).min(1.0)
+    }
+
+    /// Provers active within the liveness window
+    pub fn live_provers(&self, now: u64) -> Result<Vec<Vec<u8>>, StorageError> {
+        Ok(self
+            .storage
+            .provers()?
+            .into_iter()
+            .filter(|(stats, _)| self.is_live(stats, now))
+            .map(|(stats, _)| stats.address)
+            .collect())
+    }
+
+    /// Known provers, highest uptime first
+    pub fn provers_by_uptime(&self, now: u64, high_availability_only: bool) -> Result<Vec<Vec<u8>>, StorageError> {
+        let mut provers: Vec<(f64, Vec<u8>)> = self
+            .storage
+            .provers()?
+            .into_iter()
+            .map(|(stats, up_secs)| (self.uptime(&stats, up_secs, now), stats.address))
+            .filter(|(uptime, _)| !high_availability_only || *uptime >= HIGH_AVAILABILITY_UPTIME)
+            .collect();
+        provers.sort_by(|a, b| b.0.total_cmp(&a.0));
+        Ok(provers.into_iter().map(|(_, address)| address).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_liveness_and_uptime() {
+        let storage = Storage::default();
+        let registry = ProverRegistry::new(storage.clone()).with_liveness_window(Duration::from_secs(100));
+        let (steady, flaky) = (vec![1; 20], vec![2; 20]);
+
+        registry.record_bid(&steady, 1000).unwrap();
+        registry.record_bid(&flaky, 1000).unwrap();
+        for now in [1050, 1100, 1150, 1200] {
+            registry.record_activity(&steady, now, |_| {}).unwrap();
+        }
+        registry.record_fulfillment(&flaky, 500, 1200).unwrap();
+
+        // The flaky prover was away between 1100 and 1200
+        let (stats, up_secs) = storage.get_prover(&flaky).unwrap().unwrap();
+        assert_eq!((stats.successful_requests, stats.total_gas_proved.as_str()), (1, "500"));
+        assert_eq!(registry.uptime(&stats, up_secs, 1200), 0.5);
+        assert_eq!(registry.provers_by_uptime(1200, false).unwrap(), vec![steady.clone(), flaky.clone()]);
+        assert_eq!(registry.provers_by_uptime(1200, true).unwrap(), vec![steady.clone()]);
+
+        assert_eq!(registry.live_provers(1250).unwrap().len(), 2);
+        registry.record_activity(&steady, 1300, |_| {}).unwrap();
+        assert_eq!(registry.live_provers(1350).unwrap(), vec![steady]);
+    }
+}
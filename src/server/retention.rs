@@ -0,0 +1,142 @@
+use rpc_types::*;
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime};
+
+use crate::storage::{is_valid_artifact_id, FsArtifactBackend, Storage, StorageError};
+
+/// Removes finished proof requests and uploaded artifacts once they are older
+/// than their retention period. Artifacts stay as long as a stored program or
+/// proof request refers to them, so they go once their request was pruned.
+#[derive(Debug, Clone)]
+pub struct Retention {
+    storage: Storage,
+    /// Artifacts are only pruned when they are stored on disk
+    artifacts: Option<FsArtifactBackend>,
+    proof_requests_period: Option<Duration>,
+    artifacts_period: Option<Duration>,
+}
+
+impl Retention {
+    pub fn new(storage: Storage, proof_requests_period: Option<Duration>) -> Self {
+        Self {
+            storage,
+            artifacts: None,
+            proof_requests_period,
+            artifacts_period: None,
+        }
+    }
+
+    /// Also prune the artifacts of `backend`
+    pub fn with_artifacts(mut self, backend: FsArtifactBackend, period: Option<Duration>) -> Self {
+        self.artifacts = Some(backend);
+        self.artifacts_period = period;
+        self
+    }
+
+    /// Whether anything has a retention period
+    pub fn is_enabled(&self) -> bool {
+        self.proof_requests_period.is_some() || (self.artifacts.is_some() && self.artifacts_period.is_some())
+    }
+
+    /// Remove the fulfilled and unfulfillable requests last updated before `now - period`
+    pub fn prune_proof_requests(&self, now: u64) -> Result<usize, StorageError> {
+        let Some(period) = self.proof_requests_period else {
+            return Ok(0);
+        };
+        let cutoff = now.saturating_sub(period.as_secs());
+        let mut removed = 0;
+        for (request, _) in self.storage.proof_requests()? {
+            let finished = request.fulfillment_status == FulfillmentStatus::Fulfilled as i32
+                || request.fulfillment_status == FulfillmentStatus::Unfulfillable as i32;
+            if finished && request.updated_at < cutoff {
+                self.storage.remove_proof_request(&request.request_id)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Ids of the artifacts referred to by the stored programs and proof requests
+    pub fn referenced_artifacts(&self) -> Result<HashSet<String>, StorageError> {
+        let mut uris = Vec::new();
+        for program in self.storage.programs()? {
+            uris.push(program.program_uri);
+        }
+        for (request, status) in self.storage.proof_requests()? {
+            uris.extend([request.program_uri, request.stdin_uri]);
+            uris.extend(status.proof_uri);
+        }
+        // Artifact URIs, presigned or not, end with the artifact id
+        Ok(uris
+            .iter()
+            .filter_map(|uri| uri.split('?').next()?.rsplit('/').next())
+            .filter(|id| is_valid_artifact_id(id))
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Periodically prune what is past its retention period
+    pub fn spawn(self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let now = chrono::Utc::now().timestamp() as u64;
+                match self.prune_proof_requests(now) {
+                    Ok(0) => {}
+                    Ok(removed) => tracing::info!("RETENTION: Removed {} proof requests", removed),
+                    Err(e) => tracing::error!("RETENTION: Failed to prune proof requests: {}", e),
+                }
+                if let (Some(artifacts), Some(period)) = (&self.artifacts, self.artifacts_period) {
+                    let keep = match self.referenced_artifacts() {
+                        Ok(keep) => keep,
+                        Err(e) => {
+                            tracing::error!("RETENTION: Failed to list the artifacts in use: {}", e);
+                            continue;
+                        }
+                    };
+                    match artifacts.prune(SystemTime::now() - period, &keep).await {
+                        Ok(0) => {}
+                        Ok(removed) => tracing::info!("RETENTION: Removed {} artifacts", removed),
+                        Err(e) => tracing::error!("RETENTION: Failed to prune artifacts: {}", e),
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prune_finished_proof_requests() {
+        let storage = Storage::default();
+        for (id, status, updated_at) in [
+            (1, FulfillmentStatus::Fulfilled, 100),
+            (2, FulfillmentStatus::Fulfilled, 950),
+            (3, FulfillmentStatus::Assigned, 100),
+        ] {
+            let request = ProofRequest {
+                request_id: vec![id; 32],
+                fulfillment_status: status as i32,
+                updated_at,
+                stdin_uri: format!("http://localhost:8082/artifacts/Stdin/stdin{}", id),
+                ..Default::default()
+            };
+            storage.put_proof_request(&request, &GetProofRequestStatusResponse::default()).unwrap();
+        }
+
+        let retention = Retention::new(storage.clone(), Some(Duration::from_secs(100)));
+        assert_eq!(retention.prune_proof_requests(1000).unwrap(), 1);
+        assert!(storage.get_proof_request(&[1; 32]).unwrap().is_none());
+        assert_eq!(storage.proof_requests().unwrap().len(), 2);
+
+        // The stdin of the pruned request can go, not the program or the other stdins
+        let program_uri = "s3://spn-artifacts/program/elf".to_string();
+        storage.put_program(&Program { vk_hash: vec![1; 32], program_uri, ..Default::default() }).unwrap();
+        let referenced = retention.referenced_artifacts().unwrap();
+        assert_eq!(referenced, HashSet::from(["stdin2".to_string(), "stdin3".to_string(), "elf".to_string()]));
+    }
+}
@@ -0,0 +1,206 @@
+use rpc_types::*;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::storage::{Storage, StorageError};
+
+/// Results returned per kind of record
+const RESULTS_PER_KIND: usize = 10;
+/// Prefix matches considered before ranking, short prefixes match most of the index
+const MAX_CANDIDATES: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SearchKind {
+    Request,
+    Program,
+    Requester,
+    Prover,
+}
+
+/// Records found by a query, newest first within each kind
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchResults {
+    pub requests: Vec<SearchResult>,
+    pub programs: Vec<SearchResult>,
+    pub requesters: Vec<SearchResult>,
+    pub provers: Vec<SearchResult>,
+}
+
+#[derive(Debug, Default)]
+struct Index {
+    /// Name and recency of every record
+    records: HashMap<(SearchKind, Vec<u8>), (Option<String>, u64)>,
+    /// Lowercase hex keys a record can be found by: its id, and its transaction hash for requests
+    hex_keys: BTreeSet<(String, SearchKind, Vec<u8>)>,
+}
+
+impl Index {
+    fn add(&mut self, kind: SearchKind, id: &[u8], name: Option<String>, at: u64) {
+        let record = self.records.entry((kind, id.to_vec())).or_insert((None, 0));
+        if name.is_some() {
+            record.0 = name;
+        }
+        record.1 = record.1.max(at);
+        self.hex_keys.insert((hex::encode(id), kind, id.to_vec()));
+    }
+
+    fn add_request(&mut self, request: &ProofRequest) {
+        self.add(SearchKind::Request, &request.request_id, None, request.created_at);
+        if !request.tx_hash.is_empty() {
+            self.hex_keys.insert((hex::encode(&request.tx_hash), SearchKind::Request, request.request_id.clone()));
+        }
+        self.add(SearchKind::Requester, &request.requester, None, request.created_at);
+        self.add(SearchKind::Program, &request.vk_hash, None, request.created_at);
+    }
+}
+
+/// In-process search over proof requests, programs, requesters and provers.
+///
+/// Records are found by a hex prefix of their id, `0x` optional, or of the
+/// transaction hash of requests, and programs and provers also by a case
+/// insensitive part of their name. New proof requests are indexed as they are
+/// written, and the whole index is rebuilt periodically to pick up programs,
+/// provers and removed requests.
+#[derive(Debug, Clone)]
+pub struct SearchIndex {
+    storage: Storage,
+    index: Arc<RwLock<Index>>,
+}
+
+impl SearchIndex {
+    pub fn new(storage: Storage) -> Self {
+        Self { storage, index: Arc::default() }
+    }
+
+    /// Index every stored record again
+    pub fn rebuild(&self) -> Result<(), StorageError> {
+        let mut index = Index::default();
+        for (request, _) in self.storage.proof_requests()? {
+            index.add_request(&request);
+        }
+        for program in self.storage.programs()? {
+            index.add(SearchKind::Program, &program.vk_hash, program.name, program.created_at);
+        }
+        for (stats, _) in self.storage.provers()? {
+            index.add(SearchKind::Prover, &stats.address, stats.name, stats.last_active_at);
+        }
+        *self.index.write().unwrap_or_else(|e| e.into_inner()) = index;
+        Ok(())
+    }
+
+    pub fn add_request(&self, request: &ProofRequest) {
+        self.index.write().unwrap_or_else(|e| e.into_inner()).add_request(request);
+    }
+
+    pub fn search(&self, query: &str) -> SearchResults {
+        let query = query.trim();
+        let hex_query = query.strip_prefix("0x").unwrap_or(query).to_lowercase();
+        let name_query = query.to_lowercase();
+        let index = self.index.read().unwrap_or_else(|e| e.into_inner());
+
+        let mut candidates: BTreeSet<(SearchKind, Vec<u8>)> = BTreeSet::new();
+        if !hex_query.is_empty() && hex_query.chars().all(|c| c.is_ascii_hexdigit()) {
+            candidates.extend(
+                index
+                    .hex_keys
+                    .range((hex_query.clone(), SearchKind::Request, Vec::new())..)
+                    .take_while(|(key, _, _)| key.starts_with(&hex_query))
+                    .take(MAX_CANDIDATES)
+                    .map(|(_, kind, id)| (*kind, id.clone())),
+            );
+        }
+        if !name_query.is_empty() {
+            candidates.extend(
+                index
+                    .records
+                    .iter()
+                    .filter(|(_, (name, _))| name.as_ref().is_some_and(|name| name.to_lowercase().contains(&name_query)))
+                    .map(|(key, _)| key.clone()),
+            );
+        }
+
+        let mut ranked: Vec<(u64, SearchKind, Vec<u8>, Option<String>)> = candidates
+            .into_iter()
+            .filter_map(|(kind, id)| {
+                let (name, at) = index.records.get(&(kind, id.clone()))?;
+                Some((*at, kind, id, name.clone()))
+            })
+            .collect();
+        ranked.sort_by_key(|(at, _, _, _)| std::cmp::Reverse(*at));
+
+        let mut results = SearchResults::default();
+        for (_, kind, id, name) in ranked {
+            let list = match kind {
+                SearchKind::Request => &mut results.requests,
+                SearchKind::Program => &mut results.programs,
+                SearchKind::Requester => &mut results.requesters,
+                SearchKind::Prover => &mut results.provers,
+            };
+            if list.len() < RESULTS_PER_KIND {
+                list.push(SearchResult { id, name });
+            }
+        }
+        results
+    }
+
+    /// Index proof requests as they are written and rebuild the whole index every `interval`
+    pub fn spawn(self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut updates = self.storage.subscribe_proof_requests();
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {
+                        if let Err(e) = self.rebuild() {
+                            tracing::error!("SEARCH: Failed to rebuild the search index: {}", e);
+                        }
+                    }
+                    update = updates.recv() => match update {
+                        Ok(request) => self.add_request(&request),
+                        // Missed requests are picked up by the next rebuild
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => return,
+                    },
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_by_hex_prefix_and_name() {
+        let storage = Storage::default();
+        let search = SearchIndex::new(storage.clone());
+        for (id, created_at) in [(0xab, 10), (0xac, 20)] {
+            let request = ProofRequest {
+                request_id: vec![id; 32],
+                tx_hash: vec![0x11; 32],
+                requester: vec![0xab; 20],
+                vk_hash: vec![0x22; 32],
+                created_at,
+                ..Default::default()
+            };
+            storage.put_proof_request(&request, &GetProofRequestStatusResponse::default()).unwrap();
+        }
+        storage
+            .put_program(&Program { vk_hash: vec![0x33; 32], name: Some("Fibonacci".to_string()), ..Default::default() })
+            .unwrap();
+        search.rebuild().unwrap();
+
+        // Newest first, the requester shares the prefix
+        let results = search.search("0xA");
+        let ids: Vec<u8> = results.requests.iter().map(|r| r.id[0]).collect();
+        assert_eq!(ids, vec![0xac, 0xab]);
+        assert_eq!(results.requesters.len(), 1);
+
+        // Transaction hashes lead to their request
+        assert_eq!(search.search("1111").requests.len(), 2);
+        assert_eq!(search.search("fibo").programs[0].name.as_deref(), Some("Fibonacci"));
+        assert_eq!(search.search(""), SearchResults::default());
+    }
+}
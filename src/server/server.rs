@@ -1,69 +1,215 @@
 use anyhow::Result;
 use rpc_types::*;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc;
-use tonic::transport::{Server, ServerTlsConfig, Identity};
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::{Certificate, Identity, Server, ServerTlsConfig};
 use tonic_reflection::server::{Builder as ReflBuilder};
 
+use crate::server::analytics::Analytics;
 use crate::server::prover_network_service::ProverNetworkServiceImpl;
 use crate::server::artifacts_service::ArtifactStoreServiceImpl;
+use crate::server::deadline::DeadlineReaper;
+use crate::server::config::{ArtifactBackendKind, CoordinatorConfig, StorageBackendKind};
+use crate::server::health::{GrpcState, Health};
 use crate::server::http_server::HttpServer;
+use crate::server::retention::Retention;
+use crate::server::telemetry::Telemetry;
+use crate::server::verifier_service::{ProofVerifier, RemoteVerifier, VerifierServiceImpl};
+use crate::storage::{ArtifactBackend, FsArtifactBackend, MemoryArtifactBackend, S3Presigner, SledBackend, Storage};
 
 const PROTOS: &[u8] = include_bytes!("../../crates/types/rpc/src/generated/descriptor.bin");
 
 /// Run both gRPC server and HTTP server concurrently
-pub async fn run_server(mut shutdown_rx: mpsc::Receiver<()>) -> Result<()> {
+pub async fn run_server(config: CoordinatorConfig, mut shutdown_rx: mpsc::Receiver<()>) -> Result<()> {
     tracing::info!("=== Starting gRPC Server and HTTP Server ===");
-    
-    let grpc_addr = "0.0.0.0:50051".parse()?;
-    let http_port = 8082;
-    let tls_activated = false; // Set to true if TLS is enabled
-    let prover_network_service = ProverNetworkServiceImpl::default();
-    let artifacts_service = ArtifactStoreServiceImpl::default();
-    
+
+    let grpc_addr = config.grpc_addr;
+    let http_addr = config.http_addr;
+
+    // Open the storage, with sled records from previous runs are reloaded from disk
+    let (storage, sled) = match config.storage.backend {
+        StorageBackendKind::Sled => {
+            let backend = Arc::new(SledBackend::open(&config.storage.path)?);
+            (Storage::new(backend.clone()), Some(backend))
+        }
+        StorageBackendKind::Memory => (Storage::default(), None),
+    };
+    if storage.ensure_proof_request_indexes()? {
+        tracing::info!("Proof request indexes built");
+    }
+    if Analytics::new(storage.clone()).backfill()? {
+        tracing::info!("Analytics backfilled from the stored requests and programs");
+    }
+    tracing::info!(
+        "Storage opened ({:?} at {}): {} proof requests, {} programs",
+        config.storage.backend,
+        config.storage.path.display(),
+        storage.proof_requests()?.len(),
+        storage.programs()?.len()
+    );
+
+    // Artifacts go through the object store when S3 is configured, otherwise through our HTTP server
+    let s3 = config.s3.clone().map(S3Presigner::new).transpose()?;
+    if let Some(s3) = &s3 {
+        tracing::info!("Artifacts stored in S3 bucket {}", s3.bucket());
+    }
+
+    // Served by get_proof_request_params and enforced on incoming requests
+    config.network.validate()?;
+    let params = config.network.clone();
+    tracing::info!("Domain separator: 0x{}, auctioneer: 0x{}", hex::encode(&params.domain), hex::encode(&params.auctioneer));
+
+    // Fulfilled proofs are checked by a remote verifier, nothing would check them otherwise
+    let endpoint = config.verifier_endpoint()?;
+    tracing::info!("Proofs verified by {}", endpoint);
+    let verifier: Arc<dyn ProofVerifier> = Arc::new(RemoteVerifier::new(endpoint)?);
+
+    // Prometheus metrics of both servers, served by the HTTP server on /metrics
+    let telemetry = Telemetry::new().with_storage(storage.clone());
+
+    let prover_network_service = ProverNetworkServiceImpl::new(storage.clone())
+        .with_s3(s3.clone())
+        .with_params(params)
+        .with_staking(config.staking.clone())
+        .with_public_url(&config.public_url)
+        .with_verifier(verifier.clone())
+        .with_telemetry(telemetry.clone())
+        .with_prover_liveness(config.provers.liveness_window())
+        .with_hosted_provers(config.hosted_provers()?);
+    let verifier_service = VerifierServiceImpl::new(verifier);
+    let artifact_allowlist = config.artifact_allowlist()?;
+    if config.artifacts.allow_all {
+        tracing::warn!("Any authenticated account can create artifacts");
+    } else if artifact_allowlist.is_empty() {
+        tracing::warn!("The artifact allowlist is empty, create_artifact is refused to every account");
+    }
+    let artifacts_service = ArtifactStoreServiceImpl::new(s3, artifact_allowlist)
+        .with_allow_all(config.artifacts.allow_all)
+        .with_public_url(&config.public_url);
+
+    // Settle auctions whose minimum auction period has elapsed
+    let auction_handle = prover_network_service.auctioneer().spawn(Duration::from_secs(1));
+
+    // Index new proof requests for search, and everything else every minute
+    let search_handle = prover_network_service.search().spawn(Duration::from_secs(60));
+
+    // Assign hosted requests once a prover is live
+    let assignment_handle = prover_network_service.assigner().spawn(Duration::from_secs(1));
+
+    // Expire requests past their deadline, slashing their prover, and re-open stalled auctions
+    let deadline_handle = DeadlineReaper::new(storage.clone())
+        .with_ledger(prover_network_service.ledger())
+        .with_staking(prover_network_service.staking())
+        .with_reassignment(config.deadlines.reassign_after())
+        .spawn(Duration::from_secs(1));
+
     // build a descriptor set at compile-time with prost-build / tonic-prost-build
     // then include it here (PROTOS is &[u8])
     let reflection = ReflBuilder::configure()
         .register_encoded_file_descriptor_set(PROTOS)
+        .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
         .build_v1()?;
 
+    // Artifacts are kept in files when configured, in memory otherwise
+    let fs_artifacts = match config.artifacts.backend {
+        ArtifactBackendKind::Fs => Some(FsArtifactBackend::new(&config.artifacts.path)),
+        ArtifactBackendKind::Memory => None,
+    };
+    let artifact_backend: Arc<dyn ArtifactBackend> = match &fs_artifacts {
+        Some(fs) => {
+            tracing::info!("Artifacts stored under {}", fs.root().display());
+            Arc::new(fs.clone())
+        }
+        None => Arc::new(MemoryArtifactBackend::new()),
+    };
+
+    // Serving state of the gRPC server and of the backends, behind grpc.health.v1 and the HTTP health checks
+    let (health_reporter, health_service) = tonic_health::server::health_reporter();
+    let health = Health::new(health_reporter)
+        .with_storage(storage.clone())
+        .with_artifacts(artifact_backend.clone());
+    let health_handle = health.clone().spawn(Duration::from_secs(10));
+
     // Create a real tonic gRPC server with both services
     let mut server = Server::builder();
-    if tls_activated == true {
+    if let (Some(cert), Some(key)) = (&config.tls.cert, &config.tls.key) {
         tracing::info!("Server TLS enabled");
-        let cert = tokio::fs::read("testing-cert/server.pem").await?;
-        let key  = tokio::fs::read("testing-cert/server.key").await?;
-        let identity = Identity::from_pem(cert, key);
-        server = server.tls_config(ServerTlsConfig::new().identity(identity))?;
+        let cert = tokio::fs::read(cert).await?;
+        let key  = tokio::fs::read(key).await?;
+        let mut tls = ServerTlsConfig::new().identity(Identity::from_pem(cert, key));
+        if let Some(ca) = &config.tls.ca {
+            tls = tls.client_ca_root(Certificate::from_pem(tokio::fs::read(ca).await?));
+        }
+        server = server.tls_config(tls)?;
     }
-    
-    // Start the gRPC server
-    let grpc_server = server.add_service(prover_network_server::ProverNetworkServer::new(prover_network_service))
+
+    // Start the gRPC server, bound here so it is known to serve once the listener is up
+    let grpc_listener = tokio::net::TcpListener::bind(grpc_addr).await?;
+    let shutdown_health = health.clone();
+    let grpc_server = server.layer(telemetry.grpc_layer())
+        .add_service(prover_network_server::ProverNetworkServer::new(prover_network_service))
         .add_service(artifact_store_server::ArtifactStoreServer::new(artifacts_service))
+        .add_service(verifier_server::VerifierServer::new(verifier_service))
+        .add_service(health_service)
         .add_service(reflection)
-        .serve_with_shutdown(grpc_addr, async {
+        .serve_with_incoming_shutdown(TcpIncoming::from(grpc_listener).with_nodelay(Some(true)), async move {
             let _ = shutdown_rx.recv().await;
             tracing::debug!("Shutdown signal received, gracefully stopping gRPC server...");
+            // Report not serving before the listener closes, so clients move elsewhere
+            shutdown_health.set_grpc_state(GrpcState::Stopped).await;
         });
+    health.set_grpc_state(GrpcState::Serving).await;
 
     // Start HTTP server in a separate task
+    let http_health = health.clone();
     let http_server_handle = tokio::spawn(async move {
-        let http_server = HttpServer::new(http_port);
+        let http_server = HttpServer::with_backend(http_addr.port(), artifact_backend)
+            .with_addr(http_addr)
+            .with_telemetry(telemetry)
+            .with_health(http_health);
         if let Err(e) = http_server.start().await {
             tracing::error!("HTTP server error: {}", e);
         }
     });
 
+    // Remove what is past its retention period
+    let mut retention = Retention::new(storage, config.retention.proof_requests());
+    if let Some(fs) = fs_artifacts {
+        retention = retention.with_artifacts(fs, config.retention.artifacts());
+    }
+    let retention_handle = retention.is_enabled().then(|| retention.spawn(Duration::from_secs(60)));
+
     tracing::info!("GRPC Server listening on {}", grpc_addr);
-    tracing::info!("HTTP Server listening on port {}", http_port);
+    tracing::info!("HTTP Server listening on {}, public URL {}", http_addr, config.public_url);
 
     // Run gRPC server and wait for it to complete
-    if let Err(e) = grpc_server.await {
+    let grpc_result = grpc_server.await;
+    health.set_grpc_state(GrpcState::Stopped).await;
+    if let Err(e) = &grpc_result {
         tracing::error!("gRPC server error: {}", e);
     }
 
-    // Abort the HTTP server task when gRPC server finishes
+    // Abort the background tasks when gRPC server finishes
     http_server_handle.abort();
-    
+    auction_handle.abort();
+    deadline_handle.abort();
+    assignment_handle.abort();
+    search_handle.abort();
+    health_handle.abort();
+    if let Some(handle) = retention_handle {
+        handle.abort();
+    }
+
+    if let Some(sled) = sled {
+        if let Err(e) = sled.flush() {
+            tracing::error!("Failed to flush storage: {}", e);
+        }
+    }
+
     tracing::info!("Servers shutdown complete");
+    // A failed gRPC server fails the process, so it gets restarted
+    grpc_result?;
     Ok(())
-}
\ No newline at end of file
+}
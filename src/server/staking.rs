@@ -0,0 +1,210 @@
+use anyhow::Result;
+use ethers_core::utils::keccak256;
+use rpc_types::*;
+use serde::Deserialize;
+use tonic::Status;
+
+use crate::server::auction::parse_amount;
+use crate::server::ledger::{signed, Ledger};
+use crate::server::params::hex_bytes;
+use crate::storage::{ProofRequestIndex, Storage};
+
+/// Parameters of prover staking
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StakingParams {
+    /// Credits transferred to this address are staked by their sender
+    #[serde(deserialize_with = "hex_bytes")]
+    pub vault: Vec<u8>,
+    /// Stake a prover needs to bid and be assigned requests
+    pub min_stake: String,
+    /// Share of its stake a prover loses for each missed deadline or rejected proof, in basis points
+    pub slash_bips: u64,
+}
+
+impl Default for StakingParams {
+    fn default() -> Self {
+        Self { vault: default_vault(), min_stake: "0".to_string(), slash_bips: 1000 }
+    }
+}
+
+impl StakingParams {
+    /// Override the parameters with the `SPN_STAKING_VAULT`, `SPN_MIN_STAKE` and `SPN_SLASH_BIPS` environment variables
+    pub fn apply_env(&mut self) -> Result<()> {
+        if let Ok(v) = std::env::var("SPN_STAKING_VAULT") {
+            self.vault = hex::decode(v.trim().trim_start_matches("0x")).map_err(|e| anyhow::anyhow!("Invalid SPN_STAKING_VAULT: {}", e))?;
+        }
+        if let Ok(v) = std::env::var("SPN_MIN_STAKE") {
+            parse_amount(&v).ok_or_else(|| anyhow::anyhow!("Invalid SPN_MIN_STAKE: {}", v))?;
+            self.min_stake = v.trim().to_string();
+        }
+        if let Ok(v) = std::env::var("SPN_SLASH_BIPS") {
+            self.slash_bips = v.trim().parse().map_err(|_| anyhow::anyhow!("Invalid SPN_SLASH_BIPS: {}", v))?;
+        }
+        if self.slash_bips > 10_000 {
+            anyhow::bail!("Slash bips {} is above 10000", self.slash_bips);
+        }
+        Ok(())
+    }
+}
+
+/// Address no key controls, the last 20 bytes of `keccak256("spn_staking_vault")`
+fn default_vault() -> Vec<u8> {
+    keccak256(b"spn_staking_vault")[12..].to_vec()
+}
+
+/// Local stand-in for the staking contract.
+///
+/// Provers stake by transferring credits to the vault, which keeps them in
+/// the ledger, and unstake with a `withdraw` from the vault. Provers below the minimum stake can't bid nor be assigned
+/// requests, and lose part of their stake to the treasury when they miss the
+/// deadline of an assigned request or submit a proof that fails verification.
+#[derive(Debug, Clone)]
+pub struct Staking {
+    storage: Storage,
+    ledger: Ledger,
+    params: StakingParams,
+}
+
+impl Staking {
+    pub fn new(storage: Storage, ledger: Ledger) -> Self {
+        Self { storage, ledger, params: StakingParams::default() }
+    }
+
+    pub fn with_params(mut self, params: StakingParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// Ledger moving the slashed credits
+    pub fn with_ledger(mut self, ledger: Ledger) -> Self {
+        self.ledger = ledger;
+        self
+    }
+
+    pub fn is_vault(&self, address: &[u8]) -> bool {
+        address == self.params.vault.as_slice()
+    }
+
+    pub fn stake_of(&self, prover: &[u8]) -> Result<i128, Status> {
+        Ok(self.storage.get_stake(prover)?)
+    }
+
+    pub fn has_min_stake(&self, prover: &[u8]) -> Result<bool, Status> {
+        let min_stake = parse_amount(&self.params.min_stake).unwrap_or(0);
+        Ok(min_stake == 0 || self.stake_of(prover)? >= min_stake as i128)
+    }
+
+    pub fn require_min_stake(&self, prover: &[u8]) -> Result<(), Status> {
+        if !self.has_min_stake(prover)? {
+            return Err(Status::failed_precondition(format!(
+                "Prover {} has less than the minimum stake {}",
+                hex::encode(prover),
+                self.params.min_stake
+            )));
+        }
+        Ok(())
+    }
+
+    /// Record the stake of credits already transferred to the vault
+    pub fn stake(&self, prover: &[u8], amount: u128, tx_hash: &[u8], now: u64) -> Result<(), Status> {
+        self.post(prover, StakeBalanceOperation::Stake, signed(amount)?, tx_hash, now)?;
+        tracing::info!("STAKING: {} staked {}", hex::encode(prover), amount);
+        Ok(())
+    }
+
+    /// Stake `amount` of a prover's credits, transferring them to the vault and paying the fee
+    /// amount to the fee recipient in the same write as the stake
+    pub fn transfer_stake(&self, prover: &[u8], amount: u128, fee: (&[u8], u128), tx_hash: &[u8], now: u64) -> Result<(), Status> {
+        let log = stake_log(prover, StakeBalanceOperation::Stake, signed(amount)?, tx_hash, now);
+        self.ledger.lock_stake(prover, &self.params.vault, amount, fee, &[log], tx_hash, now)?;
+        tracing::info!("STAKING: {} staked {}", hex::encode(prover), amount);
+        Ok(())
+    }
+
+    /// Give `amount` of its stake back to a prover, moving the credits from the vault to it and
+    /// the fee amount from it to the fee recipient. Provers can't unstake while assigned requests,
+    /// which they could be slashed for.
+    pub fn unstake(&self, prover: &[u8], amount: u128, fee: (&[u8], u128), tx_hash: &[u8], now: u64) -> Result<(), Status> {
+        if self.storage.count_proof_requests(&ProofRequestIndex::AssignedTo(prover.to_vec()))? > 0 {
+            return Err(Status::failed_precondition("Prover has assigned requests"));
+        }
+        let log = stake_log(prover, StakeBalanceOperation::Unstake, -signed(amount)?, tx_hash, now);
+        self.ledger.release_stake(&self.params.vault, prover, amount, fee, &[log], tx_hash, now)?;
+        tracing::info!("STAKING: {} unstaked {}", hex::encode(prover), amount);
+        Ok(())
+    }
+
+    /// Slash a prover for `request_id`, moving the slashed credits from the vault to the treasury.
+    /// Returns the slashed amount.
+    pub fn slash(&self, prover: &[u8], request_id: &[u8], now: u64) -> Result<u128, Status> {
+        let stake = self.stake_of(prover)?.max(0) as u128;
+        let amount = stake * self.params.slash_bips as u128 / 10_000;
+        if amount == 0 {
+            return Ok(0);
+        }
+        let log = stake_log(prover, StakeBalanceOperation::Slash, -signed(amount)?, request_id, now);
+        self.ledger.pay_treasury(&self.params.vault, amount, &[log], request_id, now)?;
+        tracing::warn!("STAKING: Slashed {} of {} for request {}", amount, hex::encode(prover), hex::encode(request_id));
+        Ok(amount)
+    }
+
+    fn post(&self, prover: &[u8], operation: StakeBalanceOperation, amount: i128, tx_hash: &[u8], now: u64) -> Result<(), Status> {
+        if !self.storage.apply_stake_logs(&[stake_log(prover, operation, amount, tx_hash, now)])? {
+            return Err(Status::failed_precondition("Insufficient stake"));
+        }
+        Ok(())
+    }
+}
+
+fn stake_log(prover: &[u8], operation: StakeBalanceOperation, amount: i128, tx_hash: &[u8], now: u64) -> StakeBalanceLog {
+    StakeBalanceLog {
+        address: prover.to_vec(),
+        operation: operation as i32,
+        amount: amount.to_string(),
+        tx_hash: tx_hash.to_vec(),
+        created_at: now,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stake_gates_and_slash() {
+        let storage = Storage::default();
+        let ledger = Ledger::new(storage.clone()).with_treasury(vec![3; 20]);
+        let params = StakingParams { min_stake: "100".to_string(), ..Default::default() };
+        let staking = Staking::new(storage.clone(), ledger.clone()).with_params(params.clone());
+        let prover = vec![1; 20];
+        assert!(staking.require_min_stake(&prover).is_err());
+
+        ledger.add_credit(&prover, 500, &[0; 32], 1).unwrap();
+        staking.transfer_stake(&prover, 200, (&[3; 20], 0), &[0; 32], 2).unwrap();
+        assert!(staking.require_min_stake(&prover).is_ok());
+
+        // 10% of the stake goes to the treasury
+        assert_eq!(staking.slash(&prover, &[9; 32], 3).unwrap(), 20);
+        assert_eq!(staking.stake_of(&prover).unwrap(), 180);
+        assert_eq!(ledger.balance(&params.vault).unwrap(), 180);
+        assert_eq!(ledger.balance(&[3; 20]).unwrap(), 20);
+
+        // Nothing is unstaked past the stake, then the rest goes back to the prover
+        assert_eq!(staking.unstake(&prover, 181, (&[3; 20], 0), &[0; 32], 4).unwrap_err().code(), tonic::Code::FailedPrecondition);
+        assert_eq!(ledger.balance(&params.vault).unwrap(), 180);
+        staking.unstake(&prover, 180, (&[3; 20], 5), &[0; 32], 4).unwrap();
+        assert_eq!(staking.stake_of(&prover).unwrap(), 0);
+        assert_eq!(ledger.balance(&prover).unwrap(), 300 + 180 - 5);
+        assert_eq!(ledger.balance(&params.vault).unwrap(), 0);
+
+        // Amounts that don't fit a stake are refused
+        let too_large = staking.transfer_stake(&prover, u128::MAX, (&[3; 20], 0), &[0; 32], 5);
+        assert_eq!(too_large.unwrap_err().code(), tonic::Code::InvalidArgument);
+        assert_eq!(staking.unstake(&prover, u128::MAX, (&[3; 20], 0), &[0; 32], 5).unwrap_err().code(), tonic::Code::InvalidArgument);
+
+        let operations: Vec<i32> = storage.stake_logs().unwrap().iter().map(|log| log.operation).collect();
+        assert!(operations.contains(&(StakeBalanceOperation::Slash as i32)));
+        assert!(operations.contains(&(StakeBalanceOperation::Unstake as i32)));
+    }
+}
@@ -0,0 +1,315 @@
+use prometheus::{
+    exponential_buckets, Encoder, Histogram, HistogramOpts, HistogramTimer, HistogramVec, IntCounterVec, IntGauge,
+    IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use rpc_types::*;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use crate::storage::Storage;
+
+/// Metrics exported by the coordinator in the Prometheus text format.
+///
+/// gRPC calls are counted by [`GrpcMetricsLayer`] and HTTP requests by the
+/// HTTP server, the services record what happens to proof requests and
+/// artifacts. The number of proof requests per fulfillment status is read
+/// from the counts the storage keeps when the metrics are gathered. gRPC
+/// calls are labelled by method only for the services the coordinator serves.
+#[derive(Debug, Clone)]
+pub struct Telemetry {
+    registry: Registry,
+    /// Read for the proof request counts, when set
+    storage: Option<Storage>,
+    grpc_requests: IntCounterVec,
+    grpc_duration: HistogramVec,
+    http_requests: IntCounterVec,
+    http_duration: HistogramVec,
+    proof_requests: IntGaugeVec,
+    fulfillment_duration: Histogram,
+    artifact_bytes: IntCounterVec,
+    artifact_upload_duration: HistogramVec,
+    artifact_download_duration: HistogramVec,
+    subscriptions: IntGauge,
+}
+
+impl Default for Telemetry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Telemetry {
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some("spn".to_string()), None).expect("valid metrics prefix");
+        let register = |metric: Box<dyn prometheus::core::Collector>| {
+            registry.register(metric).expect("metrics are registered once");
+        };
+
+        let grpc_requests = IntCounterVec::new(Opts::new("grpc_requests_total", "gRPC calls by method and status code"), &["method", "code"]).unwrap();
+        let grpc_duration = HistogramVec::new(HistogramOpts::new("grpc_request_duration_seconds", "Time to answer gRPC calls"), &["method"]).unwrap();
+        let http_requests = IntCounterVec::new(Opts::new("http_requests_total", "HTTP requests by route and status code"), &["route", "status"]).unwrap();
+        let http_duration = HistogramVec::new(HistogramOpts::new("http_request_duration_seconds", "Time to answer HTTP requests"), &["route"]).unwrap();
+        let proof_requests = IntGaugeVec::new(Opts::new("proof_requests", "Stored proof requests by fulfillment status"), &["status"]).unwrap();
+        // From a second to about four and a half hours
+        let fulfillment_duration = Histogram::with_opts(
+            HistogramOpts::new("proof_request_fulfillment_seconds", "Time from proof request to fulfillment")
+                .buckets(exponential_buckets(1.0, 2.0, 15).unwrap()),
+        )
+        .unwrap();
+        let artifact_bytes = IntCounterVec::new(Opts::new("artifact_bytes_total", "Artifact bytes stored by artifact type"), &["artifact_type"]).unwrap();
+        // From 5ms to about 40s
+        let transfer_buckets = exponential_buckets(0.005, 2.0, 14).unwrap();
+        let artifact_upload_duration = HistogramVec::new(
+            HistogramOpts::new("artifact_upload_seconds", "Time to upload artifacts").buckets(transfer_buckets.clone()),
+            &["artifact_type"],
+        )
+        .unwrap();
+        let artifact_download_duration = HistogramVec::new(
+            HistogramOpts::new("artifact_download_seconds", "Time to download artifacts").buckets(transfer_buckets),
+            &["artifact_type"],
+        )
+        .unwrap();
+        let subscriptions = IntGauge::new("active_subscriptions", "Open proof request subscription streams").unwrap();
+
+        register(Box::new(grpc_requests.clone()));
+        register(Box::new(grpc_duration.clone()));
+        register(Box::new(http_requests.clone()));
+        register(Box::new(http_duration.clone()));
+        register(Box::new(proof_requests.clone()));
+        register(Box::new(fulfillment_duration.clone()));
+        register(Box::new(artifact_bytes.clone()));
+        register(Box::new(artifact_upload_duration.clone()));
+        register(Box::new(artifact_download_duration.clone()));
+        register(Box::new(subscriptions.clone()));
+
+        Self {
+            registry,
+            storage: None,
+            grpc_requests,
+            grpc_duration,
+            http_requests,
+            http_duration,
+            proof_requests,
+            fulfillment_duration,
+            artifact_bytes,
+            artifact_upload_duration,
+            artifact_download_duration,
+            subscriptions,
+        }
+    }
+
+    /// Export the number of proof requests of this storage per fulfillment status
+    pub fn with_storage(mut self, storage: Storage) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    pub fn record_grpc(&self, method: &str, code: tonic::Code, elapsed: Duration) {
+        self.grpc_requests.with_label_values(&[method, &format!("{:?}", code)]).inc();
+        self.grpc_duration.with_label_values(&[method]).observe(elapsed.as_secs_f64());
+    }
+
+    pub fn record_http(&self, route: &str, status: u16, elapsed: Duration) {
+        self.http_requests.with_label_values(&[route, &status.to_string()]).inc();
+        self.http_duration.with_label_values(&[route]).observe(elapsed.as_secs_f64());
+    }
+
+    /// Observe how long a fulfilled request waited for its proof
+    pub fn record_fulfillment(&self, request: &ProofRequest) {
+        if let Some(fulfilled_at) = request.fulfilled_at {
+            self.fulfillment_duration.observe(fulfilled_at.saturating_sub(request.created_at) as f64);
+        }
+    }
+
+    pub fn record_artifact_upload(&self, artifact_type: ArtifactType, bytes: u64, elapsed: Duration) {
+        let label = artifact_type_label(artifact_type);
+        self.artifact_bytes.with_label_values(&[label]).inc_by(bytes);
+        self.artifact_upload_duration.with_label_values(&[label]).observe(elapsed.as_secs_f64());
+    }
+
+    /// Observes the download time when dropped, once the artifact is sent or the download given up
+    pub fn time_artifact_download(&self, artifact_type: ArtifactType) -> HistogramTimer {
+        self.artifact_download_duration.with_label_values(&[artifact_type_label(artifact_type)]).start_timer()
+    }
+
+    /// Counts a subscription stream as open until the returned guard is dropped
+    pub fn track_subscription(&self) -> SubscriptionGuard {
+        self.subscriptions.inc();
+        SubscriptionGuard(self.subscriptions.clone())
+    }
+
+    /// Every metric in the Prometheus text format
+    pub fn render(&self) -> anyhow::Result<String> {
+        if let Some(storage) = &self.storage {
+            for status in [
+                FulfillmentStatus::Requested,
+                FulfillmentStatus::Assigned,
+                FulfillmentStatus::Fulfilled,
+                FulfillmentStatus::Unfulfillable,
+            ] {
+                let count = storage.count_proof_requests_with_status(status)?;
+                self.proof_requests.with_label_values(&[&status.as_str_name().to_lowercase()]).set(count as i64);
+            }
+        }
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+
+    /// Layer counting the gRPC calls of a tonic server
+    pub fn grpc_layer(&self) -> GrpcMetricsLayer {
+        GrpcMetricsLayer { telemetry: self.clone() }
+    }
+}
+
+fn artifact_type_label(artifact_type: ArtifactType) -> &'static str {
+    match artifact_type {
+        ArtifactType::UnspecifiedArtifactType => "unspecified",
+        ArtifactType::Program => "program",
+        ArtifactType::Stdin => "stdin",
+        ArtifactType::Proof => "proof",
+        ArtifactType::Transaction => "transaction",
+    }
+}
+
+/// Open subscription stream, see [`Telemetry::track_subscription`]
+#[derive(Debug)]
+pub struct SubscriptionGuard(IntGauge);
+
+impl Drop for SubscriptionGuard {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GrpcMetricsLayer {
+    telemetry: Telemetry,
+}
+
+impl<S> tower::Layer<S> for GrpcMetricsLayer {
+    type Service = GrpcMetrics<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcMetrics { inner, telemetry: self.telemetry.clone() }
+    }
+}
+
+/// Records the method, status code and duration of every gRPC call to `inner`
+#[derive(Debug, Clone)]
+pub struct GrpcMetrics<S> {
+    inner: S,
+    telemetry: Telemetry,
+}
+
+type BoxFuture<T, E> = Pin<Box<dyn Future<Output = Result<T, E>> + Send>>;
+
+impl<S, B, ResBody> tower::Service<http::Request<B>> for GrpcMetrics<S>
+where
+    S: tower::Service<http::Request<B>, Response = http::Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    B: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<S::Response, S::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        // The service polled ready is the one that must be called
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let telemetry = self.telemetry.clone();
+        let method = request.uri().path().to_string();
+        let started = Instant::now();
+        Box::pin(async move {
+            let response = inner.call(request).await;
+            // Failed calls answered at once carry their status in the headers, the others end with it in trailers
+            let code = match &response {
+                Ok(response) => response
+                    .headers()
+                    .get("grpc-status")
+                    .and_then(|code| code.to_str().ok())
+                    .and_then(|code| code.parse::<i32>().ok())
+                    .map(tonic::Code::from)
+                    .unwrap_or(tonic::Code::Ok),
+                Err(_) => tonic::Code::Unknown,
+            };
+            telemetry.record_grpc(grpc_method_label(&method, code), code, started.elapsed());
+            response
+        })
+    }
+}
+
+/// Services whose methods label the gRPC metrics
+const GRPC_SERVICES: [&str; 5] = [
+    "network.ProverNetwork",
+    "artifact.ArtifactStore",
+    "verifier.Verifier",
+    "grpc.health.v1.Health",
+    "grpc.reflection.v1.ServerReflection",
+];
+
+/// Label of a gRPC call: its path for the methods served, "unknown" otherwise, so clients can't
+/// grow the metrics with made up paths
+fn grpc_method_label(path: &str, code: tonic::Code) -> &str {
+    let service = path.strip_prefix('/').and_then(|path| path.split_once('/')).map(|(service, _)| service);
+    if code == tonic::Code::Unimplemented || !service.is_some_and(|service| GRPC_SERVICES.contains(&service)) {
+        return "unknown";
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let storage = Storage::default();
+        let request = ProofRequest {
+            request_id: vec![1; 32],
+            fulfillment_status: FulfillmentStatus::Assigned as i32,
+            created_at: 10,
+            fulfilled_at: Some(70),
+            ..Default::default()
+        };
+        storage.put_proof_request(&request, &GetProofRequestStatusResponse::default()).unwrap();
+        let telemetry = Telemetry::new().with_storage(storage.clone());
+
+        telemetry.record_grpc("/network.ProverNetwork/RequestProof", tonic::Code::InvalidArgument, Duration::from_millis(3));
+        telemetry.record_fulfillment(&request);
+        telemetry.record_artifact_upload(ArtifactType::Stdin, 42, Duration::from_millis(5));
+        let subscription = telemetry.track_subscription();
+
+        let text = telemetry.render().unwrap();
+        assert!(text.contains(r#"spn_grpc_requests_total{code="InvalidArgument",method="/network.ProverNetwork/RequestProof"} 1"#));
+        assert!(text.contains(r#"spn_proof_requests{status="assigned"} 1"#));
+        assert!(text.contains(r#"spn_proof_requests{status="requested"} 0"#));
+        assert!(text.contains("spn_proof_request_fulfillment_seconds_sum 60"));
+        assert!(text.contains(r#"spn_artifact_bytes_total{artifact_type="stdin"} 42"#));
+        assert!(text.contains("spn_active_subscriptions 1"));
+
+        drop(subscription);
+        assert!(telemetry.render().unwrap().contains("spn_active_subscriptions 0"));
+
+        // Status counts follow the requests written after the first scrape
+        storage.put_proof_request(&ProofRequest { fulfillment_status: FulfillmentStatus::Fulfilled as i32, ..request.clone() }, &Default::default()).unwrap();
+        let text = telemetry.render().unwrap();
+        assert!(text.contains(r#"spn_proof_requests{status="assigned"} 0"#));
+        assert!(text.contains(r#"spn_proof_requests{status="fulfilled"} 1"#));
+    }
+
+    #[test]
+    fn test_grpc_method_label() {
+        assert_eq!(grpc_method_label("/network.ProverNetwork/RequestProof", tonic::Code::Ok), "/network.ProverNetwork/RequestProof");
+        assert_eq!(grpc_method_label("/network.ProverNetwork/Made", tonic::Code::Unimplemented), "unknown");
+        assert_eq!(grpc_method_label("/made.Up/Method", tonic::Code::Unimplemented), "unknown");
+        assert_eq!(grpc_method_label("/favicon.ico", tonic::Code::Ok), "unknown");
+    }
+}
@@ -0,0 +1,138 @@
+use anyhow::Result;
+use rpc_types::*;
+use std::sync::Arc;
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Request, Response, Status};
+
+/// Checks proofs submitted by fulfillers
+#[tonic::async_trait]
+pub trait ProofVerifier: Send + Sync + std::fmt::Debug {
+    /// Whether the proof is valid for the verification key, and for the public
+    /// values hash when one is given
+    async fn verify(&self, request: VerifyProofRequest) -> Result<bool>;
+}
+
+/// In-process verifier. It only rejects empty proofs and fails on the others,
+/// which only a [`RemoteVerifier`] can tell valid. Requests without a verification
+/// key, mode or version fail too: that is not the prover's fault.
+#[derive(Debug, Clone, Default)]
+pub struct LocalVerifier;
+
+#[tonic::async_trait]
+impl ProofVerifier for LocalVerifier {
+    async fn verify(&self, request: VerifyProofRequest) -> Result<bool> {
+        let mode_known = matches!(
+            ProofMode::try_from(request.mode),
+            Ok(ProofMode::Core | ProofMode::Compressed | ProofMode::Plonk | ProofMode::Groth16)
+        );
+        if request.vkey.is_empty() || !mode_known || request.version.is_empty() {
+            anyhow::bail!("The proof request has no verification key, mode or version to verify against")
+        }
+        if request.proof.is_empty() {
+            return Ok(false);
+        }
+        anyhow::bail!("No verifier endpoint is configured to verify the proof")
+    }
+}
+
+/// Forwards verification to a `verifier.Verifier` gRPC server, e.g. a sidecar built with the SP1 SDK
+#[derive(Debug, Clone)]
+pub struct RemoteVerifier {
+    client: verifier_client::VerifierClient<Channel>,
+}
+
+impl RemoteVerifier {
+    /// The connection is only opened on the first verification
+    pub fn new(endpoint: &str) -> Result<Self> {
+        let channel = Endpoint::from_shared(endpoint.to_string())?.connect_lazy();
+        Ok(Self { client: verifier_client::VerifierClient::new(channel) })
+    }
+}
+
+#[tonic::async_trait]
+impl ProofVerifier for RemoteVerifier {
+    async fn verify(&self, request: VerifyProofRequest) -> Result<bool> {
+        let response = self.client.clone().verify_proof(Request::new(request)).await?;
+        Ok(response.into_inner().valid)
+    }
+}
+
+/// Verify a proof, telling apart proofs of the wrong program from proofs with
+/// the wrong public values: the latter still verify without the public values hash.
+pub async fn check_proof(verifier: &dyn ProofVerifier, mut request: VerifyProofRequest) -> Result<Option<ProofRequestError>> {
+    if verifier.verify(request.clone()).await? {
+        return Ok(None);
+    }
+    if request.public_values_hash.take().is_some() && verifier.verify(request).await? {
+        return Ok(Some(ProofRequestError::PublicValuesMismatch));
+    }
+    Ok(Some(ProofRequestError::VerificationKeyMismatch))
+}
+
+/// gRPC service implementation for Verifier
+#[derive(Debug)]
+pub struct VerifierServiceImpl {
+    verifier: Arc<dyn ProofVerifier>,
+}
+
+impl VerifierServiceImpl {
+    pub fn new(verifier: Arc<dyn ProofVerifier>) -> Self {
+        Self { verifier }
+    }
+}
+
+#[tonic::async_trait]
+impl verifier_server::Verifier for VerifierServiceImpl {
+    async fn verify_proof(&self, request: Request<VerifyProofRequest>) -> Result<Response<VerifyProofResponse>, Status> {
+        let valid = self
+            .verifier
+            .verify(request.into_inner())
+            .await
+            .map_err(|e| Status::unavailable(format!("Verification failed: {}", e)))?;
+        Ok(Response::new(VerifyProofResponse { valid }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Accepts a single proof, public values hash included
+    #[derive(Debug)]
+    struct FixedVerifier;
+
+    #[tonic::async_trait]
+    impl ProofVerifier for FixedVerifier {
+        async fn verify(&self, request: VerifyProofRequest) -> Result<bool> {
+            Ok(request.proof == b"proof" && request.public_values_hash.as_ref().is_none_or(|h| h == &[1; 32]))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_proof_reports_the_mismatch() {
+        let request = |proof: &[u8], hash: u8| VerifyProofRequest {
+            proof: proof.to_vec(),
+            public_values_hash: Some(vec![hash; 32]),
+            ..Default::default()
+        };
+        assert_eq!(check_proof(&FixedVerifier, request(b"proof", 1)).await.unwrap(), None);
+        assert_eq!(check_proof(&FixedVerifier, request(b"proof", 2)).await.unwrap(), Some(ProofRequestError::PublicValuesMismatch));
+        assert_eq!(check_proof(&FixedVerifier, request(b"garbage", 1)).await.unwrap(), Some(ProofRequestError::VerificationKeyMismatch));
+    }
+
+    #[tokio::test]
+    async fn test_local_verifier_fails_closed() {
+        let request = VerifyProofRequest {
+            proof: b"proof".to_vec(),
+            vkey: vec![1; 32],
+            mode: ProofMode::Groth16 as i32,
+            version: "sp1-v5.0.0".to_string(),
+            public_values_hash: None,
+        };
+        assert!(LocalVerifier.verify(request.clone()).await.is_err());
+        assert!(!LocalVerifier.verify(VerifyProofRequest { proof: Vec::new(), ..request.clone() }).await.unwrap());
+        // Malformed requests are not the prover's fault
+        assert!(LocalVerifier.verify(VerifyProofRequest { proof: Vec::new(), version: String::new(), ..request.clone() }).await.is_err());
+        assert!(LocalVerifier.verify(VerifyProofRequest { proof: Vec::new(), mode: 0, ..request }).await.is_err());
+    }
+}
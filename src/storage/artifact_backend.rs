@@ -0,0 +1,71 @@
+use anyhow::Result;
+use bytes::Bytes;
+use rpc_types::ArtifactType;
+use std::collections::HashMap;
+use std::pin::Pin;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::sync::RwLock;
+
+/// Reader used to stream artifact bodies in and out of a backend
+pub type ArtifactReader = Pin<Box<dyn AsyncRead + Send>>;
+
+/// Storage for the artifacts uploaded through the HTTP server
+/// (program ELFs, stdins and proofs).
+#[tonic::async_trait]
+pub trait ArtifactBackend: Send + Sync + std::fmt::Debug {
+    /// Store the artifact read from `body`, returning the number of bytes written.
+    /// A partially written artifact must never be visible to `get`.
+    async fn put(&self, artifact_type: ArtifactType, artifact_id: &str, body: ArtifactReader) -> Result<u64>;
+
+    /// Open the artifact for reading, returning its size and a reader over its content
+    async fn get(&self, artifact_type: ArtifactType, artifact_id: &str) -> Result<Option<(u64, ArtifactReader)>>;
+
+    /// Check the backend can currently store artifacts
+    async fn check(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Parse the artifact type used in artifact URLs (e.g. `Program` or `PROGRAM`)
+pub fn parse_artifact_type(value: &str) -> Option<ArtifactType> {
+    ArtifactType::from_str_name(&value.to_uppercase())
+        .filter(|t| *t != ArtifactType::UnspecifiedArtifactType)
+}
+
+/// Artifact ids end up in file names, so only accept plain identifiers
+pub fn is_valid_artifact_id(artifact_id: &str) -> bool {
+    !artifact_id.is_empty()
+        && artifact_id.len() <= 128
+        && artifact_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Volatile artifact backend keeping every artifact in memory
+#[derive(Debug, Default)]
+pub struct MemoryArtifactBackend {
+    artifacts: RwLock<HashMap<(ArtifactType, String), Bytes>>,
+}
+
+impl MemoryArtifactBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[tonic::async_trait]
+impl ArtifactBackend for MemoryArtifactBackend {
+    async fn put(&self, artifact_type: ArtifactType, artifact_id: &str, mut body: ArtifactReader) -> Result<u64> {
+        let mut buf = Vec::new();
+        body.read_to_end(&mut buf).await?;
+        let size = buf.len() as u64;
+        self.artifacts.write().await.insert((artifact_type, artifact_id.to_string()), Bytes::from(buf));
+        Ok(size)
+    }
+
+    async fn get(&self, artifact_type: ArtifactType, artifact_id: &str) -> Result<Option<(u64, ArtifactReader)>> {
+        let artifacts = self.artifacts.read().await;
+        Ok(artifacts.get(&(artifact_type, artifact_id.to_string())).map(|data| {
+            let reader: ArtifactReader = Box::pin(std::io::Cursor::new(data.clone()));
+            (data.len() as u64, reader)
+        }))
+    }
+}
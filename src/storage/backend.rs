@@ -0,0 +1,68 @@
+use anyhow::Result;
+
+/// Raw key/value backend used by [`crate::storage::Storage`].
+///
+/// Records are grouped in named trees and keys are kept in lexicographic order,
+/// so prefix scans can be used to build secondary indexes on top of it.
+pub trait StorageBackend: Send + Sync + std::fmt::Debug {
+    /// Get the value stored under `key` in `tree`
+    fn get(&self, tree: &str, key: &[u8]) -> Result<Option<Vec<u8>>>;
+
+    /// Insert or overwrite the value stored under `key` in `tree`
+    fn insert(&self, tree: &str, key: &[u8], value: Vec<u8>) -> Result<()>;
+
+    /// Remove the value stored under `key` in `tree`, if any
+    fn remove(&self, tree: &str, key: &[u8]) -> Result<()>;
+
+    /// Return every entry of `tree` whose key starts with `prefix`, in key order
+    fn scan_prefix(&self, tree: &str, prefix: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>>;
+
+    /// Return at most `limit` entries of `tree` whose key starts with `prefix` and is at least `start`, in key order
+    fn scan_range(&self, tree: &str, prefix: &[u8], start: &[u8], limit: usize) -> Result<Vec<(Vec<u8>, Vec<u8>)>>;
+
+    /// Make every write of `batch`, in order. Either all of them are stored or none
+    /// is, even if the process stops halfway.
+    fn apply(&self, batch: WriteBatch) -> Result<()>;
+}
+
+/// `(tree, key, value)` of a write, removals have no value
+pub type Write = (&'static str, Vec<u8>, Option<Vec<u8>>);
+
+/// Inserts and removals across trees, made together by [`StorageBackend::apply`]
+#[derive(Debug, Clone, Default)]
+pub struct WriteBatch {
+    writes: Vec<Write>,
+}
+
+impl WriteBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insert or overwrite the value stored under `key` in `tree`
+    pub fn insert(&mut self, tree: &'static str, key: &[u8], value: Vec<u8>) {
+        self.writes.push((tree, key.to_vec(), Some(value)));
+    }
+
+    /// Remove the value stored under `key` in `tree`, if any
+    pub fn remove(&mut self, tree: &'static str, key: &[u8]) {
+        self.writes.push((tree, key.to_vec(), None));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+
+    /// Trees written by the batch, each once
+    pub fn trees(&self) -> Vec<&'static str> {
+        let mut trees: Vec<&'static str> = self.writes.iter().map(|(tree, _, _)| *tree).collect();
+        trees.sort_unstable();
+        trees.dedup();
+        trees
+    }
+
+    /// Writes in the order they were added
+    pub fn writes(&self) -> &[Write] {
+        &self.writes
+    }
+}
@@ -0,0 +1,140 @@
+use anyhow::Result;
+use rand::random;
+use rpc_types::ArtifactType;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tokio::io::AsyncWriteExt;
+
+use crate::storage::artifact_backend::{ArtifactBackend, ArtifactReader};
+
+/// Artifact backend storing every artifact as a file under `<root>/<ArtifactType>/<artifact_id>`.
+///
+/// Bodies are streamed to a temporary file in the same directory and renamed
+/// once complete, so readers only ever see fully uploaded artifacts.
+#[derive(Debug, Clone)]
+pub struct FsArtifactBackend {
+    root: PathBuf,
+}
+
+impl FsArtifactBackend {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn artifact_dir(&self, artifact_type: ArtifactType) -> PathBuf {
+        self.root.join(format!("{:?}", artifact_type))
+    }
+
+    /// Remove the artifacts last modified before `cutoff`, except those whose id is in `keep`,
+    /// returning how many were removed
+    pub async fn prune(&self, cutoff: SystemTime, keep: &HashSet<String>) -> Result<usize> {
+        let mut removed = 0;
+        let mut dirs = match tokio::fs::read_dir(&self.root).await {
+            Ok(dirs) => dirs,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        while let Some(dir) = dirs.next_entry().await? {
+            if !dir.file_type().await?.is_dir() {
+                continue;
+            }
+            let mut files = tokio::fs::read_dir(dir.path()).await?;
+            while let Some(file) = files.next_entry().await? {
+                let metadata = file.metadata().await?;
+                let kept = file.file_name().to_str().is_some_and(|id| keep.contains(id));
+                if metadata.is_file() && !kept && metadata.modified()? < cutoff {
+                    tokio::fs::remove_file(file.path()).await?;
+                    removed += 1;
+                }
+            }
+        }
+        Ok(removed)
+    }
+}
+
+#[tonic::async_trait]
+impl ArtifactBackend for FsArtifactBackend {
+    async fn put(&self, artifact_type: ArtifactType, artifact_id: &str, mut body: ArtifactReader) -> Result<u64> {
+        let dir = self.artifact_dir(artifact_type);
+        tokio::fs::create_dir_all(&dir).await?;
+
+        let final_path = dir.join(artifact_id);
+        let tmp_path = dir.join(format!(".{}.{}.tmp", artifact_id, hex::encode(random::<[u8; 8]>())));
+
+        let write = async {
+            let mut file = tokio::fs::File::create(&tmp_path).await?;
+            let size = tokio::io::copy(&mut body, &mut file).await?;
+            file.flush().await?;
+            file.sync_all().await?;
+            Ok::<_, std::io::Error>(size)
+        };
+        match write.await {
+            Ok(size) => {
+                tokio::fs::rename(&tmp_path, &final_path).await?;
+                Ok(size)
+            }
+            Err(e) => {
+                let _ = tokio::fs::remove_file(&tmp_path).await;
+                Err(e.into())
+            }
+        }
+    }
+
+    async fn get(&self, artifact_type: ArtifactType, artifact_id: &str) -> Result<Option<(u64, ArtifactReader)>> {
+        let path = self.artifact_dir(artifact_type).join(artifact_id);
+        match tokio::fs::File::open(&path).await {
+            Ok(file) => {
+                let size = file.metadata().await?.len();
+                Ok(Some((size, Box::pin(file))))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write and remove a probe file at the root, which fails on a missing, full or read-only disk
+    async fn check(&self) -> Result<()> {
+        tokio::fs::create_dir_all(&self.root).await?;
+        let probe = self.root.join(format!(".health.{}.tmp", hex::encode(random::<[u8; 8]>())));
+        tokio::fs::write(&probe, b"ok").await?;
+        tokio::fs::remove_file(&probe).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_put_and_get_artifact() {
+        let root = std::env::temp_dir().join(format!("spn_coordinator_artifacts_{}", hex::encode(random::<[u8; 8]>())));
+        let backend = FsArtifactBackend::new(&root);
+
+        let data = b"program elf".to_vec();
+        let size = backend.put(ArtifactType::Program, "abc123", Box::pin(std::io::Cursor::new(data.clone()))).await.unwrap();
+        assert_eq!(size, data.len() as u64);
+        assert!(root.join("Program").join("abc123").exists());
+
+        let (size, mut reader) = backend.get(ArtifactType::Program, "abc123").await.unwrap().unwrap();
+        let mut read = Vec::new();
+        reader.read_to_end(&mut read).await.unwrap();
+        assert_eq!(size, data.len() as u64);
+        assert_eq!(read, data);
+
+        assert!(backend.get(ArtifactType::Proof, "abc123").await.unwrap().is_none());
+
+        let later = SystemTime::now() + std::time::Duration::from_secs(1);
+        assert_eq!(backend.prune(SystemTime::UNIX_EPOCH, &HashSet::new()).await.unwrap(), 0);
+        assert_eq!(backend.prune(later, &HashSet::from(["abc123".to_string()])).await.unwrap(), 0);
+        assert_eq!(backend.prune(later, &HashSet::new()).await.unwrap(), 1);
+        assert!(backend.get(ArtifactType::Program, "abc123").await.unwrap().is_none());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
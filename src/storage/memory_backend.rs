@@ -0,0 +1,86 @@
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::RwLock;
+
+use crate::storage::backend::{StorageBackend, WriteBatch};
+
+type Tree = BTreeMap<Vec<u8>, Vec<u8>>;
+
+/// Volatile backend, everything is lost when the process stops.
+/// Useful for tests and for running the coordinator without a data directory.
+#[derive(Debug, Default)]
+pub struct MemoryBackend {
+    trees: RwLock<HashMap<String, Tree>>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl StorageBackend for MemoryBackend {
+    fn get(&self, tree: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let trees = self.trees.read().map_err(|_| anyhow::anyhow!("memory backend lock poisoned"))?;
+        Ok(trees.get(tree).and_then(|t| t.get(key).cloned()))
+    }
+
+    fn insert(&self, tree: &str, key: &[u8], value: Vec<u8>) -> Result<()> {
+        let mut trees = self.trees.write().map_err(|_| anyhow::anyhow!("memory backend lock poisoned"))?;
+        trees.entry(tree.to_string()).or_default().insert(key.to_vec(), value);
+        Ok(())
+    }
+
+    fn remove(&self, tree: &str, key: &[u8]) -> Result<()> {
+        let mut trees = self.trees.write().map_err(|_| anyhow::anyhow!("memory backend lock poisoned"))?;
+        if let Some(t) = trees.get_mut(tree) {
+            t.remove(key);
+        }
+        Ok(())
+    }
+
+    fn scan_prefix(&self, tree: &str, prefix: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let trees = self.trees.read().map_err(|_| anyhow::anyhow!("memory backend lock poisoned"))?;
+        Ok(trees
+            .get(tree)
+            .map(|t| {
+                t.range(prefix.to_vec()..)
+                    .take_while(|(k, _)| k.starts_with(prefix))
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    fn scan_range(&self, tree: &str, prefix: &[u8], start: &[u8], limit: usize) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let trees = self.trees.read().map_err(|_| anyhow::anyhow!("memory backend lock poisoned"))?;
+        let start = start.max(prefix).to_vec();
+        Ok(trees
+            .get(tree)
+            .map(|t| {
+                t.range(start..)
+                    .take_while(|(k, _)| k.starts_with(prefix))
+                    .take(limit)
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    fn apply(&self, batch: WriteBatch) -> Result<()> {
+        let mut trees = self.trees.write().map_err(|_| anyhow::anyhow!("memory backend lock poisoned"))?;
+        for (tree, key, value) in batch.writes() {
+            match value {
+                Some(value) => {
+                    trees.entry(tree.to_string()).or_default().insert(key.clone(), value.clone());
+                }
+                None => {
+                    if let Some(t) = trees.get_mut(*tree) {
+                        t.remove(key);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
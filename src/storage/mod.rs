@@ -0,0 +1,16 @@
+pub mod artifact_backend;
+pub mod backend;
+pub mod fs_artifact_backend;
+pub mod memory_backend;
+pub mod s3;
+pub mod sled_backend;
+#[allow(clippy::module_inception)]
+pub mod storage;
+
+pub use artifact_backend::*;
+pub use backend::*;
+pub use fs_artifact_backend::*;
+pub use memory_backend::*;
+pub use s3::*;
+pub use sled_backend::*;
+pub use storage::*;
@@ -0,0 +1,87 @@
+use anyhow::Result;
+use sled::transaction::{TransactionError, Transactional};
+use std::path::Path;
+
+use crate::storage::backend::{StorageBackend, WriteBatch};
+
+/// Embedded on-disk backend based on sled. Every tree maps to a sled tree
+/// inside the same database directory.
+#[derive(Debug, Clone)]
+pub struct SledBackend {
+    db: sled::Db,
+}
+
+impl SledBackend {
+    /// Open (or create) the database stored in `path`
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let db = sled::open(path.as_ref())
+            .map_err(|e| anyhow::anyhow!("Failed to open sled database at {}: {}", path.as_ref().display(), e))?;
+        Ok(Self { db })
+    }
+
+    /// Flush pending writes to disk
+    pub fn flush(&self) -> Result<()> {
+        self.db.flush()?;
+        Ok(())
+    }
+}
+
+impl StorageBackend for SledBackend {
+    fn get(&self, tree: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.db.open_tree(tree)?.get(key)?.map(|v| v.to_vec()))
+    }
+
+    fn insert(&self, tree: &str, key: &[u8], value: Vec<u8>) -> Result<()> {
+        self.db.open_tree(tree)?.insert(key, value)?;
+        Ok(())
+    }
+
+    fn remove(&self, tree: &str, key: &[u8]) -> Result<()> {
+        self.db.open_tree(tree)?.remove(key)?;
+        Ok(())
+    }
+
+    fn scan_prefix(&self, tree: &str, prefix: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        self.db
+            .open_tree(tree)?
+            .scan_prefix(prefix)
+            .map(|entry| {
+                let (k, v) = entry?;
+                Ok((k.to_vec(), v.to_vec()))
+            })
+            .collect()
+    }
+
+    fn scan_range(&self, tree: &str, prefix: &[u8], start: &[u8], limit: usize) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut entries = Vec::new();
+        for entry in self.db.open_tree(tree)?.range(start.max(prefix)..) {
+            let (k, v) = entry?;
+            if !k.starts_with(prefix) || entries.len() == limit {
+                break;
+            }
+            entries.push((k.to_vec(), v.to_vec()));
+        }
+        Ok(entries)
+    }
+
+    fn apply(&self, batch: WriteBatch) -> Result<()> {
+        let names = batch.trees();
+        let trees = names.iter().map(|name| self.db.open_tree(name)).collect::<Result<Vec<_>, _>>()?;
+        trees
+            .as_slice()
+            .transaction(|views| {
+                for (tree, key, value) in batch.writes() {
+                    // Trees are sorted and written ones are all listed
+                    let view = &views[names.binary_search(tree).expect("tree of the batch")];
+                    match value {
+                        Some(value) => view.insert(key.as_slice(), value.as_slice())?,
+                        None => view.remove(key.as_slice())?,
+                    };
+                }
+                Ok(())
+            })
+            .map_err(|e: TransactionError| match e {
+                TransactionError::Storage(e) | TransactionError::Abort(e) => anyhow::Error::from(e),
+            })
+    }
+}
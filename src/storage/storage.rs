@@ -0,0 +1,964 @@
+use prost::Message;
+use rpc_types::*;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+use tokio::sync::broadcast;
+
+use crate::storage::backend::{StorageBackend, WriteBatch};
+use crate::storage::memory_backend::MemoryBackend;
+
+const PROOF_REQUESTS_TREE: &str = "proof_requests";
+const PROGRAMS_TREE: &str = "programs";
+const BIDS_TREE: &str = "bids";
+/// When each request was last assigned to its fulfiller
+const ASSIGNMENTS_TREE: &str = "assignments";
+const NONCES_TREE: &str = "nonces";
+const RESERVATIONS_TREE: &str = "reservations";
+const PROVERS_TREE: &str = "provers";
+const BALANCES_TREE: &str = "balances";
+const BALANCE_LOGS_TREE: &str = "balance_logs";
+const STAKES_TREE: &str = "stakes";
+const STAKE_LOGS_TREE: &str = "stake_logs";
+const WITHDRAWAL_RECEIPTS_TREE: &str = "withdrawal_receipts";
+const DELEGATIONS_TREE: &str = "delegations";
+const COUNTERS_TREE: &str = "counters";
+/// Records about the storage itself, such as which indexes are built
+const META_TREE: &str = "meta";
+const PROOF_REQUEST_INDEXES_KEY: &[u8] = b"proof_request_indexes";
+/// Bumped when an index is added, so databases get the new one built
+const PROOF_REQUEST_INDEXES_VERSION: u8 = 2;
+/// Owner of each delegate, the reverse of the delegations tree
+const DELEGATES_TREE: &str = "delegates";
+/// Delegation offered by each owner, until its delegate accepts it
+const DELEGATION_OFFERS_TREE: &str = "delegation_offers";
+
+/// Secondary index of proof requests.
+///
+/// Each index is its own tree whose keys are the indexed value followed by the
+/// position of the request: its creation time and id. Requests sharing a value
+/// are therefore ordered by creation time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProofRequestIndex {
+    CreatedAt,
+    FulfillmentStatus(i32),
+    Requester(Vec<u8>),
+    Fulfiller(Vec<u8>),
+    VkHash(Vec<u8>),
+    /// Requests assigned to a fulfiller and not finished yet
+    AssignedTo(Vec<u8>),
+}
+
+impl ProofRequestIndex {
+    fn tree(&self) -> &'static str {
+        match self {
+            Self::CreatedAt => "proof_requests_by_created_at",
+            Self::FulfillmentStatus(_) => "proof_requests_by_status",
+            Self::Requester(_) => "proof_requests_by_requester",
+            Self::Fulfiller(_) => "proof_requests_by_fulfiller",
+            Self::VkHash(_) => "proof_requests_by_vk_hash",
+            Self::AssignedTo(_) => "proof_requests_by_assignee",
+        }
+    }
+
+    fn prefix(&self) -> Vec<u8> {
+        match self {
+            Self::CreatedAt => Vec::new(),
+            Self::FulfillmentStatus(status) => status.to_be_bytes().to_vec(),
+            Self::Requester(value) | Self::Fulfiller(value) | Self::VkHash(value) | Self::AssignedTo(value) => value.clone(),
+        }
+    }
+
+    /// Indexes a request is listed in
+    fn of(request: &ProofRequest) -> Vec<Self> {
+        let mut indexes = vec![
+            Self::CreatedAt,
+            Self::FulfillmentStatus(request.fulfillment_status),
+            Self::Requester(request.requester.clone()),
+            Self::VkHash(request.vk_hash.clone()),
+        ];
+        if let Some(fulfiller) = &request.fulfiller {
+            indexes.push(Self::Fulfiller(fulfiller.clone()));
+            if request.fulfillment_status == FulfillmentStatus::Assigned as i32 {
+                indexes.push(Self::AssignedTo(fulfiller.clone()));
+            }
+        }
+        indexes
+    }
+}
+
+/// Position of a request in the indexes: its creation time, then its id
+pub fn proof_request_position(request: &ProofRequest) -> Vec<u8> {
+    [request.created_at.to_be_bytes().as_slice(), &request.request_id].concat()
+}
+
+/// Counters together with the key they are stored under
+pub type KeyedCounters = (Vec<u8>, Vec<u128>);
+
+/// Number of proof request updates a slow subscriber can fall behind before missing some
+const EVENTS_CAPACITY: usize = 1024;
+
+/// Errors returned by [`Storage`]
+#[derive(Debug, Error)]
+pub enum StorageError {
+    #[error("record not found")]
+    NotFound,
+    #[error("failed to decode stored record: {0}")]
+    Decode(#[from] prost::DecodeError),
+    #[error("storage backend error: {0}")]
+    Backend(#[from] anyhow::Error),
+}
+
+/// Typed access to the coordinator state on top of a [`StorageBackend`].
+///
+/// Records are stored protobuf encoded, so they can be read back by any
+/// version of the coordinator that understands the same proto files.
+#[derive(Debug, Clone)]
+pub struct Storage {
+    backend: Arc<dyn StorageBackend>,
+    /// Serializes read-modify-write cycles done through the `update_*` helpers
+    write_lock: Arc<Mutex<()>>,
+    /// Every proof request written is published here
+    events: broadcast::Sender<ProofRequest>,
+    /// Number of proof requests per fulfillment status, counted from the index on first read and
+    /// kept up to date as requests are written
+    status_counts: Arc<Mutex<HashMap<i32, usize>>>,
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Self::new(Arc::new(MemoryBackend::new()))
+    }
+}
+
+impl Storage {
+    pub fn new(backend: Arc<dyn StorageBackend>) -> Self {
+        Self {
+            backend,
+            write_lock: Arc::new(Mutex::new(())),
+            events: broadcast::channel(EVENTS_CAPACITY).0,
+            status_counts: Arc::default(),
+        }
+    }
+
+    /// Receive every proof request from now on, each time it is created or updated
+    pub fn subscribe_proof_requests(&self) -> broadcast::Receiver<ProofRequest> {
+        self.events.subscribe()
+    }
+
+    /// Get a proof request together with its status
+    pub fn get_proof_request(&self, request_id: &[u8]) -> Result<Option<(ProofRequest, GetProofRequestStatusResponse)>, StorageError> {
+        self.backend
+            .get(PROOF_REQUESTS_TREE, request_id)?
+            .map(|bytes| decode_proof_request(&bytes))
+            .transpose()
+    }
+
+    /// Insert or overwrite a proof request together with its status
+    pub fn put_proof_request(&self, request: &ProofRequest, status: &GetProofRequestStatusResponse) -> Result<(), StorageError> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.write_proof_request(request, status, WriteBatch::new())
+    }
+
+    /// Store a proof request and its index entries in the same batch as `batch`, for callers
+    /// already holding the write lock, so the indexes are updated from the request they replace
+    fn write_proof_request(&self, request: &ProofRequest, status: &GetProofRequestStatusResponse, mut batch: WriteBatch) -> Result<(), StorageError> {
+        let previous = self.get_proof_request(&request.request_id)?.map(|(previous, _)| previous);
+        batch.insert(PROOF_REQUESTS_TREE, &request.request_id, encode_proof_request(request, status));
+        self.write_indexes(batch, previous.as_ref(), Some(request))?;
+        // Nobody listening is not an error
+        let _ = self.events.send(request.clone());
+        Ok(())
+    }
+
+    /// Atomically load a proof request, apply `f` to it and store the result.
+    /// Nothing is written if `f` returns an error.
+    pub fn update_proof_request<T, E>(
+        &self,
+        request_id: &[u8],
+        f: impl FnOnce(&mut ProofRequest, &mut GetProofRequestStatusResponse) -> Result<T, E>,
+    ) -> Result<T, E>
+    where
+        E: From<StorageError>,
+    {
+        self.update_proof_request_with_writes(request_id, |request, status, _| f(request, status))
+    }
+
+    /// [`Self::update_proof_request`] where `f` can also stage writes of records tied to the
+    /// request, stored in the same batch as the request
+    pub fn update_proof_request_with_writes<T, E>(
+        &self,
+        request_id: &[u8],
+        f: impl FnOnce(&mut ProofRequest, &mut GetProofRequestStatusResponse, &mut ProofRequestWrites) -> Result<T, E>,
+    ) -> Result<T, E>
+    where
+        E: From<StorageError>,
+    {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let (mut request, mut status) = self.get_proof_request(request_id)?.ok_or(StorageError::NotFound)?;
+        let mut writes = ProofRequestWrites::default();
+        let result = f(&mut request, &mut status, &mut writes)?;
+        self.write_proof_request(&request, &status, writes.batch)?;
+        Ok(result)
+    }
+
+    /// Atomically load a proof request, apply `f` to it and store the result together with the
+    /// balance logs `f` returns, as [`Self::apply_balance_logs`] does. Nothing is written if `f`
+    /// returns an error, and `None` is returned with nothing written if the logs can't be applied.
+    pub fn update_proof_request_with_balance_logs<T, E>(
+        &self,
+        request_id: &[u8],
+        overdraft: &[u8],
+        f: impl FnOnce(&mut ProofRequest, &mut GetProofRequestStatusResponse) -> Result<(T, Vec<BalanceLog>), E>,
+    ) -> Result<Option<T>, E>
+    where
+        E: From<StorageError>,
+    {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let (mut request, mut status) = self.get_proof_request(request_id)?.ok_or(StorageError::NotFound)?;
+        let (result, logs) = f(&mut request, &mut status)?;
+        let mut batch = WriteBatch::new();
+        if !self.stage_balance_and_stake_logs(&mut batch, &logs, &[], overdraft)? {
+            return Ok(None);
+        }
+        if let Some(fulfiller) = &request.fulfiller {
+            self.stage_earnings(&mut batch, fulfiller, &logs)?;
+        }
+        self.write_proof_request(&request, &status, batch)?;
+        Ok(Some(result))
+    }
+
+    /// Remove a proof request together with its bids
+    pub fn remove_proof_request(&self, request_id: &[u8]) -> Result<(), StorageError> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut batch = WriteBatch::new();
+        for (key, _) in self.backend.scan_prefix(BIDS_TREE, request_id)? {
+            batch.remove(BIDS_TREE, &key);
+        }
+        batch.remove(ASSIGNMENTS_TREE, request_id);
+        let previous = self.get_proof_request(request_id)?.map(|(request, _)| request);
+        if previous.is_some() {
+            batch.remove(PROOF_REQUESTS_TREE, request_id);
+        }
+        self.write_indexes(batch, previous.as_ref(), None)
+    }
+
+    /// Return at most `limit` proof requests of an index, from the position `start` on, together with their position
+    pub fn scan_proof_requests(
+        &self,
+        index: &ProofRequestIndex,
+        start: &[u8],
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, ProofRequest, GetProofRequestStatusResponse)>, StorageError> {
+        let prefix = index.prefix();
+        let start = [prefix.as_slice(), start].concat();
+        let mut requests = Vec::new();
+        for (key, _) in self.backend.scan_range(index.tree(), &prefix, &start, limit)? {
+            let position = key[prefix.len()..].to_vec();
+            // Positions end with the request id, after its 8 byte creation time
+            if let Some((request, status)) = position.get(8..).map(|id| self.get_proof_request(id)).transpose()?.flatten() {
+                requests.push((position, request, status));
+            }
+        }
+        Ok(requests)
+    }
+
+    /// Return every proof request with a fulfillment status, oldest first
+    pub fn proof_requests_with_status(
+        &self,
+        status: FulfillmentStatus,
+    ) -> Result<Vec<(ProofRequest, GetProofRequestStatusResponse)>, StorageError> {
+        let index = ProofRequestIndex::FulfillmentStatus(status as i32);
+        Ok(self.scan_proof_requests(&index, &[], usize::MAX)?.into_iter().map(|(_, request, status)| (request, status)).collect())
+    }
+
+    /// Check the backend still answers reads
+    pub fn check(&self) -> Result<(), StorageError> {
+        self.backend.get(META_TREE, PROOF_REQUEST_INDEXES_KEY)?;
+        Ok(())
+    }
+
+    /// Number of proof requests listed in an index
+    pub fn count_proof_requests(&self, index: &ProofRequestIndex) -> Result<usize, StorageError> {
+        Ok(self.backend.scan_prefix(index.tree(), &index.prefix())?.len())
+    }
+
+    /// Number of proof requests with a fulfillment status, without reading the index once known
+    pub fn count_proof_requests_with_status(&self, status: FulfillmentStatus) -> Result<usize, StorageError> {
+        let mut counts = self.status_counts.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(count) = counts.get(&(status as i32)) {
+            return Ok(*count);
+        }
+        let count = self.count_proof_requests(&ProofRequestIndex::FulfillmentStatus(status as i32))?;
+        counts.insert(status as i32, count);
+        Ok(count)
+    }
+
+    /// Build the proof request indexes of a database written before they existed, returns false if they were built already
+    pub fn ensure_proof_request_indexes(&self) -> Result<bool, StorageError> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        if self.backend.get(META_TREE, PROOF_REQUEST_INDEXES_KEY)? == Some(vec![PROOF_REQUEST_INDEXES_VERSION]) {
+            return Ok(false);
+        }
+        // Entries already indexed are written again, which leaves them unchanged
+        for (request, _) in self.proof_requests()? {
+            self.write_indexes(WriteBatch::new(), None, Some(&request))?;
+        }
+        // Requests indexed already were counted again, the counts are read from the index anew
+        self.status_counts.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        self.backend.insert(META_TREE, PROOF_REQUEST_INDEXES_KEY, vec![PROOF_REQUEST_INDEXES_VERSION])?;
+        Ok(true)
+    }
+
+    /// Apply `batch` together with the move of a request from the index entries of its previous
+    /// version to those of its new version
+    fn write_indexes(&self, mut batch: WriteBatch, previous: Option<&ProofRequest>, request: Option<&ProofRequest>) -> Result<(), StorageError> {
+        let entries = |request: Option<&ProofRequest>| -> Vec<(&'static str, Vec<u8>)> {
+            let Some(request) = request else {
+                return Vec::new();
+            };
+            let position = proof_request_position(request);
+            ProofRequestIndex::of(request)
+                .into_iter()
+                .map(|index| (index.tree(), [index.prefix().as_slice(), &position].concat()))
+                .collect()
+        };
+        // Held while the index changes, so counts read from the index are never adjusted twice
+        let mut counts = self.status_counts.lock().unwrap_or_else(|e| e.into_inner());
+        let (old, new) = (entries(previous), entries(request));
+        for (tree, key) in old.iter().filter(|entry| !new.contains(entry)) {
+            batch.remove(tree, key);
+        }
+        for (tree, key) in new.iter().filter(|entry| !old.contains(entry)) {
+            batch.insert(tree, key, Vec::new());
+        }
+        self.backend.apply(batch)?;
+        if let Some(count) = previous.and_then(|previous| counts.get_mut(&previous.fulfillment_status)) {
+            *count = count.saturating_sub(1);
+        }
+        if let Some(count) = request.and_then(|request| counts.get_mut(&request.fulfillment_status)) {
+            *count += 1;
+        }
+        Ok(())
+    }
+
+    /// Return every stored proof request
+    pub fn proof_requests(&self) -> Result<Vec<(ProofRequest, GetProofRequestStatusResponse)>, StorageError> {
+        self.backend
+            .scan_prefix(PROOF_REQUESTS_TREE, &[])?
+            .iter()
+            .map(|(_, bytes)| decode_proof_request(bytes))
+            .collect()
+    }
+
+    /// Get a program by its verification key hash
+    pub fn get_program(&self, vk_hash: &[u8]) -> Result<Option<Program>, StorageError> {
+        self.backend
+            .get(PROGRAMS_TREE, vk_hash)?
+            .map(|bytes| Program::decode(bytes.as_slice()).map_err(StorageError::from))
+            .transpose()
+    }
+
+    /// Insert or overwrite a program
+    pub fn put_program(&self, program: &Program) -> Result<(), StorageError> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.backend.insert(PROGRAMS_TREE, &program.vk_hash, program.encode_to_vec())?;
+        Ok(())
+    }
+
+    /// Return every stored program
+    pub fn programs(&self) -> Result<Vec<Program>, StorageError> {
+        self.backend
+            .scan_prefix(PROGRAMS_TREE, &[])?
+            .iter()
+            .map(|(_, bytes)| Program::decode(bytes.as_slice()).map_err(StorageError::from))
+            .collect()
+    }
+
+    /// Next nonce expected from an account
+    pub fn next_nonce(&self, address: &[u8]) -> Result<u64, StorageError> {
+        Ok(self
+            .backend
+            .get(NONCES_TREE, address)?
+            .and_then(|bytes| bytes.try_into().ok().map(u64::from_be_bytes))
+            .unwrap_or(0))
+    }
+
+    /// Consume `nonce` for an account. Returns false, without consuming it, if the
+    /// nonce was already used or is lower than a nonce already used.
+    pub fn use_nonce(&self, address: &[u8], nonce: u64) -> Result<bool, StorageError> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        if nonce < self.next_nonce(address)? {
+            return Ok(false);
+        }
+        let next = nonce.checked_add(1).ok_or_else(|| anyhow::anyhow!("nonce overflow"))?;
+        self.backend.insert(NONCES_TREE, address, next.to_be_bytes().to_vec())?;
+        Ok(true)
+    }
+
+    /// Give back `nonce`, consumed by a request that was then rejected. Nothing is
+    /// given back when a later nonce was used since.
+    pub fn release_nonce(&self, address: &[u8], nonce: u64) -> Result<bool, StorageError> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        if nonce.checked_add(1) != Some(self.next_nonce(address)?) {
+            return Ok(false);
+        }
+        self.backend.insert(NONCES_TREE, address, nonce.to_be_bytes().to_vec())?;
+        Ok(true)
+    }
+
+    /// Insert or replace the bid of `bid.bidder` on a request
+    pub fn put_bid(&self, request_id: &[u8], bid: &BidHistory) -> Result<(), StorageError> {
+        self.write(|writes| writes.put_bid(request_id, bid))
+    }
+
+    /// Remove the bid of `bidder` on a request, if any
+    pub fn remove_bid(&self, request_id: &[u8], bidder: &[u8]) -> Result<(), StorageError> {
+        self.write(|writes| writes.remove_bid(request_id, bidder))
+    }
+
+    /// Record that a request was assigned to its fulfiller at `assigned_at`
+    pub fn put_assigned_at(&self, request_id: &[u8], assigned_at: u64) -> Result<(), StorageError> {
+        self.write(|writes| writes.put_assigned_at(request_id, assigned_at))
+    }
+
+    /// Make the writes staged by `f` under the write lock
+    fn write(&self, f: impl FnOnce(&mut ProofRequestWrites)) -> Result<(), StorageError> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut writes = ProofRequestWrites::default();
+        f(&mut writes);
+        self.backend.apply(writes.batch)?;
+        Ok(())
+    }
+
+    /// When a request was last assigned, if it was since assignments are recorded
+    pub fn get_assigned_at(&self, request_id: &[u8]) -> Result<Option<u64>, StorageError> {
+        Ok(self.backend.get(ASSIGNMENTS_TREE, request_id)?.and_then(|bytes| bytes.try_into().ok().map(u64::from_be_bytes)))
+    }
+
+    /// Return every bid placed on a request
+    pub fn bids(&self, request_id: &[u8]) -> Result<Vec<BidHistory>, StorageError> {
+        self.backend
+            .scan_prefix(BIDS_TREE, request_id)?
+            .iter()
+            .map(|(_, bytes)| BidHistory::decode(bytes.as_slice()).map_err(StorageError::from))
+            .collect()
+    }
+
+    /// Get the reservation of a requester
+    pub fn get_reservation(&self, requester: &[u8]) -> Result<Option<Reservation>, StorageError> {
+        self.backend
+            .get(RESERVATIONS_TREE, requester)?
+            .map(|bytes| Reservation::decode(bytes.as_slice()).map_err(StorageError::from))
+            .transpose()
+    }
+
+    /// Insert or replace the reservation of `reservation.requester`
+    pub fn put_reservation(&self, reservation: &Reservation) -> Result<(), StorageError> {
+        self.backend.insert(RESERVATIONS_TREE, &reservation.requester, reservation.encode_to_vec())?;
+        Ok(())
+    }
+
+    /// Remove the reservation of a requester, returns false if it had none
+    pub fn remove_reservation(&self, requester: &[u8]) -> Result<bool, StorageError> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        if self.backend.get(RESERVATIONS_TREE, requester)?.is_none() {
+            return Ok(false);
+        }
+        self.backend.remove(RESERVATIONS_TREE, requester)?;
+        Ok(true)
+    }
+
+    /// Return every stored reservation
+    pub fn reservations(&self) -> Result<Vec<Reservation>, StorageError> {
+        self.backend
+            .scan_prefix(RESERVATIONS_TREE, &[])?
+            .iter()
+            .map(|(_, bytes)| Reservation::decode(bytes.as_slice()).map_err(StorageError::from))
+            .collect()
+    }
+
+    /// Store the receipt of a withdrawal
+    pub fn put_withdrawal_receipt(&self, receipt: &WithdrawalReceipt) -> Result<(), StorageError> {
+        // Receipts are ordered by time, the transaction hash keeps receipts of the same second apart
+        let key = [receipt.created_at.to_be_bytes().as_slice(), &receipt.tx_hash].concat();
+        self.backend.insert(WITHDRAWAL_RECEIPTS_TREE, &key, receipt.encode_to_vec())?;
+        Ok(())
+    }
+
+    /// Return every withdrawal receipt, oldest first
+    pub fn withdrawal_receipts(&self) -> Result<Vec<WithdrawalReceipt>, StorageError> {
+        self.backend
+            .scan_prefix(WITHDRAWAL_RECEIPTS_TREE, &[])?
+            .iter()
+            .map(|(_, bytes)| WithdrawalReceipt::decode(bytes.as_slice()).map_err(StorageError::from))
+            .collect()
+    }
+
+    /// Counters stored under `key`, empty if none were added yet
+    pub fn get_counters(&self, key: &[u8]) -> Result<Vec<u128>, StorageError> {
+        Ok(self.backend.get(COUNTERS_TREE, key)?.map(|bytes| decode_counters(&bytes)).unwrap_or_default())
+    }
+
+    /// Add `deltas` to the counters stored under `key`, counters missing so far start at zero
+    pub fn add_counters(&self, key: &[u8], deltas: &[u128]) -> Result<(), StorageError> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut counters = self.get_counters(key)?;
+        counters.resize(counters.len().max(deltas.len()), 0);
+        for (counter, delta) in counters.iter_mut().zip(deltas) {
+            *counter = counter.saturating_add(*delta);
+        }
+        let bytes: Vec<u8> = counters.iter().flat_map(|counter| counter.to_be_bytes()).collect();
+        self.backend.insert(COUNTERS_TREE, key, bytes)?;
+        Ok(())
+    }
+
+    /// Remove every counter
+    pub fn clear_counters(&self) -> Result<(), StorageError> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut batch = WriteBatch::new();
+        for (key, _) in self.backend.scan_prefix(COUNTERS_TREE, &[])? {
+            batch.remove(COUNTERS_TREE, &key);
+        }
+        self.backend.apply(batch)?;
+        Ok(())
+    }
+
+    /// Whether the flag `key` of the storage is set
+    pub fn has_meta_flag(&self, key: &[u8]) -> Result<bool, StorageError> {
+        Ok(self.backend.get(META_TREE, key)?.is_some())
+    }
+
+    /// Set the flag `key` of the storage
+    pub fn set_meta_flag(&self, key: &[u8]) -> Result<(), StorageError> {
+        self.backend.insert(META_TREE, key, vec![1])?;
+        Ok(())
+    }
+
+    /// Return at most `limit` counters whose key starts with `prefix` and is at least `start`, in key order
+    pub fn counters_range(&self, prefix: &[u8], start: &[u8], limit: usize) -> Result<Vec<KeyedCounters>, StorageError> {
+        Ok(self
+            .backend
+            .scan_range(COUNTERS_TREE, prefix, start, limit)?
+            .into_iter()
+            .map(|(key, bytes)| (key, decode_counters(&bytes)))
+            .collect())
+    }
+
+    /// Get the delegation of an owner
+    pub fn get_delegation(&self, owner: &[u8]) -> Result<Option<Delegation>, StorageError> {
+        self.backend
+            .get(DELEGATIONS_TREE, owner)?
+            .map(|bytes| Delegation::decode(bytes.as_slice()).map_err(StorageError::from))
+            .transpose()
+    }
+
+    /// Get the owner a delegate acts for
+    pub fn get_delegation_owner(&self, delegate: &[u8]) -> Result<Option<Vec<u8>>, StorageError> {
+        Ok(self.backend.get(DELEGATES_TREE, delegate)?)
+    }
+
+    /// Get the delegation an owner offered and its delegate has not accepted yet
+    pub fn get_delegation_offer(&self, owner: &[u8]) -> Result<Option<Delegation>, StorageError> {
+        self.backend
+            .get(DELEGATION_OFFERS_TREE, owner)?
+            .map(|bytes| Delegation::decode(bytes.as_slice()).map_err(StorageError::from))
+            .transpose()
+    }
+
+    /// Insert or replace the delegation offered by `delegation.owner`, which has no effect until accepted
+    pub fn put_delegation_offer(&self, delegation: &Delegation) -> Result<(), StorageError> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.backend.insert(DELEGATION_OFFERS_TREE, &delegation.owner, delegation.encode_to_vec())?;
+        Ok(())
+    }
+
+    /// Insert or replace the delegation of `delegation.owner`, whose previous delegate stops acting for it
+    /// and whose offer is consumed. Returns false if the delegate already acts for another owner.
+    pub fn put_delegation(&self, delegation: &Delegation) -> Result<bool, StorageError> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        if self.get_delegation_owner(&delegation.delegate)?.is_some_and(|owner| owner != delegation.owner) {
+            return Ok(false);
+        }
+        let mut batch = WriteBatch::new();
+        if let Some(previous) = self.get_delegation(&delegation.owner)? {
+            batch.remove(DELEGATES_TREE, &previous.delegate);
+        }
+        batch.insert(DELEGATIONS_TREE, &delegation.owner, delegation.encode_to_vec());
+        batch.insert(DELEGATES_TREE, &delegation.delegate, delegation.owner.clone());
+        batch.remove(DELEGATION_OFFERS_TREE, &delegation.owner);
+        self.backend.apply(batch)?;
+        Ok(true)
+    }
+
+    /// Get the stats of a prover together with the seconds it has been up
+    pub fn get_prover(&self, address: &[u8]) -> Result<Option<(ProverStats, u64)>, StorageError> {
+        self.backend.get(PROVERS_TREE, address)?.map(|bytes| decode_prover(&bytes)).transpose()
+    }
+
+    /// Atomically load the stats and up seconds of a prover, apply `f` to them and store the result.
+    /// Unknown provers start from empty stats.
+    pub fn update_prover<T>(&self, address: &[u8], f: impl FnOnce(&mut ProverStats, &mut u64) -> T) -> Result<T, StorageError> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let (mut stats, mut up_secs) = self.get_prover(address)?.unwrap_or_else(|| {
+            let stats = ProverStats { address: address.to_vec(), owner: address.to_vec(), ..Default::default() };
+            (stats, 0)
+        });
+        let result = f(&mut stats, &mut up_secs);
+        self.backend.insert(PROVERS_TREE, address, encode_prover(&stats, up_secs))?;
+        Ok(result)
+    }
+
+    /// Add the rewards paid to `prover` by `logs` to its earnings
+    fn stage_earnings(&self, batch: &mut WriteBatch, prover: &[u8], logs: &[BalanceLog]) -> Result<(), StorageError> {
+        let earned: u128 = logs
+            .iter()
+            .filter(|log| log.address == prover && log.operation == BalanceOperation::Reward as i32)
+            .filter_map(|log| log.amount.parse::<u128>().ok())
+            .sum();
+        if earned == 0 {
+            return Ok(());
+        }
+        let (mut stats, up_secs) = self.get_prover(prover)?.unwrap_or_else(|| {
+            let stats = ProverStats { address: prover.to_vec(), owner: prover.to_vec(), ..Default::default() };
+            (stats, 0)
+        });
+        let total = stats.total_earnings.parse::<u128>().unwrap_or(0).saturating_add(earned);
+        stats.total_earnings = total.to_string();
+        batch.insert(PROVERS_TREE, prover, encode_prover(&stats, up_secs));
+        Ok(())
+    }
+
+    /// Return every known prover with the seconds it has been up
+    pub fn provers(&self) -> Result<Vec<(ProverStats, u64)>, StorageError> {
+        self.backend
+            .scan_prefix(PROVERS_TREE, &[])?
+            .iter()
+            .map(|(_, bytes)| decode_prover(bytes))
+            .collect()
+    }
+
+    /// Credit balance of an account
+    pub fn get_balance(&self, address: &[u8]) -> Result<i128, StorageError> {
+        self.read_amount(BALANCES_TREE, address)
+    }
+
+    /// Atomically apply the signed amounts of `logs` to the balances of their accounts and record
+    /// the logs. Nothing is written and false is returned if an account would go negative,
+    /// except `overdraft` which may.
+    pub fn apply_balance_logs(&self, logs: &[BalanceLog], overdraft: &[u8]) -> Result<bool, StorageError> {
+        self.apply_balance_and_stake_logs(logs, &[], overdraft)
+    }
+
+    /// Return every balance log, oldest first
+    pub fn balance_logs(&self) -> Result<Vec<BalanceLog>, StorageError> {
+        self.backend
+            .scan_prefix(BALANCE_LOGS_TREE, &[])?
+            .iter()
+            .map(|(_, bytes)| BalanceLog::decode(bytes.as_slice()).map_err(StorageError::from))
+            .collect()
+    }
+
+    /// Stake of a prover
+    pub fn get_stake(&self, address: &[u8]) -> Result<i128, StorageError> {
+        self.read_amount(STAKES_TREE, address)
+    }
+
+    /// Atomically apply the signed amounts of `logs` to the stakes of their provers and record
+    /// the logs. Nothing is written and false is returned if a stake would go negative.
+    pub fn apply_stake_logs(&self, logs: &[StakeBalanceLog]) -> Result<bool, StorageError> {
+        self.apply_balance_and_stake_logs(&[], logs, &[])
+    }
+
+    /// Return every stake log, oldest first
+    pub fn stake_logs(&self) -> Result<Vec<StakeBalanceLog>, StorageError> {
+        self.backend
+            .scan_prefix(STAKE_LOGS_TREE, &[])?
+            .iter()
+            .map(|(_, bytes)| StakeBalanceLog::decode(bytes.as_slice()).map_err(StorageError::from))
+            .collect()
+    }
+
+    fn read_amount(&self, tree: &str, address: &[u8]) -> Result<i128, StorageError> {
+        Ok(self
+            .backend
+            .get(tree, address)?
+            .and_then(|bytes| bytes.try_into().ok().map(i128::from_be_bytes))
+            .unwrap_or(0))
+    }
+
+    /// Atomically apply balance logs and stake logs, as [`Self::apply_balance_logs`] and
+    /// [`Self::apply_stake_logs`] do. Nothing is written unless both can be applied.
+    pub fn apply_balance_and_stake_logs(
+        &self,
+        balance_logs: &[BalanceLog],
+        stake_logs: &[StakeBalanceLog],
+        overdraft: &[u8],
+    ) -> Result<bool, StorageError> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut batch = WriteBatch::new();
+        if !self.stage_balance_and_stake_logs(&mut batch, balance_logs, stake_logs, overdraft)? {
+            return Ok(false);
+        }
+        self.backend.apply(batch)?;
+        Ok(true)
+    }
+
+    /// Add the writes of [`Self::apply_balance_and_stake_logs`] to `batch`, for callers already holding
+    /// the write lock. Nothing is added and false is returned if the logs can't be applied.
+    fn stage_balance_and_stake_logs(
+        &self,
+        batch: &mut WriteBatch,
+        balance_logs: &[BalanceLog],
+        stake_logs: &[StakeBalanceLog],
+        overdraft: &[u8],
+    ) -> Result<bool, StorageError> {
+        let balance_entries: Vec<LogEntry> =
+            balance_logs.iter().map(|log| (log.address.as_slice(), log.amount.as_str(), log.created_at, log.encode_to_vec())).collect();
+        let stake_entries: Vec<LogEntry> =
+            stake_logs.iter().map(|log| (log.address.as_slice(), log.amount.as_str(), log.created_at, log.encode_to_vec())).collect();
+        let balances = self.apply_amounts(BALANCES_TREE, &balance_entries, overdraft)?;
+        let stakes = self.apply_amounts(STAKES_TREE, &stake_entries, &[])?;
+        let (Some(balances), Some(stakes)) = (balances, stakes) else {
+            return Ok(false);
+        };
+        stage_logs(batch, BALANCES_TREE, BALANCE_LOGS_TREE, balances, balance_entries);
+        stage_logs(batch, STAKES_TREE, STAKE_LOGS_TREE, stakes, stake_entries);
+        Ok(true)
+    }
+
+    /// New amounts of the addresses of `entries` once applied, `None` if one other than `overdraft` would go negative
+    fn apply_amounts<'a>(&self, amounts_tree: &str, entries: &[LogEntry<'a>], overdraft: &[u8]) -> Result<Option<Vec<Amount<'a>>>, StorageError> {
+        let mut amounts: Vec<Amount> = Vec::new();
+        for (address, amount, _, _) in entries {
+            let amount: i128 = amount.parse().map_err(|_| anyhow::anyhow!("invalid log amount {}", amount))?;
+            let index = match amounts.iter().position(|(a, _)| a == address) {
+                Some(index) => index,
+                None => {
+                    amounts.push((address, self.read_amount(amounts_tree, address)?));
+                    amounts.len() - 1
+                }
+            };
+            amounts[index].1 = amounts[index].1.checked_add(amount).ok_or_else(|| anyhow::anyhow!("amount overflow"))?;
+        }
+        if amounts.iter().any(|(address, amount)| *amount < 0 && *address != overdraft) {
+            return Ok(None);
+        }
+        Ok(Some(amounts))
+    }
+
+}
+
+/// Writes made together with a proof request by [`Storage::update_proof_request_with_writes`]
+#[derive(Debug, Default)]
+pub struct ProofRequestWrites {
+    batch: WriteBatch,
+}
+
+impl ProofRequestWrites {
+    /// Insert or replace the bid of `bid.bidder` on a request
+    pub fn put_bid(&mut self, request_id: &[u8], bid: &BidHistory) {
+        self.batch.insert(BIDS_TREE, &[request_id, bid.bidder.as_slice()].concat(), bid.encode_to_vec());
+    }
+
+    /// Remove the bid of `bidder` on a request, if any
+    pub fn remove_bid(&mut self, request_id: &[u8], bidder: &[u8]) {
+        self.batch.remove(BIDS_TREE, &[request_id, bidder].concat());
+    }
+
+    /// Record that a request was assigned to its fulfiller at `assigned_at`
+    pub fn put_assigned_at(&mut self, request_id: &[u8], assigned_at: u64) {
+        self.batch.insert(ASSIGNMENTS_TREE, request_id, assigned_at.to_be_bytes().to_vec());
+    }
+}
+
+/// Add the amounts computed by [`Storage::apply_amounts`] and the logs of `entries` to `batch`
+fn stage_logs(batch: &mut WriteBatch, amounts_tree: &'static str, logs_tree: &'static str, amounts: Vec<Amount>, entries: Vec<LogEntry>) {
+    for (address, amount) in amounts {
+        batch.insert(amounts_tree, address, amount.to_be_bytes().to_vec());
+    }
+    for (_, _, created_at, log) in entries {
+        // Logs are ordered by time, the random suffix keeps logs of the same second apart
+        let key = [created_at.to_be_bytes().as_slice(), &rand::random::<[u8; 8]>()].concat();
+        batch.insert(logs_tree, &key, log);
+    }
+}
+
+/// `(address, signed amount, created_at, encoded log)` of a balance or stake log
+type LogEntry<'a> = (&'a [u8], &'a str, u64, Vec<u8>);
+
+/// `(address, amount)` of a balance or stake
+type Amount<'a> = (&'a [u8], i128);
+
+/// Counters are stored as consecutive 16 byte big endian numbers
+fn decode_counters(bytes: &[u8]) -> Vec<u128> {
+    bytes
+        .chunks_exact(16)
+        .map(|chunk| u128::from_be_bytes(chunk.try_into().expect("16 byte chunk")))
+        .collect()
+}
+
+/// A proof request and its status are stored in the same value so they are always written together
+fn encode_proof_request(request: &ProofRequest, status: &GetProofRequestStatusResponse) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(request.encoded_len() + status.encoded_len() + 20);
+    // Encoding into a Vec cannot fail
+    request.encode_length_delimited(&mut buf).expect("prost encode failed");
+    status.encode_length_delimited(&mut buf).expect("prost encode failed");
+    buf
+}
+
+fn decode_proof_request(mut bytes: &[u8]) -> Result<(ProofRequest, GetProofRequestStatusResponse), StorageError> {
+    let request = ProofRequest::decode_length_delimited(&mut bytes)?;
+    let status = GetProofRequestStatusResponse::decode_length_delimited(&mut bytes)?;
+    Ok((request, status))
+}
+
+/// Prover records are the up seconds followed by the stats
+fn encode_prover(stats: &ProverStats, up_secs: u64) -> Vec<u8> {
+    [up_secs.to_be_bytes().to_vec(), stats.encode_to_vec()].concat()
+}
+
+fn decode_prover(bytes: &[u8]) -> Result<(ProverStats, u64), StorageError> {
+    let (up_secs, stats) = bytes.split_at_checked(8).ok_or_else(|| anyhow::anyhow!("truncated prover record"))?;
+    let up_secs = u64::from_be_bytes(up_secs.try_into().expect("8 bytes"));
+    Ok((ProverStats::decode(stats)?, up_secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::SledBackend;
+
+    fn sample_request(id: u8) -> (ProofRequest, GetProofRequestStatusResponse) {
+        let request = ProofRequest {
+            request_id: vec![id; 32],
+            vk_hash: vec![7; 32],
+            fulfillment_status: FulfillmentStatus::Requested as i32,
+            created_at: id as u64,
+            ..Default::default()
+        };
+        let status = GetProofRequestStatusResponse {
+            fulfillment_status: FulfillmentStatus::Requested as i32,
+            deadline: 100,
+            ..Default::default()
+        };
+        (request, status)
+    }
+
+    #[test]
+    fn test_update_proof_request() {
+        let storage = Storage::default();
+        let (request, status) = sample_request(1);
+        storage.put_proof_request(&request, &status).unwrap();
+
+        storage
+            .update_proof_request(&request.request_id, |req, status| {
+                req.fulfillment_status = FulfillmentStatus::Fulfilled as i32;
+                status.fulfillment_status = FulfillmentStatus::Fulfilled as i32;
+                Ok::<_, StorageError>(())
+            })
+            .unwrap();
+        let (stored, stored_status) = storage.get_proof_request(&request.request_id).unwrap().unwrap();
+        assert_eq!(stored.fulfillment_status, FulfillmentStatus::Fulfilled as i32);
+        assert_eq!(stored_status.deadline, 100);
+
+        let missing = storage.update_proof_request(&[9; 32], |_, _| Ok::<_, StorageError>(()));
+        assert!(matches!(missing, Err(StorageError::NotFound)));
+    }
+
+    #[test]
+    fn test_nonces_are_monotonic() {
+        let storage = Storage::default();
+        let address = [1; 20];
+        assert_eq!(storage.next_nonce(&address).unwrap(), 0);
+        assert!(storage.use_nonce(&address, 0).unwrap());
+        // Replayed nonce
+        assert!(!storage.use_nonce(&address, 0).unwrap());
+        assert!(storage.use_nonce(&address, 5).unwrap());
+        assert!(!storage.use_nonce(&address, 3).unwrap());
+        assert_eq!(storage.next_nonce(&address).unwrap(), 6);
+        // Only the last nonce used can be given back
+        assert!(!storage.release_nonce(&address, 0).unwrap());
+        assert!(storage.release_nonce(&address, 5).unwrap());
+        assert!(storage.use_nonce(&address, 5).unwrap());
+        assert_eq!(storage.next_nonce(&[2; 20]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_delegations_replace_previous_delegate() {
+        let storage = Storage::default();
+        let (owner, hot, other) = (vec![1; 20], vec![2; 20], vec![3; 20]);
+        assert!(storage.put_delegation(&Delegation { owner: owner.clone(), delegate: hot.clone(), created_at: 1 }).unwrap());
+        assert_eq!(storage.get_delegation_owner(&hot).unwrap(), Some(owner.clone()));
+
+        // A delegate acts for a single owner
+        assert!(!storage.put_delegation(&Delegation { owner: other.clone(), delegate: hot.clone(), created_at: 2 }).unwrap());
+
+        assert!(storage.put_delegation(&Delegation { owner: owner.clone(), delegate: other.clone(), created_at: 3 }).unwrap());
+        assert_eq!(storage.get_delegation_owner(&hot).unwrap(), None);
+        assert_eq!(storage.get_delegation_owner(&other).unwrap(), Some(owner.clone()));
+        assert_eq!(storage.get_delegation(&owner).unwrap().unwrap().delegate, other);
+    }
+
+    #[test]
+    fn test_sled_backend_reloads_records() {
+        let dir = std::env::temp_dir().join(format!("spn_coordinator_storage_{}", hex::encode(rand::random::<[u8; 8]>())));
+        let (request, status) = sample_request(2);
+        let program = Program { vk_hash: vec![7; 32], program_uri: "uri".to_string(), ..Default::default() };
+        {
+            let backend = Arc::new(SledBackend::open(&dir).unwrap());
+            let storage = Storage::new(backend.clone());
+            storage.put_proof_request(&request, &status).unwrap();
+            storage.put_program(&program).unwrap();
+            backend.flush().unwrap();
+        }
+
+        // sled releases its file lock once its background flusher has stopped
+        let backend = (0..50)
+            .find_map(|_| SledBackend::open(&dir).ok().or_else(|| {
+                std::thread::sleep(std::time::Duration::from_millis(20));
+                None
+            }))
+            .unwrap();
+        let storage = Storage::new(Arc::new(backend));
+        assert_eq!(storage.get_proof_request(&request.request_id).unwrap(), Some((request, status)));
+        assert_eq!(storage.programs().unwrap(), vec![program]);
+        assert_eq!(storage.count_proof_requests(&ProofRequestIndex::FulfillmentStatus(FulfillmentStatus::Requested as i32)).unwrap(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_sled_batches_write_every_tree() {
+        let dir = std::env::temp_dir().join(format!("spn_coordinator_storage_{}", hex::encode(rand::random::<[u8; 8]>())));
+        let storage = Storage::new(Arc::new(SledBackend::open(&dir).unwrap()));
+        let (request, status) = sample_request(3);
+        storage.put_proof_request(&request, &status).unwrap();
+
+        let bid = BidHistory { bidder: vec![9; 20], amount: "1".to_string(), ..Default::default() };
+        storage
+            .update_proof_request_with_writes(&request.request_id, |request, status, writes| {
+                request.fulfillment_status = FulfillmentStatus::Assigned as i32;
+                request.fulfiller = Some(vec![9; 20]);
+                status.fulfillment_status = request.fulfillment_status;
+                writes.put_bid(&request.request_id, &bid);
+                writes.put_assigned_at(&request.request_id, 5);
+                Ok::<_, StorageError>(())
+            })
+            .unwrap();
+        assert_eq!(storage.bids(&request.request_id).unwrap(), vec![bid]);
+        assert_eq!(storage.get_assigned_at(&request.request_id).unwrap(), Some(5));
+        assert_eq!(storage.count_proof_requests(&ProofRequestIndex::AssignedTo(vec![9; 20])).unwrap(), 1);
+        assert_eq!(storage.count_proof_requests(&ProofRequestIndex::FulfillmentStatus(FulfillmentStatus::Requested as i32)).unwrap(), 0);
+
+        // Nothing is written when the logs can't be applied
+        let logs = vec![BalanceLog { address: vec![1; 20], amount: "-1".to_string(), ..Default::default() }];
+        let settled = storage.update_proof_request_with_balance_logs(&request.request_id, &[], |request, _| {
+            request.refund_amount = Some("1".to_string());
+            Ok::<_, StorageError>(((), logs))
+        });
+        assert!(settled.unwrap().is_none());
+        assert_eq!(storage.get_proof_request(&request.request_id).unwrap().unwrap().0.refund_amount, None);
+        assert!(storage.balance_logs().unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}